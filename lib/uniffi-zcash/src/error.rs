@@ -80,6 +80,11 @@ pub enum ZcashError {
     #[error("Change required error: {amount}")]
     ChangeRequiredError { amount: u64 },
 
+    #[error("Memo error occurred: {error}")]
+    MemoError {
+        error: zcash_primitives::memo::Error,
+    },
+
     #[error("IO error occurred: {error:?}")]
     IOError { error: std::io::Error },
 
@@ -227,6 +232,12 @@ fn from(error: BalanceError) -> Self {
     }
 }
 
+impl From<zcash_primitives::memo::Error> for ZcashError {
+    fn from(error: zcash_primitives::memo::Error) -> Self {
+        ZcashError::MemoError { error }
+    }
+}
+
 impl From<std::io::Error> for ZcashError {
     fn from(error: std::io::Error) -> Self {
         ZcashError::IOError { error }
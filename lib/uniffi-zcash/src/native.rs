@@ -26,13 +26,15 @@
     decrypt_and_store_transaction, scan_cached_blocks, shield_transparent_funds_main_fixed,
     shield_transparent_funds_main_zip317, shield_transparent_funds_test_fixed,
     shield_transparent_funds_test_zip317, spend_main_fixed, spend_main_zip317, spend_test_fixed,
-    spend_test_zip317, TupleTargetAndAnchorHeight, ZcashAccountId, ZcashAmount, ZcashBlockHeight,
-    ZcashBlockMeta, ZcashConsensusParameters, ZcashDustOutputPolicy, ZcashError, ZcashFixedFeeRule,
-    ZcashFixedSingleOutputChangeStrategy, ZcashFsBlockDb, ZcashKeysEra, ZcashLocalTxProver,
-    ZcashMainFixedGreedyInputSelector, ZcashMainZip317GreedyInputSelector, ZcashMemo,
-    ZcashMemoBytes, ZcashNonNegativeAmount, ZcashNoteId, ZcashOutPoint, ZcashOvkPolicy,
-    ZcashPayment, ZcashRecipientAddress, ZcashResult, ZcashScanRange, ZcashScript,
-    ZcashShieldedProtocol, ZcashTestFixedGreedyInputSelector, ZcashTestZip317GreedyInputSelector,
+    spend_test_zip317, spend_with_tex_main_fixed, spend_with_tex_main_zip317,
+    spend_with_tex_test_fixed, spend_with_tex_test_zip317, TupleTargetAndAnchorHeight,
+    ZcashAccountId, ZcashAmount, ZcashBlockHeight, ZcashBlockMeta, ZcashConsensusParameters,
+    ZcashDustOutputPolicy, ZcashError, ZcashFixedFeeRule, ZcashFixedSingleOutputChangeStrategy,
+    ZcashFsBlockDb, ZcashKeysEra, ZcashLocalTxProver, ZcashMainFixedGreedyInputSelector,
+    ZcashMainZip317GreedyInputSelector, ZcashMemo, ZcashMemoBytes, ZcashNonNegativeAmount,
+    ZcashNoteId, ZcashOutPoint, ZcashOvkPolicy, ZcashPayment, ZcashReceiverType,
+    ZcashRecipientAddress, ZcashResult, ZcashScanRange, ZcashScript, ZcashShieldedProtocol,
+    ZcashTestFixedGreedyInputSelector, ZcashTestZip317GreedyInputSelector, ZcashTexAddress,
     ZcashTransaction, ZcashTransactionRequest, ZcashTransparentAddress, ZcashTxId, ZcashTxOut,
     ZcashUnifiedAddress, ZcashUnifiedSpendingKey, ZcashWalletDb, ZcashWalletTransparentOutput,
     ZcashZip317FeeRule, ZcashZip317SingleOutputChangeStrategy,
@@ -295,15 +297,14 @@ pub fn get_memo_as_utf8(
             ZcashShieldedProtocol::Sapling,
             output_index as u16,
         )))
-        .map_err(|e| format_err!("An error occurred retrieving the memo, {}", e))
-        .map(|memo| match memo {
-            ZcashMemo::Empty => "".to_string(),
-            ZcashMemo::Text { v } => v,
-            ZcashMemo::Future { v } => format!("Not supported Memo::Future({:?})", v),
-            ZcashMemo::Arbitrary { v } => format!("Not supported Memo::Arbitrary({:?})", v),
-        })
         .map_err(|e| ZcashError::Message {
-            error: format_err!("some err {}", e).to_string(),
+            error: format_err!("An error occurred retrieving the memo, {}", e).to_string(),
+        })
+        .and_then(|memo| match memo {
+            ZcashMemo::Empty => Ok("".to_string()),
+            ZcashMemo::Text { v } => Ok(v),
+            ZcashMemo::Future { .. } => Err("The memo is in a reserved format, not text".into()),
+            ZcashMemo::Arbitrary { .. } => Err("The memo contains arbitrary data, not text".into()),
         })
 
     // NOTE into_raw was here
@@ -518,6 +519,9 @@ pub fn create_to_address(
             ZcashMemoBytes::new(memo_bytes).ok()
         }
         ZcashRecipientAddress::Transparent(_) => None,
+        ZcashRecipientAddress::Tex(_) => {
+            return Err("TEX addresses must be paid through create_to_tex_address".into())
+        }
     };
 
     let prover = ZcashLocalTxProver::new(&spend_params, &output_params);
@@ -630,6 +634,100 @@ pub fn create_to_address(
     }
 }
 
+/// Sends `value` to the TEX address `addr_to`, as defined in ZIP 320, and returns the ids
+/// of the created transactions in the order they must be broadcast.
+#[allow(clippy::too_many_arguments)]
+pub fn create_to_tex_address(
+    db_data: String,
+    usk: ZcashUnifiedSpendingKey,
+    addr_to: String,
+    value: u64,
+    spend_params: String,
+    output_params: String,
+    params: ZcashConsensusParameters,
+    use_zip317_fees: bool,
+) -> ZcashResult<Vec<ZcashTxId>> {
+    let db_data = wallet_db(params, db_data)?;
+    let to = ZcashTexAddress::decode(params, &addr_to)?;
+    let prover = ZcashLocalTxProver::new(&spend_params, &output_params);
+
+    let request = ZcashTransactionRequest::new(vec![ZcashPayment {
+        recipient_address: ZcashRecipientAddress::Tex(Arc::new(to)).into(),
+        amount: ZcashAmount::new(i64::try_from(value)?)?.into(),
+        memo: None,
+        label: None,
+        message: None,
+        other_params: vec![],
+    }])
+    .map_err(|e| ZcashError::Message {
+        error: format!("Error creating transaction request: {:?}", e),
+    })?;
+
+    let fixed_rule = ZcashFixedFeeRule::standard().into();
+    let zip317_rule = ZcashZip317FeeRule::standard().into();
+    let dust_policy = ZcashDustOutputPolicy::default().into();
+
+    let txids = match (params, use_zip317_fees) {
+        (ZcashConsensusParameters::MainNetwork, true) => spend_with_tex_main_zip317(
+            Arc::new(db_data),
+            params,
+            Arc::new(prover),
+            Arc::new(ZcashMainZip317GreedyInputSelector::new(
+                ZcashZip317SingleOutputChangeStrategy::new(zip317_rule).into(),
+                dust_policy,
+            )),
+            Arc::new(usk),
+            Arc::new(request),
+            ZcashOvkPolicy::Sender,
+            ANCHOR_OFFSET,
+        ),
+        (ZcashConsensusParameters::MainNetwork, false) => spend_with_tex_main_fixed(
+            Arc::new(db_data),
+            params,
+            Arc::new(prover),
+            Arc::new(ZcashMainFixedGreedyInputSelector::new(
+                ZcashFixedSingleOutputChangeStrategy::new(fixed_rule).into(),
+                dust_policy,
+            )),
+            Arc::new(usk),
+            Arc::new(request),
+            ZcashOvkPolicy::Sender,
+            ANCHOR_OFFSET,
+        ),
+        (ZcashConsensusParameters::TestNetwork, true) => spend_with_tex_test_zip317(
+            Arc::new(db_data),
+            params,
+            Arc::new(prover),
+            Arc::new(ZcashTestZip317GreedyInputSelector::new(
+                ZcashZip317SingleOutputChangeStrategy::new(zip317_rule).into(),
+                dust_policy,
+            )),
+            Arc::new(usk),
+            Arc::new(request),
+            ZcashOvkPolicy::Sender,
+            ANCHOR_OFFSET,
+        ),
+        (ZcashConsensusParameters::TestNetwork, false) => spend_with_tex_test_fixed(
+            Arc::new(db_data),
+            params,
+            Arc::new(prover),
+            Arc::new(ZcashTestFixedGreedyInputSelector::new(
+                ZcashFixedSingleOutputChangeStrategy::new(fixed_rule).into(),
+                dust_policy,
+            )),
+            Arc::new(usk),
+            Arc::new(request),
+            ZcashOvkPolicy::Sender,
+            ANCHOR_OFFSET,
+        ),
+    }
+    .map_err(|e| ZcashError::Message {
+        error: format!("Error while creating transaction: {}", e),
+    })?;
+
+    Ok(txids.into_iter().map(|x| *x).collect())
+}
+
 pub fn shield_to_address(
     db_data: String,
     usk: ZcashUnifiedSpendingKey,
@@ -800,7 +898,9 @@ pub fn is_valid_shielded_address(
     match ZcashRecipientAddress::decode(params, &addr) {
         Ok(addr) => match addr {
             ZcashRecipientAddress::Shielded(_) => Ok(true),
-            ZcashRecipientAddress::Transparent(_) | ZcashRecipientAddress::Unified(_) => Ok(false),
+            ZcashRecipientAddress::Transparent(_)
+            | ZcashRecipientAddress::Unified(_)
+            | ZcashRecipientAddress::Tex(_) => Ok(false),
         },
         Err(_) => Err(ZcashError::Message {
             error: "Address is for the wrong network".to_string(),
@@ -815,7 +915,23 @@ pub fn is_valid_transparent_address(
     match ZcashRecipientAddress::decode(params, &addr) {
         Ok(addr) => match addr {
             ZcashRecipientAddress::Transparent(_) => Ok(true),
-            ZcashRecipientAddress::Shielded(_) | ZcashRecipientAddress::Unified(_) => Ok(false),
+            ZcashRecipientAddress::Shielded(_)
+            | ZcashRecipientAddress::Unified(_)
+            | ZcashRecipientAddress::Tex(_) => Ok(false),
+        },
+        Err(_) => Err(ZcashError::Message {
+            error: "Address is for the wrong network".to_string(),
+        }),
+    }
+}
+
+pub fn is_valid_tex_address(addr: String, params: ZcashConsensusParameters) -> ZcashResult<bool> {
+    match ZcashRecipientAddress::decode(params, &addr) {
+        Ok(addr) => match addr {
+            ZcashRecipientAddress::Tex(_) => Ok(true),
+            ZcashRecipientAddress::Shielded(_)
+            | ZcashRecipientAddress::Transparent(_)
+            | ZcashRecipientAddress::Unified(_) => Ok(false),
         },
         Err(_) => Err(ZcashError::Message {
             error: "Address is for the wrong network".to_string(),
@@ -830,7 +946,9 @@ pub fn is_valid_unified_address(
     match ZcashRecipientAddress::decode(params, &addr) {
         Ok(addr) => match addr {
             ZcashRecipientAddress::Unified(_) => Ok(true),
-            ZcashRecipientAddress::Shielded(_) | ZcashRecipientAddress::Transparent(_) => Ok(false),
+            ZcashRecipientAddress::Shielded(_)
+            | ZcashRecipientAddress::Transparent(_)
+            | ZcashRecipientAddress::Tex(_) => Ok(false),
         },
         Err(_) => Err(ZcashError::Message {
             error: "Address is for the wrong network".to_string(),
@@ -1024,33 +1142,29 @@ pub fn get_sapling_receiver_for_unified_address(
     }
 }
 
-// original!
-// DOESN'T HAVE a string representation yet
-// pub fn get_orchard_receiver_for_unified_address(addr: String, params: ZcashConsensusParameters) -> ZcashResult<String> {
-//         let ua = match ZcashUnifiedAddress::decode(params, &addr) {
-//             Err(e) => return Err(ZcashError::Message {error: format!("Invalid Zcash address: {}", e)}),
-//             Ok(ua) => ua
-//         };
+/// Orchard receivers have no encoding of their own, so the receiver is returned as a
+/// Unified Address containing only the Orchard receiver.
+pub fn get_orchard_receiver_for_unified_address(
+    addr: String,
+    params: ZcashConsensusParameters,
+) -> ZcashResult<String> {
+    let ua = match ZcashUnifiedAddress::decode(params, &addr) {
+        Err(e) => {
+            return Err(ZcashError::Message {
+                error: format!("Invalid Zcash address: {}", e),
+            })
+        }
+        Ok(ua) => ua,
+    };
 
-//         if let Some(taddr) = ua.orchard() {
-//             // let Ok(_) =
-//             //     if (*taddr).is_public_key() {
-//             //         Ok("TODO")
-//             //             // ZcashAddress::from_transparent_p2pkh(network, *data)
-//             //     } else if (*taddr).is_script() {
-//             //         Ok("TODO")
-//             //             // ZcashAddress::from_transparent_p2sh(network, *data)
-//             //     } else {
-//             //         Ok("TODO")
-//             //     }
-
-//             Ok((*taddr).encode(params))
-//         } else {
-//             Err(ZcashError::Message {
-//                 error: "Unified Address doesn't contain a orchard receiver".to_string(),
-//             })
-//         }
-// }
+    if ua.orchard().is_some() {
+        Ok(ua.subset(vec![ZcashReceiverType::Orchard])?.encode(params))
+    } else {
+        Err(ZcashError::Message {
+            error: "Unified Address doesn't contain an orchard receiver".to_string(),
+        })
+    }
+}
 
 // NOTE cannot translate until I see what's the object type
 
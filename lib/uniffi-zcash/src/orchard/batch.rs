@@ -0,0 +1,60 @@
+use std::sync::{Arc, RwLock};
+
+use hdwallet::rand_core::OsRng;
+use orchard::bundle::BatchValidator;
+
+use crate::{utils::cast_slice, ZcashOrchardBundle, ZcashResult, ZcashVerifyingKey};
+
+/// Validates the proofs and signatures of many Orchard bundles together.
+pub struct ZcashOrchardBatchValidator {
+    bundles: RwLock<Vec<(Arc<ZcashOrchardBundle>, [u8; 32])>>,
+}
+
+impl ZcashOrchardBatchValidator {
+    pub fn new() -> Self {
+        Self {
+            bundles: RwLock::new(Vec::new()),
+        }
+    }
+
+    /// Adds a bundle to the batch, along with the signature hash of its transaction.
+    pub fn add_bundle(&self, bundle: Arc<ZcashOrchardBundle>, sighash: Vec<u8>) -> ZcashResult<()> {
+        let sighash = cast_slice(&sighash)?;
+        self.bundles.write().unwrap().push((bundle, sighash));
+        Ok(())
+    }
+
+    /// Validates all the bundles added so far.
+    ///
+    /// Returns the indices, in order of addition, of the bundles that are invalid. When the
+    /// batch fails, each bundle is validated on its own to find the culprits.
+    pub fn validate(&self, key: Arc<ZcashVerifyingKey>) -> Vec<u32> {
+        let bundles = self.bundles.read().unwrap();
+
+        let mut batch = BatchValidator::new();
+        for (bundle, sighash) in bundles.iter() {
+            batch.add_bundle(&bundle.0, *sighash);
+        }
+
+        if batch.validate(&key.0, OsRng) {
+            return Vec::new();
+        }
+
+        bundles
+            .iter()
+            .enumerate()
+            .filter(|(_, (bundle, sighash))| {
+                let mut single = BatchValidator::new();
+                single.add_bundle(&bundle.0, *sighash);
+                !single.validate(&key.0, OsRng)
+            })
+            .map(|(index, _)| index as u32)
+            .collect()
+    }
+}
+
+impl Default for ZcashOrchardBatchValidator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
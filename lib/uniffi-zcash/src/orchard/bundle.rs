@@ -9,12 +9,12 @@
 
 use crate::{
     ZcashAmount, ZcashAnchor, ZcashError, ZcashOrchardAction, ZcashOrchardAddress,
-    ZcashOrchardIncomingViewingKey, ZcashOrchardNote, ZcashOrchardOutgoingViewingKey, ZcashResult,
-    ZcashVerifyingKey,
+    ZcashOrchardIncomingViewingKey, ZcashOrchardNote, ZcashOrchardNullifier,
+    ZcashOrchardOutgoingViewingKey, ZcashResult, ZcashVerifyingKey,
 };
 
 /// A bundle of actions to be applied to the ledger.
-pub struct ZcashOrchardBundle(Bundle<Authorized, Amount>);
+pub struct ZcashOrchardBundle(pub(crate) Bundle<Authorized, Amount>);
 
 impl ZcashOrchardBundle {
     /// The list of actions that make up this bundle.
@@ -51,6 +51,29 @@ pub fn verify_proof(&self, key: Arc<ZcashVerifyingKey>) -> ZcashResult<()> {
             .or(Err("Error verifying proof".into()))
     }
 
+    /// Matches the nullifiers revealed by the actions of this bundle against the
+    /// nullifiers of known notes, and returns the index of each matching action along
+    /// with the index of the note it spends.
+    pub fn match_spends(
+        &self,
+        known_nullifiers: Vec<Arc<ZcashOrchardNullifier>>,
+    ) -> Vec<ZcashOrchardSpendMatch> {
+        self.0
+            .actions()
+            .iter()
+            .enumerate()
+            .filter_map(|(action_idx, action)| {
+                known_nullifiers
+                    .iter()
+                    .position(|nf| *action.nullifier() == nf.as_ref().into())
+                    .map(|note_idx| ZcashOrchardSpendMatch {
+                        action_idx: action_idx as u64,
+                        note_idx: note_idx as u64,
+                    })
+            })
+            .collect()
+    }
+
     /// Performs trial decryption of the action at `action_idx` in the bundle with the
     /// specified incoming viewing key, and returns the decrypted note plaintext
     /// contents if successful.
@@ -196,6 +219,11 @@ fn try_from(
     }
 }
 
+pub struct ZcashOrchardSpendMatch {
+    pub action_idx: u64,
+    pub note_idx: u64,
+}
+
 /// Orchard-specific flags.
 pub struct ZcashOrchardFlags(Flags);
 
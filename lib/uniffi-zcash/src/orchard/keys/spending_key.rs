@@ -1,6 +1,9 @@
 use std::sync::Arc;
 
-use orchard::keys::SpendingKey;
+use group::ff::PrimeField;
+use orchard::keys::{SpendAuthorizingKey, SpendingKey};
+use pasta_curves::pallas;
+use rand::rngs::OsRng;
 
 use crate::{utils::cast_slice, ZcashError, ZcashOrchardFullViewingKey, ZcashResult};
 
@@ -33,6 +36,22 @@ pub fn from_zip32_seed(seed: Vec<u8>, coin_type: u32, account: u32) -> ZcashResu
             .map_err(ZcashError::from)?;
         Ok(key.into())
     }
+
+    /// Creates the spend authorization signature of an Orchard action, given the shielded
+    /// signature hash of the transaction and the randomizer `alpha` of its spend.
+    ///
+    /// This is what a [`crate::ZcashExternalSigner`] holding the key returns from
+    /// `sign_orchard`.
+    pub fn sign_spend_auth(&self, sighash: Vec<u8>, alpha: Vec<u8>) -> ZcashResult<Vec<u8>> {
+        let sighash: [u8; 32] = cast_slice(&sighash)?;
+        let alpha = Option::from(pallas::Scalar::from_repr(cast_slice(&alpha)?))
+            .ok_or("Invalid Orchard randomizer")?;
+
+        let signature = SpendAuthorizingKey::from(&self.0)
+            .randomize(&alpha)
+            .sign(OsRng, &sighash);
+        Ok(<[u8; 64]>::from(&signature).to_vec())
+    }
 }
 
 impl From<SpendingKey> for ZcashOrchardSpendingKey {
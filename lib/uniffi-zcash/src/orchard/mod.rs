@@ -16,6 +16,9 @@
 mod value;
 pub use self::value::*;
 
+mod batch;
+pub use self::batch::*;
+
 mod bundle;
 pub use self::bundle::*;
 
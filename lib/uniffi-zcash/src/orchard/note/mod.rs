@@ -8,7 +8,10 @@
     Note,
 };
 
-use crate::{utils::cast_slice, ZcashOrchardAddress, ZcashOrchardNoteValue, ZcashResult};
+use crate::{
+    utils::cast_slice, ZcashOrchardAddress, ZcashOrchardFullViewingKey, ZcashOrchardNoteValue,
+    ZcashResult,
+};
 
 /// A discrete amount of funds received by an address.
 pub struct ZcashOrchardNote(Note);
@@ -59,6 +62,11 @@ pub fn value(&self) -> Arc<ZcashOrchardNoteValue> {
     pub fn commitment(&self) -> Arc<ZcashOrchardNoteCommitment> {
         Arc::new(self.0.commitment().into())
     }
+
+    /// Derives the nullifier for this note.
+    pub fn nullifier(&self, fvk: Arc<ZcashOrchardFullViewingKey>) -> Arc<ZcashOrchardNullifier> {
+        Arc::new(self.0.nullifier(&fvk.as_ref().into()).into())
+    }
 }
 
 impl From<Note> for ZcashOrchardNote {
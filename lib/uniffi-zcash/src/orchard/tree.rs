@@ -1,14 +1,22 @@
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
 
 use crate::ZcashExtractedNoteCommitment;
 use crate::{utils::cast_slice, ZcashResult};
 
+use incrementalmerkletree::frontier::CommitmentTree;
+use incrementalmerkletree::witness::IncrementalWitness;
 use orchard::tree::{MerkleHashOrchard, MerklePath};
+use zcash_primitives::merkle_tree::{
+    read_commitment_tree, read_incremental_witness, write_commitment_tree,
+    write_incremental_witness,
+};
 
 use orchard::Anchor;
 
 use derive_more::{From, Into};
 
+const DEPTH: u8 = 32;
+
 #[derive(From, Into)]
 pub struct ZcashAnchor(Anchor);
 
@@ -70,6 +78,30 @@ pub fn from_parts(
     pub fn root(&self, cmx: Arc<ZcashExtractedNoteCommitment>) -> Arc<ZcashAnchor> {
         Arc::new(self.inner.root((*cmx.as_ref()).clone().into()).into())
     }
+
+    pub(crate) fn position(&self) -> u32 {
+        self.position
+    }
+
+    pub(crate) fn auth_path(&self) -> [MerkleHashOrchard; 32] {
+        self.auth_path
+    }
+}
+
+impl From<incrementalmerkletree::MerklePath<MerkleHashOrchard, DEPTH>> for ZcashOrchardMerklePath {
+    fn from(path: incrementalmerkletree::MerklePath<MerkleHashOrchard, DEPTH>) -> Self {
+        // A position in a tree of depth 32 always fits in 32 bits.
+        let position = u64::from(path.position()) as u32;
+        let auth_path: [MerkleHashOrchard; 32] = path
+            .path_elems()
+            .try_into()
+            .expect("Merkle path has the depth of the tree");
+        ZcashOrchardMerklePath {
+            inner: MerklePath::from_parts(position, auth_path),
+            position,
+            auth_path,
+        }
+    }
 }
 
 impl From<&ZcashOrchardMerklePath> for MerklePath {
@@ -102,3 +134,117 @@ pub fn to_bytes(&self) -> Vec<u8> {
         self.0.to_bytes().to_vec()
     }
 }
+
+/// An Orchard note commitment tree, in the legacy `CommitmentTree` representation.
+pub struct ZcashOrchardCommitmentTree(RwLock<CommitmentTree<MerkleHashOrchard, DEPTH>>);
+
+impl ZcashOrchardCommitmentTree {
+    /// Creates an empty tree.
+    pub fn empty() -> Self {
+        ZcashOrchardCommitmentTree(RwLock::new(CommitmentTree::empty()))
+    }
+
+    /// Reads a tree in the legacy `CommitmentTree` encoding used by zcashd.
+    pub fn from_bytes(data: &[u8]) -> ZcashResult<Self> {
+        let tree = read_commitment_tree::<MerkleHashOrchard, _, DEPTH>(data)?;
+        Ok(tree.into())
+    }
+
+    /// Writes the tree in the legacy `CommitmentTree` encoding used by zcashd.
+    pub fn to_bytes(&self) -> ZcashResult<Vec<u8>> {
+        let mut data = Vec::new();
+        write_commitment_tree(&*self.0.read().unwrap(), &mut data)?;
+        Ok(data)
+    }
+
+    /// Returns the number of leaf nodes in the tree.
+    pub fn size(&self) -> u64 {
+        self.0.read().unwrap().size() as u64
+    }
+
+    /// Returns the current root of the tree.
+    pub fn root(&self) -> Arc<ZcashAnchor> {
+        Arc::new(Anchor::from(self.0.read().unwrap().root()).into())
+    }
+
+    /// Adds a leaf node to the tree.
+    ///
+    /// Returns an error if the tree is full.
+    pub fn append(&self, node: Arc<ZcashOrchardMerkleHash>) -> ZcashResult<()> {
+        match self.0.write().unwrap().append(node.0) {
+            Ok(_) => Ok(()),
+            // Underlying implementation throws an Err(()) when tree is full.
+            // Changing behavior here.
+            Err(_) => Err("Tree is full".into()),
+        }
+    }
+}
+
+impl From<CommitmentTree<MerkleHashOrchard, DEPTH>> for ZcashOrchardCommitmentTree {
+    fn from(value: CommitmentTree<MerkleHashOrchard, DEPTH>) -> Self {
+        ZcashOrchardCommitmentTree(RwLock::new(value))
+    }
+}
+
+impl From<&ZcashOrchardCommitmentTree> for CommitmentTree<MerkleHashOrchard, DEPTH> {
+    fn from(value: &ZcashOrchardCommitmentTree) -> Self {
+        value.0.read().unwrap().clone()
+    }
+}
+
+/// A witness to the path from a leaf of an Orchard note commitment tree to its root.
+pub struct ZcashOrchardIncrementalWitness(RwLock<IncrementalWitness<MerkleHashOrchard, DEPTH>>);
+
+impl ZcashOrchardIncrementalWitness {
+    /// Creates an `IncrementalWitness` for the most recent commitment added to the given
+    /// tree.
+    pub fn from_tree(tree: Arc<ZcashOrchardCommitmentTree>) -> Self {
+        IncrementalWitness::from_tree((&(*tree)).into()).into()
+    }
+
+    /// Reads a witness in the legacy `IncrementalWitness` encoding used by zcashd.
+    pub fn from_bytes(data: &[u8]) -> ZcashResult<Self> {
+        let witness = read_incremental_witness::<MerkleHashOrchard, _, DEPTH>(data)?;
+        Ok(witness.into())
+    }
+
+    /// Writes the witness in the legacy `IncrementalWitness` encoding used by zcashd.
+    pub fn to_bytes(&self) -> ZcashResult<Vec<u8>> {
+        let mut data = Vec::new();
+        write_incremental_witness(&*self.0.read().unwrap(), &mut data)?;
+        Ok(data)
+    }
+
+    /// Returns the position of the witnessed leaf node in the commitment tree.
+    pub fn witnessed_position(&self) -> u64 {
+        self.0.read().unwrap().witnessed_position().into()
+    }
+
+    /// Returns the current root of the tree corresponding to the witness.
+    pub fn root(&self) -> Arc<ZcashAnchor> {
+        Arc::new(Anchor::from(self.0.read().unwrap().root()).into())
+    }
+
+    /// Tracks a leaf node that has been added to the underlying tree.
+    ///
+    /// Returns an error if the tree is full.
+    pub fn append(&self, node: Arc<ZcashOrchardMerkleHash>) -> ZcashResult<()> {
+        match self.0.write().unwrap().append(node.0) {
+            Ok(_) => Ok(()),
+            // Underlying implementation throws an Err(()) when tree is full.
+            // Changing behavior here.
+            Err(_) => Err("Tree is full".into()),
+        }
+    }
+
+    /// Returns the current witness, or None if the tree is empty.
+    pub fn path(&self) -> Option<Arc<ZcashOrchardMerklePath>> {
+        self.0.read().unwrap().path().map(From::from).map(Arc::new)
+    }
+}
+
+impl From<IncrementalWitness<MerkleHashOrchard, DEPTH>> for ZcashOrchardIncrementalWitness {
+    fn from(value: IncrementalWitness<MerkleHashOrchard, DEPTH>) -> Self {
+        ZcashOrchardIncrementalWitness(RwLock::new(value))
+    }
+}
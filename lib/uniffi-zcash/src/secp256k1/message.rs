@@ -0,0 +1,99 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+use secp256k1::ecdsa::{RecoverableSignature, RecoveryId};
+use secp256k1::{Message, Secp256k1};
+use sha2::{Digest, Sha256};
+
+use crate::{SecpPublicKey, SecpSecretKey, ZcashError, ZcashResult};
+
+/// The prefix of signed messages, which prevents signing transactions by mistake.
+const MESSAGE_MAGIC: &str = "Zcash Signed Message:\n";
+
+/// The header byte of a compact signature is `27 + recovery id`, plus 4 when the signing
+/// key is compressed.
+const HEADER_BASE: u8 = 27;
+const HEADER_COMPRESSED: u8 = 4;
+
+impl SecpSecretKey {
+    /// Signs a message like the `signmessage` RPC method of `zcashd`.
+    ///
+    /// Returns the base64 encoding of the 65 bytes compact recoverable signature, made with
+    /// the compressed public key.
+    pub fn sign_message(&self, message: &str) -> String {
+        let (recovery_id, signature) = Secp256k1::signing_only()
+            .sign_ecdsa_recoverable(&message_hash(message), &self.into())
+            .serialize_compact();
+
+        let mut data = Vec::with_capacity(65);
+        data.push(HEADER_BASE + HEADER_COMPRESSED + recovery_id.to_i32() as u8);
+        data.extend_from_slice(&signature);
+        STANDARD.encode(data)
+    }
+}
+
+impl SecpPublicKey {
+    /// Recovers the public key that signed a message, given a signature in the format of the
+    /// `signmessage` RPC method of `zcashd`.
+    pub fn recover_from_message(message: &str, signature: &str) -> ZcashResult<Self> {
+        recover_message_signer(message, signature).map(|(key, _)| key)
+    }
+}
+
+/// Recovers the signer of a message, and whether the signature was made with the
+/// compressed public key.
+pub(crate) fn recover_message_signer(
+    message: &str,
+    signature: &str,
+) -> ZcashResult<(SecpPublicKey, bool)> {
+    let data = STANDARD
+        .decode(signature)
+        .map_err(|e| ZcashError::Message {
+            error: format!("Invalid signature encoding: {}", e),
+        })?;
+    if data.len() != 65 {
+        return Err(ZcashError::ArrayLengthMismatch {
+            expected: 65,
+            got: data.len() as u64,
+        });
+    }
+
+    let header = data[0]
+        .checked_sub(HEADER_BASE)
+        .filter(|header| *header < 2 * HEADER_COMPRESSED)
+        .ok_or("Invalid signature header")?;
+    let compressed = header >= HEADER_COMPRESSED;
+    let recovery_id = RecoveryId::from_i32((header % HEADER_COMPRESSED).into())?;
+    let signature = RecoverableSignature::from_compact(&data[1..], recovery_id)?;
+
+    let key = Secp256k1::verification_only().recover_ecdsa(&message_hash(message), &signature)?;
+    Ok((key.into(), compressed))
+}
+
+/// Hashes a message with its length-prefixed magic, using double SHA-256.
+fn message_hash(message: &str) -> Message {
+    let mut data = Vec::new();
+    write_compact_size(&mut data, MESSAGE_MAGIC.len());
+    data.extend_from_slice(MESSAGE_MAGIC.as_bytes());
+    write_compact_size(&mut data, message.len());
+    data.extend_from_slice(message.as_bytes());
+
+    let hash: [u8; 32] = Sha256::digest(Sha256::digest(&data)).into();
+    Message::from_slice(&hash).expect("A SHA-256 hash is 32 bytes long")
+}
+
+fn write_compact_size(data: &mut Vec<u8>, size: usize) {
+    match size {
+        0..=0xfc => data.push(size as u8),
+        0xfd..=0xffff => {
+            data.push(0xfd);
+            data.extend_from_slice(&(size as u16).to_le_bytes());
+        }
+        0x10000..=0xffff_ffff => {
+            data.push(0xfe);
+            data.extend_from_slice(&(size as u32).to_le_bytes());
+        }
+        _ => {
+            data.push(0xff);
+            data.extend_from_slice(&(size as u64).to_le_bytes());
+        }
+    }
+}
@@ -1,3 +1,9 @@
+mod message;
+pub(crate) use self::message::recover_message_signer;
+
+mod public_key;
+pub use self::public_key::*;
+
 use secp256k1::SecretKey;
 
 use crate::{ZcashError, ZcashResult};
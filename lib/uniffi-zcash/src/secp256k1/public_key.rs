@@ -0,0 +1,60 @@
+use std::sync::Arc;
+
+use ripemd::Ripemd160;
+use secp256k1::{PublicKey, Secp256k1};
+use sha2::{Digest, Sha256};
+use zcash_primitives::legacy::TransparentAddress;
+
+use crate::{SecpSecretKey, ZcashResult, ZcashTransparentAddress};
+
+pub struct SecpPublicKey(PublicKey);
+
+impl SecpPublicKey {
+    /// Parses a public key from its compressed (33 bytes) or uncompressed (65 bytes)
+    /// serialization.
+    pub fn new(data: Vec<u8>) -> ZcashResult<Self> {
+        Ok(PublicKey::from_slice(&data)?.into())
+    }
+
+    /// Computes the public key of a secret key.
+    pub fn from_secret_key(sk: Arc<SecpSecretKey>) -> Self {
+        PublicKey::from_secret_key(&Secp256k1::signing_only(), &sk.as_ref().into()).into()
+    }
+
+    /// Serializes the key in compressed form, as 33 bytes.
+    pub fn serialize(&self) -> Vec<u8> {
+        self.0.serialize().to_vec()
+    }
+
+    /// Serializes the key in uncompressed form, as 65 bytes.
+    pub fn serialize_uncompressed(&self) -> Vec<u8> {
+        self.0.serialize_uncompressed().to_vec()
+    }
+
+    /// Returns the P2PKH address of the compressed form of the key, as derived by wallets.
+    pub fn to_transparent_address(&self) -> Arc<ZcashTransparentAddress> {
+        Arc::new(self.p2pkh_address(true).into())
+    }
+
+    /// Returns the P2PKH address of the compressed or uncompressed form of the key.
+    pub(crate) fn p2pkh_address(&self, compressed: bool) -> TransparentAddress {
+        let hash = if compressed {
+            Ripemd160::digest(Sha256::digest(self.0.serialize()))
+        } else {
+            Ripemd160::digest(Sha256::digest(self.0.serialize_uncompressed()))
+        };
+        TransparentAddress::PublicKey(hash.into())
+    }
+}
+
+impl From<PublicKey> for SecpPublicKey {
+    fn from(inner: PublicKey) -> Self {
+        SecpPublicKey(inner)
+    }
+}
+
+impl From<&SecpPublicKey> for PublicKey {
+    fn from(value: &SecpPublicKey) -> Self {
+        value.0
+    }
+}
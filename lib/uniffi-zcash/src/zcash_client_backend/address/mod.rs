@@ -5,9 +5,15 @@
 
 use crate::{ZcashAccountId, ZcashDiversifierIndex};
 
+mod parsed_address;
+pub use self::parsed_address::*;
+
 mod recipient_address;
 pub use self::recipient_address::*;
 
+mod tex_address;
+pub use self::tex_address::*;
+
 mod unified_address;
 pub use self::unified_address::*;
 
@@ -0,0 +1,372 @@
+use std::fmt;
+use std::sync::Arc;
+
+use bech32::Variant;
+use zcash_address::unified::{self, Container, Receiver};
+use zcash_address::{ConversionError, Network, ParseError, TryFromAddress, ZcashAddress};
+use zcash_primitives::{
+    constants::{mainnet, regtest, testnet},
+    legacy::TransparentAddress,
+    sapling::PaymentAddress,
+};
+
+use crate::{
+    ZcashConsensusParameters, ZcashOrchardAddress, ZcashPaymentAddress, ZcashTexAddress,
+    ZcashTransparentAddress,
+};
+
+/// The network an address was encoded for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ZcashAddressNetwork {
+    Main,
+    Test,
+    Regtest,
+}
+
+impl From<Network> for ZcashAddressNetwork {
+    fn from(network: Network) -> Self {
+        match network {
+            Network::Main => ZcashAddressNetwork::Main,
+            Network::Test => ZcashAddressNetwork::Test,
+            Network::Regtest => ZcashAddressNetwork::Regtest,
+        }
+    }
+}
+
+impl From<ZcashAddressNetwork> for Network {
+    fn from(network: ZcashAddressNetwork) -> Self {
+        match network {
+            ZcashAddressNetwork::Main => Network::Main,
+            ZcashAddressNetwork::Test => Network::Test,
+            ZcashAddressNetwork::Regtest => Network::Regtest,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ZcashAddressKind {
+    Sprout,
+    Sapling,
+    P2pkh,
+    P2sh,
+    Unified,
+    /// A transparent-source-only address, defined in ZIP 320.
+    Tex,
+}
+
+/// A receiver of a Unified Address with a typecode unknown to this library.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ZcashUnknownReceiver {
+    pub typecode: u32,
+    pub data: Vec<u8>,
+}
+
+/// An address decoded without knowing its network in advance.
+pub struct ZcashParsedAddress {
+    network: ZcashAddressNetwork,
+    kind: ZcashAddressKind,
+    typecodes: Vec<u32>,
+    orchard: Option<Arc<ZcashOrchardAddress>>,
+    sapling: Option<Arc<ZcashPaymentAddress>>,
+    transparent: Option<Arc<ZcashTransparentAddress>>,
+    unknown: Vec<ZcashUnknownReceiver>,
+}
+
+impl ZcashParsedAddress {
+    fn new(network: Network, kind: ZcashAddressKind) -> Self {
+        ZcashParsedAddress {
+            network: network.into(),
+            kind,
+            typecodes: vec![],
+            orchard: None,
+            sapling: None,
+            transparent: None,
+            unknown: vec![],
+        }
+    }
+
+    pub fn network(&self) -> ZcashAddressNetwork {
+        self.network
+    }
+
+    pub fn kind(&self) -> ZcashAddressKind {
+        self.kind
+    }
+
+    /// Returns the typecodes of the receivers of the address, in ascending order.
+    ///
+    /// Addresses other than Unified Addresses have the typecode of their only receiver, if any:
+    /// Sprout addresses have none, and TEX addresses have the P2PKH typecode.
+    pub fn typecodes(&self) -> Vec<u32> {
+        self.typecodes.clone()
+    }
+
+    pub fn orchard(&self) -> Option<Arc<ZcashOrchardAddress>> {
+        self.orchard.clone()
+    }
+
+    pub fn sapling(&self) -> Option<Arc<ZcashPaymentAddress>> {
+        self.sapling.clone()
+    }
+
+    /// Returns the P2PKH or P2SH receiver of the address.
+    pub fn transparent(&self) -> Option<Arc<ZcashTransparentAddress>> {
+        self.transparent.clone()
+    }
+
+    pub fn unknown_receivers(&self) -> Vec<ZcashUnknownReceiver> {
+        self.unknown.clone()
+    }
+
+    fn add_receiver(&mut self, receiver: &Receiver) -> ZcashAddressParseResult<()> {
+        match receiver {
+            Receiver::Orchard(data) => {
+                let address = ZcashOrchardAddress::from_raw_address_bytes(data.to_vec())
+                    .map_err(|_| ZcashAddressParseError::InvalidReceiver { typecode: 3 })?;
+                self.orchard = Some(Arc::new(address));
+            }
+            Receiver::Sapling(data) => self.sapling = Some(sapling_receiver(data)?),
+            Receiver::P2pkh(data) => {
+                self.transparent = Some(Arc::new(TransparentAddress::PublicKey(*data).into()))
+            }
+            Receiver::P2sh(data) => {
+                self.transparent = Some(Arc::new(TransparentAddress::Script(*data).into()))
+            }
+            Receiver::Unknown { typecode, data } => self.unknown.push(ZcashUnknownReceiver {
+                typecode: *typecode,
+                data: data.clone(),
+            }),
+        }
+        self.typecodes.push(receiver_typecode(receiver));
+        Ok(())
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ZcashAddressParseError {
+    /// The checksum of a Bech32, Bech32m or Base58Check encoding does not match.
+    InvalidChecksum,
+    /// The human-readable part of a Bech32 or Bech32m encoding is not a Zcash one.
+    UnknownHrp { hrp: String },
+    /// The version prefix of a Base58Check encoding is not a Zcash one.
+    UnknownPrefix { prefix: Vec<u8> },
+    /// A Unified Address item has a typecode above the maximum allowed value.
+    InvalidTypecode { typecode: u64 },
+    /// A Unified Address contains several items with the same typecode.
+    DuplicateTypecode { typecode: u32 },
+    /// The items of a Unified Address are not in typecode order.
+    InvalidTypecodeOrder,
+    /// A Unified Address contains both a P2PKH and a P2SH receiver.
+    BothP2pkhAndP2sh,
+    /// A Unified Address contains only transparent receivers.
+    OnlyTransparent,
+    /// The receiver with the given typecode is not a valid receiver of its kind.
+    InvalidReceiver { typecode: u32 },
+    /// The string is not a well-formed address encoding.
+    InvalidEncoding { v: String },
+}
+
+impl fmt::Display for ZcashAddressParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ZcashAddressParseError::InvalidChecksum => write!(f, "Invalid checksum"),
+            ZcashAddressParseError::UnknownHrp { hrp } => {
+                write!(f, "Unknown human-readable part {:?}", hrp)
+            }
+            ZcashAddressParseError::UnknownPrefix { prefix } => {
+                write!(f, "Unknown address prefix {}", hex::encode(prefix))
+            }
+            ZcashAddressParseError::InvalidTypecode { typecode } => {
+                write!(f, "Typecode {} is out of range", typecode)
+            }
+            ZcashAddressParseError::DuplicateTypecode { typecode } => {
+                write!(f, "Duplicate typecode {}", typecode)
+            }
+            ZcashAddressParseError::InvalidTypecodeOrder => {
+                write!(f, "Receivers are not in typecode order")
+            }
+            ZcashAddressParseError::BothP2pkhAndP2sh => {
+                write!(f, "Unified Address contains both P2PKH and P2SH receivers")
+            }
+            ZcashAddressParseError::OnlyTransparent => {
+                write!(f, "Unified Address contains only transparent receivers")
+            }
+            ZcashAddressParseError::InvalidReceiver { typecode } => {
+                write!(f, "Invalid receiver with typecode {}", typecode)
+            }
+            ZcashAddressParseError::InvalidEncoding { v } => write!(f, "Invalid encoding: {}", v),
+        }
+    }
+}
+
+impl From<unified::ParseError> for ZcashAddressParseError {
+    fn from(e: unified::ParseError) -> Self {
+        match e {
+            unified::ParseError::BothP2phkAndP2sh => ZcashAddressParseError::BothP2pkhAndP2sh,
+            unified::ParseError::DuplicateTypecode(typecode) => {
+                ZcashAddressParseError::DuplicateTypecode {
+                    typecode: typecode.into(),
+                }
+            }
+            unified::ParseError::InvalidTypecodeValue(typecode) => {
+                ZcashAddressParseError::InvalidTypecode { typecode }
+            }
+            unified::ParseError::InvalidEncoding(v) => {
+                ZcashAddressParseError::InvalidEncoding { v }
+            }
+            unified::ParseError::InvalidTypecodeOrder => {
+                ZcashAddressParseError::InvalidTypecodeOrder
+            }
+            unified::ParseError::OnlyTransparent => ZcashAddressParseError::OnlyTransparent,
+            unified::ParseError::NotUnified => ZcashAddressParseError::InvalidEncoding {
+                v: "not Bech32m encoded".to_string(),
+            },
+            unified::ParseError::UnknownPrefix(hrp) => ZcashAddressParseError::UnknownHrp { hrp },
+        }
+    }
+}
+
+pub type ZcashAddressParseResult<T> = Result<T, ZcashAddressParseError>;
+
+/// Parses any Zcash address, detecting its network and kind.
+///
+/// Leading and trailing whitespace is ignored.
+pub fn parse_address(address: &str) -> ZcashAddressParseResult<Arc<ZcashParsedAddress>> {
+    let address = address.trim();
+
+    match ZcashAddress::try_from_encoded(address) {
+        Ok(parsed) => parsed
+            .convert::<ZcashParsedAddress>()
+            .map(Arc::new)
+            .map_err(|e| match e {
+                ConversionError::User(e) => e,
+                e => ZcashAddressParseError::InvalidEncoding { v: e.to_string() },
+            }),
+        Err(ParseError::Unified(e)) => Err(e.into()),
+        Err(ParseError::InvalidEncoding) => Err(invalid_encoding_error(address)),
+        // zcash_address 0.3 predates ZIP 320, so TEX addresses are not Zcash addresses to it.
+        Err(ParseError::NotZcash) => parse_tex(address)
+            .map(Arc::new)
+            .ok_or_else(|| not_zcash_error(address)),
+    }
+}
+
+impl TryFromAddress for ZcashParsedAddress {
+    type Error = ZcashAddressParseError;
+
+    fn try_from_sprout(
+        net: Network,
+        _data: [u8; 64],
+    ) -> Result<Self, ConversionError<Self::Error>> {
+        Ok(ZcashParsedAddress::new(net, ZcashAddressKind::Sprout))
+    }
+
+    fn try_from_sapling(
+        net: Network,
+        data: [u8; 43],
+    ) -> Result<Self, ConversionError<Self::Error>> {
+        let mut parsed = ZcashParsedAddress::new(net, ZcashAddressKind::Sapling);
+        parsed.add_receiver(&Receiver::Sapling(data))?;
+        Ok(parsed)
+    }
+
+    fn try_from_unified(
+        net: Network,
+        data: unified::Address,
+    ) -> Result<Self, ConversionError<Self::Error>> {
+        let mut parsed = ZcashParsedAddress::new(net, ZcashAddressKind::Unified);
+        for receiver in data.items_as_parsed() {
+            parsed.add_receiver(receiver)?;
+        }
+        Ok(parsed)
+    }
+
+    fn try_from_transparent_p2pkh(
+        net: Network,
+        data: [u8; 20],
+    ) -> Result<Self, ConversionError<Self::Error>> {
+        let mut parsed = ZcashParsedAddress::new(net, ZcashAddressKind::P2pkh);
+        parsed.add_receiver(&Receiver::P2pkh(data))?;
+        Ok(parsed)
+    }
+
+    fn try_from_transparent_p2sh(
+        net: Network,
+        data: [u8; 20],
+    ) -> Result<Self, ConversionError<Self::Error>> {
+        let mut parsed = ZcashParsedAddress::new(net, ZcashAddressKind::P2sh);
+        parsed.add_receiver(&Receiver::P2sh(data))?;
+        Ok(parsed)
+    }
+}
+
+fn parse_tex(address: &str) -> Option<ZcashParsedAddress> {
+    [
+        (ZcashConsensusParameters::MainNetwork, Network::Main),
+        (ZcashConsensusParameters::TestNetwork, Network::Test),
+    ]
+    .into_iter()
+    .find_map(|(params, network)| {
+        let tex = ZcashTexAddress::decode(params, address).ok()?;
+        let mut parsed = ZcashParsedAddress::new(network, ZcashAddressKind::Tex);
+        parsed
+            .add_receiver(&Receiver::P2pkh(tex.to_bytes().try_into().ok()?))
+            .ok()?;
+        Some(parsed)
+    })
+}
+
+/// Explains why a string that zcash_address fails to decode is invalid. Bech32 strings
+/// are decoded as Sapling addresses, whatever their human-readable part.
+fn invalid_encoding_error(address: &str) -> ZcashAddressParseError {
+    let sapling_hrps = [
+        mainnet::HRP_SAPLING_PAYMENT_ADDRESS,
+        testnet::HRP_SAPLING_PAYMENT_ADDRESS,
+        regtest::HRP_SAPLING_PAYMENT_ADDRESS,
+    ];
+
+    match bech32::decode(address) {
+        Ok((hrp, _, Variant::Bech32)) if !sapling_hrps.contains(&hrp.as_str()) => {
+            ZcashAddressParseError::UnknownHrp { hrp }
+        }
+        _ => ZcashAddressParseError::InvalidEncoding {
+            v: "invalid address payload".to_string(),
+        },
+    }
+}
+
+/// Explains why a string that zcash_address does not recognize is not a Zcash address,
+/// by decoding it again only as far as needed to tell the cause.
+fn not_zcash_error(address: &str) -> ZcashAddressParseError {
+    match bech32::decode(address) {
+        Ok((hrp, _, _)) => return ZcashAddressParseError::UnknownHrp { hrp },
+        Err(bech32::Error::InvalidChecksum) => return ZcashAddressParseError::InvalidChecksum,
+        Err(_) => {}
+    }
+
+    match bs58::decode(address).with_check(None).into_vec() {
+        Ok(decoded) => ZcashAddressParseError::UnknownPrefix {
+            prefix: decoded.into_iter().take(2).collect(),
+        },
+        Err(bs58::decode::Error::InvalidChecksum { .. }) => ZcashAddressParseError::InvalidChecksum,
+        Err(_) => ZcashAddressParseError::InvalidEncoding {
+            v: "not a Bech32, Bech32m or Base58Check string".to_string(),
+        },
+    }
+}
+
+fn receiver_typecode(receiver: &Receiver) -> u32 {
+    match receiver {
+        Receiver::P2pkh(_) => 0x00,
+        Receiver::P2sh(_) => 0x01,
+        Receiver::Sapling(_) => 0x02,
+        Receiver::Orchard(_) => 0x03,
+        Receiver::Unknown { typecode, .. } => *typecode,
+    }
+}
+
+fn sapling_receiver(data: &[u8; 43]) -> ZcashAddressParseResult<Arc<ZcashPaymentAddress>> {
+    PaymentAddress::from_bytes(data)
+        .map(|address| Arc::new(address.into()))
+        .ok_or(ZcashAddressParseError::InvalidReceiver { typecode: 2 })
+}
@@ -3,7 +3,7 @@
 use zcash_client_backend::address::RecipientAddress;
 
 use crate::{
-    ZcashConsensusParameters, ZcashError, ZcashPaymentAddress, ZcashResult,
+    ZcashConsensusParameters, ZcashError, ZcashPaymentAddress, ZcashResult, ZcashTexAddress,
     ZcashTransparentAddress, ZcashUnifiedAddress,
 };
 
@@ -13,6 +13,7 @@ pub enum ZcashRecipientAddress {
     Shielded(Arc<ZcashPaymentAddress>),
     Transparent(Arc<ZcashTransparentAddress>),
     Unified(Arc<ZcashUnifiedAddress>),
+    Tex(Arc<ZcashTexAddress>),
 }
 
 impl From<RecipientAddress> for ZcashRecipientAddress {
@@ -43,6 +44,8 @@ fn from(addr: ZcashRecipientAddress) -> Self {
             ZcashRecipientAddress::Unified(addr) => {
                 RecipientAddress::Unified((*addr).clone().into())
             }
+            // On chain, a TEX recipient is paid by a P2PKH output.
+            ZcashRecipientAddress::Tex(addr) => RecipientAddress::Transparent(addr.as_ref().into()),
         }
     }
 }
@@ -60,14 +63,31 @@ pub fn unified(addr: Arc<ZcashUnifiedAddress>) -> Self {
         ZcashRecipientAddress::Unified(addr)
     }
 
+    pub fn tex(addr: Arc<ZcashTexAddress>) -> Self {
+        ZcashRecipientAddress::Tex(addr)
+    }
+
     pub fn decode(params: ZcashConsensusParameters, address: &str) -> ZcashResult<Self> {
         RecipientAddress::decode(&params, address)
             .map(From::from)
+            .or_else(|| {
+                ZcashTexAddress::decode(params, address)
+                    .ok()
+                    .map(|addr| ZcashRecipientAddress::Tex(Arc::new(addr)))
+            })
             .ok_or::<ZcashError>("unable to parse address".into())
     }
 
     pub fn encode(&self, params: crate::ZcashConsensusParameters) -> String {
+        if let ZcashRecipientAddress::Tex(addr) = self {
+            return addr.encode(params);
+        }
         let addr: RecipientAddress = self.clone().into();
         addr.encode(&params)
     }
+
+    /// Returns whether the recipient only accepts funds from transparent inputs.
+    pub fn is_transparent_source_only(&self) -> bool {
+        matches!(self, ZcashRecipientAddress::Tex(_))
+    }
 }
@@ -0,0 +1,86 @@
+use std::sync::Arc;
+
+use bech32::{FromBase32, ToBase32, Variant};
+use zcash_primitives::legacy::TransparentAddress;
+
+use crate::{ZcashConsensusParameters, ZcashError, ZcashResult, ZcashTransparentAddress};
+
+/// A transparent-source-only address, as defined in [ZIP 320].
+///
+/// A TEX address encodes the same public key hash as a P2PKH address, but signals that the
+/// recipient only accepts funds from transactions with transparent inputs. Wallets holding
+/// shielded funds must route them through an ephemeral transparent address first.
+///
+/// [ZIP 320]: https://zips.z.cash/zip-0320
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ZcashTexAddress([u8; 20]);
+
+impl ZcashTexAddress {
+    /// Creates a TEX address from a public key hash.
+    pub fn from_bytes(data: &[u8]) -> ZcashResult<Self> {
+        data.try_into()
+            .map(ZcashTexAddress)
+            .map_err(|_| ZcashError::ArrayLengthMismatch {
+                expected: 20,
+                got: data.len() as u64,
+            })
+    }
+
+    /// Creates the TEX address of a P2PKH address.
+    pub fn from_transparent_address(address: Arc<ZcashTransparentAddress>) -> ZcashResult<Self> {
+        match TransparentAddress::from(address.as_ref()) {
+            TransparentAddress::PublicKey(data) => Ok(ZcashTexAddress(data)),
+            TransparentAddress::Script(_) => {
+                Err("Only P2PKH addresses can be converted to TEX addresses".into())
+            }
+        }
+    }
+
+    /// Decodes a TEX address from its Bech32m encoding.
+    pub fn decode(params: ZcashConsensusParameters, address: &str) -> ZcashResult<Self> {
+        let (hrp, data, variant) = bech32::decode(address).map_err(|e| ZcashError::Message {
+            error: format!("Invalid TEX address: {}", e),
+        })?;
+        if variant != Variant::Bech32m || hrp != hrp_tex_address(params) {
+            return Err("Not a TEX address for the given network".into());
+        }
+        Self::from_bytes(
+            &Vec::<u8>::from_base32(&data).map_err(|e| ZcashError::Message {
+                error: format!("Invalid TEX address: {}", e),
+            })?,
+        )
+    }
+
+    /// Returns the Bech32m encoding of the address for the given network.
+    pub fn encode(&self, params: ZcashConsensusParameters) -> String {
+        bech32::encode(
+            hrp_tex_address(params),
+            self.0.to_base32(),
+            Variant::Bech32m,
+        )
+        .expect("The TEX human-readable part is valid")
+    }
+
+    /// Returns the P2PKH address that receives the funds sent to this address.
+    pub fn to_transparent_address(&self) -> Arc<ZcashTransparentAddress> {
+        Arc::new(TransparentAddress::PublicKey(self.0).into())
+    }
+
+    /// Returns the public key hash of the address.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.0.to_vec()
+    }
+}
+
+impl From<&ZcashTexAddress> for TransparentAddress {
+    fn from(address: &ZcashTexAddress) -> Self {
+        TransparentAddress::PublicKey(address.0)
+    }
+}
+
+fn hrp_tex_address(params: ZcashConsensusParameters) -> &'static str {
+    match params {
+        ZcashConsensusParameters::MainNetwork => "tex",
+        ZcashConsensusParameters::TestNetwork => "textest",
+    }
+}
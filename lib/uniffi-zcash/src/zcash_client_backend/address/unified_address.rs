@@ -1,6 +1,9 @@
 use std::sync::Arc;
 
-use zcash_client_backend::{address::UnifiedAddress, encoding::AddressCodec};
+use zcash_client_backend::{
+    address::{RecipientAddress, UnifiedAddress},
+    encoding::AddressCodec,
+};
 
 use crate::{
     ZcashConsensusParameters, ZcashError, ZcashOrchardAddress, ZcashPaymentAddress, ZcashResult,
@@ -9,6 +12,21 @@
 
 use derive_more::{From, Into};
 
+/// The type of a receiver within a Unified Address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ZcashReceiverType {
+    Orchard,
+    Sapling,
+    /// A P2PKH or P2SH receiver.
+    Transparent,
+}
+
+/// A receiver of a Unified Address, along with its string encoding.
+pub struct ZcashUnifiedAddressReceiver {
+    pub receiver_type: ZcashReceiverType,
+    pub address: String,
+}
+
 /// A Unified Address.
 #[derive(Debug, Clone, From, Into)]
 pub struct ZcashUnifiedAddress(UnifiedAddress);
@@ -29,7 +47,81 @@ pub fn new(
 
         UnifiedAddress::from_receivers(orchard, sapling, transparent)
             .map(ZcashUnifiedAddress)
-            .ok_or(ZcashError::Unknown)
+            .ok_or_else(|| "A Unified Address requires at least one shielded receiver".into())
+    }
+
+    /// Constructs a Unified Address from a subset of the receivers of this one.
+    ///
+    /// Returns an error if a requested receiver is missing from this address, or if the
+    /// subset would contain no shielded receiver, as required by ZIP 316.
+    pub fn subset(&self, receiver_types: Vec<ZcashReceiverType>) -> ZcashResult<Arc<Self>> {
+        let mut orchard = None;
+        let mut sapling = None;
+        let mut transparent = None;
+
+        for receiver_type in receiver_types {
+            let missing = || ZcashError::Message {
+                error: format!(
+                    "Unified Address doesn't contain a {:?} receiver",
+                    receiver_type
+                ),
+            };
+            match receiver_type {
+                ZcashReceiverType::Orchard => orchard = Some(self.orchard().ok_or_else(missing)?),
+                ZcashReceiverType::Sapling => sapling = Some(self.sapling().ok_or_else(missing)?),
+                ZcashReceiverType::Transparent => {
+                    transparent = Some(self.transparent().ok_or_else(missing)?)
+                }
+            }
+        }
+
+        Self::new(orchard, sapling, transparent).map(Arc::new)
+    }
+
+    /// Returns the types of the receivers within this Unified Address, from the most
+    /// preferred to the least preferred.
+    pub fn receiver_types(&self) -> Vec<ZcashReceiverType> {
+        [
+            self.0.orchard().map(|_| ZcashReceiverType::Orchard),
+            self.0.sapling().map(|_| ZcashReceiverType::Sapling),
+            self.0.transparent().map(|_| ZcashReceiverType::Transparent),
+        ]
+        .into_iter()
+        .flatten()
+        .collect()
+    }
+
+    /// Returns the encoding of every receiver within this Unified Address, from the most
+    /// preferred to the least preferred.
+    ///
+    /// As Orchard receivers have no encoding of their own, they are encoded as
+    /// Unified Addresses containing only the Orchard receiver.
+    pub fn receivers(&self, params: ZcashConsensusParameters) -> Vec<ZcashUnifiedAddressReceiver> {
+        let orchard = self.0.orchard().map(|orchard| {
+            UnifiedAddress::from_receivers(Some(*orchard), None, None)
+                .expect("An Orchard receiver is a shielded receiver")
+                .into()
+        });
+        let sapling = self.0.sapling().cloned().map(RecipientAddress::Shielded);
+        let transparent = self
+            .0
+            .transparent()
+            .cloned()
+            .map(RecipientAddress::Transparent);
+
+        [
+            (ZcashReceiverType::Orchard, orchard),
+            (ZcashReceiverType::Sapling, sapling),
+            (ZcashReceiverType::Transparent, transparent),
+        ]
+        .into_iter()
+        .filter_map(|(receiver_type, address)| {
+            address.map(|address: RecipientAddress| ZcashUnifiedAddressReceiver {
+                receiver_type,
+                address: address.encode(&params),
+            })
+        })
+        .collect()
     }
 
     /// Returns the Orchard receiver within this Unified Address, if any.
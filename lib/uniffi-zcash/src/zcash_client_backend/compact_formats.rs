@@ -0,0 +1,69 @@
+use std::sync::Arc;
+
+use derive_more::{From, Into};
+use orchard::note::{ExtractedNoteCommitment, Nullifier};
+use orchard::note_encryption::CompactAction;
+use prost::Message;
+use zcash_client_backend::proto::compact_formats::{CompactBlock, CompactOrchardAction};
+use zcash_note_encryption::{EphemeralKeyBytes, COMPACT_NOTE_SIZE};
+
+use crate::{ZcashBlockHash, ZcashBlockHeight, ZcashError, ZcashResult};
+
+/// A compact representation of the shielded data in a Zcash block, as served by
+/// lightwalletd.
+#[derive(Clone, From, Into)]
+pub struct ZcashCompactBlock(CompactBlock);
+
+impl ZcashCompactBlock {
+    /// Decodes a protobuf-encoded `CompactBlock`.
+    pub fn from_bytes(bytes: Vec<u8>) -> ZcashResult<Self> {
+        let block = CompactBlock::decode(&bytes[..]).map_err(|e| ZcashError::Message {
+            error: format!("Invalid CompactBlock: {}", e),
+        })?;
+        Ok(Self(block))
+    }
+
+    /// Returns the protobuf encoding of this block.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.0.encode_to_vec()
+    }
+
+    /// Returns the [`BlockHeight`] value for this block.
+    pub fn height(&self) -> Arc<ZcashBlockHeight> {
+        Arc::new(self.0.height().into())
+    }
+
+    /// Returns the [`BlockHash`] for this block.
+    pub fn hash(&self) -> Arc<ZcashBlockHash> {
+        Arc::new(self.0.hash().into())
+    }
+
+    /// Returns the [`BlockHash`] for this block's parent.
+    pub fn prev_hash(&self) -> Arc<ZcashBlockHash> {
+        Arc::new(self.0.prev_hash().into())
+    }
+
+    /// Returns the Unix epoch time when the block was mined.
+    pub fn time(&self) -> u32 {
+        self.0.time
+    }
+}
+
+/// Parses a compact Orchard action, returning `None` if one of its fields is malformed.
+pub(crate) fn compact_orchard_action(action: &CompactOrchardAction) -> Option<CompactAction> {
+    let nullifier = Option::from(Nullifier::from_bytes(
+        &action.nullifier[..].try_into().ok()?,
+    ))?;
+    let cmx = Option::from(ExtractedNoteCommitment::from_bytes(
+        &action.cmx[..].try_into().ok()?,
+    ))?;
+    let ephemeral_key = EphemeralKeyBytes(action.ephemeral_key[..].try_into().ok()?);
+    let enc_ciphertext: [u8; COMPACT_NOTE_SIZE] = action.ciphertext[..].try_into().ok()?;
+
+    Some(CompactAction::from_parts(
+        nullifier,
+        cmx,
+        ephemeral_key,
+        enc_ciphertext,
+    ))
+}
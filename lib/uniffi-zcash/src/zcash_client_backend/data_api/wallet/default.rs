@@ -46,6 +46,10 @@ pub fn spend_main_fixed(
     ovk_policy: ZcashOvkPolicy,
     min_confirmations: u32,
 ) -> ZcashResult<Arc<ZcashTxId>> {
+    if request.has_tex_recipients() {
+        return Err("TEX recipients must be paid through spend_with_tex_main_fixed".into());
+    }
+
     let min_confirmations = NonZeroU32::new(min_confirmations).unwrap();
 
     let mut db_data = WalletDb::for_path(&z_db_data.path, consensus::MAIN_NETWORK)
@@ -85,6 +89,10 @@ pub fn spend_test_fixed(
     ovk_policy: ZcashOvkPolicy,
     min_confirmations: u32,
 ) -> ZcashResult<Arc<ZcashTxId>> {
+    if request.has_tex_recipients() {
+        return Err("TEX recipients must be paid through spend_with_tex_test_fixed".into());
+    }
+
     let min_confirmations = NonZeroU32::new(min_confirmations).unwrap();
 
     let mut db_data = WalletDb::for_path(&z_db_data.path, consensus::TEST_NETWORK)
@@ -124,6 +132,10 @@ pub fn spend_main_zip317(
     ovk_policy: ZcashOvkPolicy,
     min_confirmations: u32,
 ) -> ZcashResult<Arc<ZcashTxId>> {
+    if request.has_tex_recipients() {
+        return Err("TEX recipients must be paid through spend_with_tex_main_zip317".into());
+    }
+
     let min_confirmations = NonZeroU32::new(min_confirmations).unwrap();
 
     let mut db_data = WalletDb::for_path(&z_db_data.path, consensus::MAIN_NETWORK)
@@ -163,6 +175,10 @@ pub fn spend_test_zip317(
     ovk_policy: ZcashOvkPolicy,
     min_confirmations: u32,
 ) -> ZcashResult<Arc<ZcashTxId>> {
+    if request.has_tex_recipients() {
+        return Err("TEX recipients must be paid through spend_with_tex_test_zip317".into());
+    }
+
     let min_confirmations = NonZeroU32::new(min_confirmations).unwrap();
 
     let mut db_data = WalletDb::for_path(&z_db_data.path, consensus::TEST_NETWORK)
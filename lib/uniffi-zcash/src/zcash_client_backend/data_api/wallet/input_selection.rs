@@ -6,6 +6,9 @@
 use zcash_client_backend::fees::zip317::SingleOutputChangeStrategy as Zip317SingleOutputChangeStrategy;
 use zcash_client_sqlite::WalletDb;
 use zcash_primitives::consensus::{MainNetwork, TestNetwork};
+use zcash_primitives::transaction::fees::{
+    fixed::FeeRule as FixedFeeRule, zip317::FeeRule as Zip317FeeRule,
+};
 
 use crate::{
     ZcashDustOutputPolicy, ZcashFixedSingleOutputChangeStrategy,
@@ -38,6 +41,11 @@ pub fn new(
             dust_output_policy: *dust_output_policy,
         }
     }
+
+    /// Returns the fee rule of the change strategy.
+    pub(crate) fn fee_rule(&self) -> FixedFeeRule {
+        self.change_strategy.fee_rule()
+    }
 }
 
 impl Clone for ZcashMainFixedGreedyInputSelector {
@@ -79,6 +87,11 @@ pub fn new(
             dust_output_policy: *dust_output_policy,
         }
     }
+
+    /// Returns the fee rule of the change strategy.
+    pub(crate) fn fee_rule(&self) -> FixedFeeRule {
+        self.change_strategy.fee_rule()
+    }
 }
 
 impl Clone for ZcashTestFixedGreedyInputSelector {
@@ -122,6 +135,11 @@ pub fn new(
             dust_output_policy: *dust_output_policy,
         }
     }
+
+    /// Returns the fee rule of the change strategy.
+    pub(crate) fn fee_rule(&self) -> Zip317FeeRule {
+        self.change_strategy.fee_rule()
+    }
 }
 
 impl Clone for ZcashMainZip317GreedyInputSelector {
@@ -163,6 +181,11 @@ pub fn new(
             dust_output_policy: *dust_output_policy,
         }
     }
+
+    /// Returns the fee rule of the change strategy.
+    pub(crate) fn fee_rule(&self) -> Zip317FeeRule {
+        self.change_strategy.fee_rule()
+    }
 }
 
 impl Clone for ZcashTestZip317GreedyInputSelector {
@@ -3,3 +3,6 @@
 
 mod default;
 pub use self::default::*;
+
+mod tex;
+pub use self::tex::*;
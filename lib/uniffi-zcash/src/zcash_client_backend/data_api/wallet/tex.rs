@@ -0,0 +1,519 @@
+use std::fmt::Debug;
+use std::num::NonZeroU32;
+use std::sync::Arc;
+
+use hdwallet::{traits::Deserialize, ExtendedPrivKey, KeyIndex};
+use ripemd::Ripemd160;
+use rusqlite::{named_params, Connection, TransactionBehavior};
+use secp256k1::{PublicKey, Secp256k1, SecretKey};
+use sha2::{Digest, Sha256};
+use zcash_client_backend::address::RecipientAddress;
+use zcash_client_backend::data_api::wallet::input_selection::InputSelector;
+use zcash_client_backend::data_api::{
+    wallet, Recipient, SentTransaction, SentTransactionOutput, WalletRead, WalletWrite,
+};
+use zcash_client_backend::encoding::AddressCodec;
+use zcash_client_backend::keys::UnifiedSpendingKey;
+use zcash_client_backend::zip321::{Payment, TransactionRequest};
+use zcash_client_sqlite::WalletDb;
+use zcash_primitives::consensus::{self, BlockHeight};
+use zcash_primitives::legacy::{keys::AccountPrivKey, TransparentAddress};
+use zcash_primitives::transaction::builder::Builder;
+use zcash_primitives::transaction::components::transparent::fees::InputView;
+use zcash_primitives::transaction::components::{Amount, OutPoint, TxOut};
+use zcash_primitives::transaction::fees::FeeRule;
+use zcash_primitives::transaction::TxId;
+use zcash_primitives::zip32::AccountId;
+use zcash_proofs::prover::LocalTxProver;
+
+use crate::{
+    MainFixedGreedyInputSelector, MainZip317GreedyInputSelector, TestFixedGreedyInputSelector,
+    TestZip317GreedyInputSelector, ZcashConsensusParameters, ZcashError, ZcashLocalTxProver,
+    ZcashMainFixedGreedyInputSelector, ZcashMainZip317GreedyInputSelector, ZcashOvkPolicy,
+    ZcashPayment, ZcashResult, ZcashTestFixedGreedyInputSelector,
+    ZcashTestZip317GreedyInputSelector, ZcashTransactionRequest, ZcashTxId,
+    ZcashUnifiedSpendingKey, ZcashWalletDb,
+};
+
+/// The BIP 44 change level of the ephemeral addresses defined in ZIP 320.
+const EPHEMERAL_CHANGE_INDEX: u32 = 2;
+
+/// Pays a request whose recipients may include TEX addresses, as defined in ZIP 320.
+///
+/// TEX recipients only accept funds from transparent inputs. When the request contains any,
+/// the shielded funds of the wallet are first sent to a fresh ephemeral transparent address
+/// of the account, and a second transaction spends that output to the TEX recipients. Other
+/// recipients are paid by the first transaction.
+///
+/// Returns the ids of the created transactions, which must be broadcast in order. Both
+/// transactions are stored in the wallet. If the second transaction cannot be created, the
+/// error names the stored funding transaction, and its ephemeral address stays reserved.
+#[allow(clippy::too_many_arguments)]
+pub fn spend_with_tex_main_fixed(
+    z_db_data: Arc<ZcashWalletDb>,
+    params: ZcashConsensusParameters,
+    prover: Arc<ZcashLocalTxProver>,
+    input_selector: Arc<ZcashMainFixedGreedyInputSelector>,
+    usk: Arc<ZcashUnifiedSpendingKey>,
+    request: Arc<ZcashTransactionRequest>,
+    ovk_policy: ZcashOvkPolicy,
+    min_confirmations: u32,
+) -> ZcashResult<Vec<Arc<ZcashTxId>>> {
+    spend_with_tex(
+        &z_db_data,
+        consensus::MAIN_NETWORK,
+        params,
+        &prover,
+        &MainFixedGreedyInputSelector::from((*input_selector).clone()),
+        &input_selector.fee_rule(),
+        &usk,
+        &request,
+        ovk_policy,
+        min_confirmations,
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn spend_with_tex_test_fixed(
+    z_db_data: Arc<ZcashWalletDb>,
+    params: ZcashConsensusParameters,
+    prover: Arc<ZcashLocalTxProver>,
+    input_selector: Arc<ZcashTestFixedGreedyInputSelector>,
+    usk: Arc<ZcashUnifiedSpendingKey>,
+    request: Arc<ZcashTransactionRequest>,
+    ovk_policy: ZcashOvkPolicy,
+    min_confirmations: u32,
+) -> ZcashResult<Vec<Arc<ZcashTxId>>> {
+    spend_with_tex(
+        &z_db_data,
+        consensus::TEST_NETWORK,
+        params,
+        &prover,
+        &TestFixedGreedyInputSelector::from((*input_selector).clone()),
+        &input_selector.fee_rule(),
+        &usk,
+        &request,
+        ovk_policy,
+        min_confirmations,
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn spend_with_tex_main_zip317(
+    z_db_data: Arc<ZcashWalletDb>,
+    params: ZcashConsensusParameters,
+    prover: Arc<ZcashLocalTxProver>,
+    input_selector: Arc<ZcashMainZip317GreedyInputSelector>,
+    usk: Arc<ZcashUnifiedSpendingKey>,
+    request: Arc<ZcashTransactionRequest>,
+    ovk_policy: ZcashOvkPolicy,
+    min_confirmations: u32,
+) -> ZcashResult<Vec<Arc<ZcashTxId>>> {
+    spend_with_tex(
+        &z_db_data,
+        consensus::MAIN_NETWORK,
+        params,
+        &prover,
+        &MainZip317GreedyInputSelector::from((*input_selector).clone()),
+        &input_selector.fee_rule(),
+        &usk,
+        &request,
+        ovk_policy,
+        min_confirmations,
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn spend_with_tex_test_zip317(
+    z_db_data: Arc<ZcashWalletDb>,
+    params: ZcashConsensusParameters,
+    prover: Arc<ZcashLocalTxProver>,
+    input_selector: Arc<ZcashTestZip317GreedyInputSelector>,
+    usk: Arc<ZcashUnifiedSpendingKey>,
+    request: Arc<ZcashTransactionRequest>,
+    ovk_policy: ZcashOvkPolicy,
+    min_confirmations: u32,
+) -> ZcashResult<Vec<Arc<ZcashTxId>>> {
+    spend_with_tex(
+        &z_db_data,
+        consensus::TEST_NETWORK,
+        params,
+        &prover,
+        &TestZip317GreedyInputSelector::from((*input_selector).clone()),
+        &input_selector.fee_rule(),
+        &usk,
+        &request,
+        ovk_policy,
+        min_confirmations,
+    )
+}
+
+/// Spends the shielded funds of the wallet with `input_selector`, adding the ephemeral hop
+/// to the TEX recipients of the request, if any.
+#[allow(clippy::too_many_arguments)]
+fn spend_with_tex<P, InputsT>(
+    z_db_data: &ZcashWalletDb,
+    network: P,
+    params: ZcashConsensusParameters,
+    prover: &ZcashLocalTxProver,
+    input_selector: &InputsT,
+    fee_rule: &InputsT::FeeRule,
+    usk: &ZcashUnifiedSpendingKey,
+    request: &ZcashTransactionRequest,
+    ovk_policy: ZcashOvkPolicy,
+    min_confirmations: u32,
+) -> ZcashResult<Vec<Arc<ZcashTxId>>>
+where
+    P: consensus::Parameters + Clone,
+    InputsT: InputSelector<DataSource = WalletDb<Connection, P>>,
+    InputsT::Error: Debug,
+    <InputsT::FeeRule as FeeRule>::Error: Debug,
+{
+    let min_confirmations = NonZeroU32::new(min_confirmations)
+        .ok_or("The number of confirmations must be greater than zero")?;
+    let mut db_data =
+        WalletDb::for_path(&z_db_data.path, network).map_err(|e| ZcashError::Message {
+            error: format!("Cannot open the wallet database: {:?}", e),
+        })?;
+    let usk: UnifiedSpendingKey = usk.clone().into();
+
+    // Either the whole request or the funding transaction of the hop is sent this way.
+    let spend = |db_data: &mut WalletDb<Connection, P>, request: TransactionRequest| {
+        wallet::spend(
+            db_data,
+            &params,
+            LocalTxProver::from(prover.clone()),
+            input_selector,
+            &usk,
+            request,
+            ovk_policy.into(),
+            min_confirmations,
+        )
+        .map_err(|e| ZcashError::Message {
+            error: format!("spending error (spend_with_tex): {:?}", e),
+        })
+    };
+
+    let (tex_payments, mut payments): (Vec<Payment>, Vec<Payment>) =
+        TransactionRequest::from(request.clone())
+            .payments()
+            .iter()
+            .map(|payment| ZcashPayment::from(payment).into())
+            .partition(|payment| request.tex_recipient(payment).is_some());
+
+    if tex_payments.is_empty() {
+        let txid = spend(&mut db_data, request.clone().into())?;
+        return Ok(vec![Arc::new(txid.into())]);
+    }
+
+    let account = db_data
+        .get_account_for_ufvk(&usk.to_unified_full_viewing_key())
+        .map_err(wallet_err)?
+        .ok_or("The spending key does not belong to an account of the wallet")?;
+    let (target_height, _) = db_data
+        .get_target_and_anchor_heights(min_confirmations)
+        .map_err(wallet_err)?
+        .ok_or("The wallet is not synchronized")?;
+
+    let tex_outputs = tex_payments
+        .iter()
+        .filter_map(|payment| match &payment.recipient_address {
+            RecipientAddress::Transparent(address) => Some((*address, payment.amount)),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    let total = tex_outputs
+        .iter()
+        .map(|(_, amount)| *amount)
+        .sum::<Option<Amount>>()
+        .ok_or("Payment amounts overflow")?;
+
+    // The address is reserved before funding it, so that concurrent payments never share it.
+    let mut conn = z_db_data.extensions_conn()?;
+    let (address_index, ephemeral_key, ephemeral_address) =
+        reserve_ephemeral_address(&mut conn, &params, usk.transparent(), account)?;
+
+    let funded = hop_fee(
+        params,
+        target_height,
+        fee_rule,
+        &ephemeral_address,
+        &tex_outputs,
+        total,
+    )
+    .and_then(|fee_amount| {
+        let hop_value = (total + fee_amount).ok_or("Payment amounts overflow")?;
+        payments.push(Payment {
+            recipient_address: RecipientAddress::Transparent(ephemeral_address),
+            amount: hop_value,
+            memo: None,
+            label: None,
+            message: None,
+            other_params: vec![],
+        });
+        let funding_request =
+            TransactionRequest::new(payments).map_err(|e| ZcashError::Message {
+                error: format!("Cannot fund the ephemeral address: {:?}", e),
+            })?;
+        let funding_txid = spend(&mut db_data, funding_request)?;
+        Ok((funding_txid, fee_amount))
+    });
+    let (funding_txid, fee_amount) = match funded {
+        Ok(funded) => funded,
+        Err(e) => {
+            // Nothing was sent to the address, so it can be used by the next payment.
+            release_ephemeral_address(&conn, account, address_index)?;
+            return Err(e);
+        }
+    };
+
+    conn.execute(
+        "UPDATE tex_ephemeral_addresses SET funding_txid = :funding_txid
+        WHERE account = :account AND address_index = :address_index",
+        named_params![
+            ":account": u32::from(account),
+            ":address_index": address_index,
+            ":funding_txid": funding_txid.as_ref().to_vec(),
+        ],
+    )
+    .map_err(|e| funding_err(funding_txid, wallet_err(e)))?;
+
+    let hop_txid = pay_from_ephemeral_address(
+        &mut db_data,
+        params,
+        prover,
+        fee_rule,
+        target_height,
+        account,
+        funding_txid,
+        ephemeral_key,
+        ephemeral_address,
+        tex_outputs,
+        fee_amount,
+    )
+    .map_err(|e| funding_err(funding_txid, e))?;
+
+    Ok(vec![
+        Arc::new(funding_txid.into()),
+        Arc::new(hop_txid.into()),
+    ])
+}
+
+/// Builds and stores the transaction spending the output of `funding_txid` to the ephemeral
+/// address, which pays the TEX recipients.
+#[allow(clippy::too_many_arguments)]
+fn pay_from_ephemeral_address<P, FR>(
+    db_data: &mut WalletDb<Connection, P>,
+    params: ZcashConsensusParameters,
+    prover: &ZcashLocalTxProver,
+    fee_rule: &FR,
+    target_height: BlockHeight,
+    account: AccountId,
+    funding_txid: TxId,
+    ephemeral_key: SecretKey,
+    ephemeral_address: TransparentAddress,
+    tex_outputs: Vec<(TransparentAddress, Amount)>,
+    fee_amount: Amount,
+) -> ZcashResult<TxId>
+where
+    P: consensus::Parameters + Clone,
+    FR: FeeRule,
+    FR::Error: Debug,
+{
+    let funding_tx = db_data.get_transaction(funding_txid).map_err(wallet_err)?;
+    let (n, coin) = funding_tx
+        .transparent_bundle()
+        .and_then(|bundle| {
+            bundle
+                .vout
+                .iter()
+                .enumerate()
+                .find(|(_, out)| out.recipient_address() == Some(ephemeral_address))
+        })
+        .ok_or("The funding transaction does not pay the ephemeral address")?;
+    let outpoint = OutPoint::new(*funding_txid.as_ref(), n as u32);
+
+    let mut builder = Builder::new(params, target_height, None);
+    builder
+        .add_transparent_input(ephemeral_key, outpoint.clone(), coin.clone())
+        .map_err(builder_err)?;
+    for (address, amount) in &tex_outputs {
+        builder
+            .add_transparent_output(address, *amount)
+            .map_err(builder_err)?;
+    }
+    let (tx, _) = builder
+        .build(&LocalTxProver::from(prover.clone()), fee_rule)
+        .map_err(builder_err)?;
+
+    let outputs = tex_outputs
+        .into_iter()
+        .enumerate()
+        .map(|(index, (address, amount))| {
+            SentTransactionOutput::from_parts(
+                index,
+                Recipient::Transparent(address),
+                amount,
+                None,
+                None,
+            )
+        })
+        .collect();
+    db_data
+        .store_sent_tx(&SentTransaction {
+            tx: &tx,
+            created: time::OffsetDateTime::now_utc(),
+            account,
+            outputs,
+            fee_amount,
+            utxos_spent: vec![outpoint],
+        })
+        .map_err(wallet_err)?;
+
+    Ok(tx.txid())
+}
+
+/// A transparent input, for computing the fee of a transaction before it is built.
+struct EphemeralInput {
+    outpoint: OutPoint,
+    coin: TxOut,
+}
+
+impl InputView for EphemeralInput {
+    fn outpoint(&self) -> &OutPoint {
+        &self.outpoint
+    }
+
+    fn coin(&self) -> &TxOut {
+        &self.coin
+    }
+}
+
+/// Returns the fee of the transaction spending the ephemeral output of value `total` to the
+/// TEX recipients.
+fn hop_fee<FR>(
+    params: ZcashConsensusParameters,
+    target_height: BlockHeight,
+    fee_rule: &FR,
+    ephemeral_address: &TransparentAddress,
+    tex_outputs: &[(TransparentAddress, Amount)],
+    total: Amount,
+) -> ZcashResult<Amount>
+where
+    FR: FeeRule,
+    FR::Error: Debug,
+{
+    let input = EphemeralInput {
+        outpoint: OutPoint::new([0; 32], 0),
+        coin: TxOut {
+            value: total,
+            script_pubkey: ephemeral_address.script(),
+        },
+    };
+    let outputs = tex_outputs
+        .iter()
+        .map(|(address, amount)| TxOut {
+            value: *amount,
+            script_pubkey: address.script(),
+        })
+        .collect::<Vec<_>>();
+    fee_rule
+        .fee_required(&params, target_height, &[input], &outputs, 0, 0, 0)
+        .map_err(|e| ZcashError::Message {
+            error: format!("Cannot compute the fee of the TEX payment: {:?}", e),
+        })
+}
+
+/// Derives the ephemeral key at `m/44'/<coin_type>'/<account>'/2/<address_index>`.
+fn derive_ephemeral_secret_key(
+    account_key: &AccountPrivKey,
+    address_index: u32,
+) -> ZcashResult<SecretKey> {
+    Ok(ExtendedPrivKey::deserialize(&account_key.to_bytes())?
+        .derive_private_key(KeyIndex::Normal(EPHEMERAL_CHANGE_INDEX))?
+        .derive_private_key(KeyIndex::Normal(address_index))?
+        .private_key)
+}
+
+/// Reserves the first ephemeral address of `account` that was never used, so that every TEX
+/// payment is funded through its own address.
+fn reserve_ephemeral_address(
+    conn: &mut Connection,
+    params: &ZcashConsensusParameters,
+    account_key: &AccountPrivKey,
+    account: AccountId,
+) -> ZcashResult<(u32, SecretKey, TransparentAddress)> {
+    let tx = conn
+        .transaction_with_behavior(TransactionBehavior::Immediate)
+        .map_err(wallet_err)?;
+    let address_index: u32 = tx
+        .query_row(
+            "SELECT COALESCE(MAX(address_index) + 1, 0) FROM tex_ephemeral_addresses
+            WHERE account = :account",
+            named_params![":account": u32::from(account)],
+            |row| row.get(0),
+        )
+        .map_err(wallet_err)?;
+
+    let ephemeral_key = derive_ephemeral_secret_key(account_key, address_index)?;
+    let ephemeral_pubkey = PublicKey::from_secret_key(&Secp256k1::signing_only(), &ephemeral_key);
+    let ephemeral_address = TransparentAddress::PublicKey(
+        Ripemd160::digest(Sha256::digest(ephemeral_pubkey.serialize())).into(),
+    );
+
+    tx.execute(
+        "INSERT INTO tex_ephemeral_addresses (account, address_index, address)
+        VALUES (:account, :address_index, :address)",
+        named_params![
+            ":account": u32::from(account),
+            ":address_index": address_index,
+            ":address": ephemeral_address.encode(params),
+        ],
+    )
+    .map_err(wallet_err)?;
+    tx.commit().map_err(wallet_err)?;
+
+    Ok((address_index, ephemeral_key, ephemeral_address))
+}
+
+/// Releases an ephemeral address reserved for a payment whose funding transaction was not
+/// created.
+fn release_ephemeral_address(
+    conn: &Connection,
+    account: AccountId,
+    address_index: u32,
+) -> ZcashResult<()> {
+    conn.execute(
+        "DELETE FROM tex_ephemeral_addresses
+        WHERE account = :account AND address_index = :address_index",
+        named_params![
+            ":account": u32::from(account),
+            ":address_index": address_index,
+        ],
+    )
+    .map(|_| ())
+    .map_err(wallet_err)
+}
+
+/// Reports a failure after the funding transaction was stored in the wallet, which must then
+/// be broadcast or expire before its funds can be spent again.
+fn funding_err(funding_txid: TxId, e: ZcashError) -> ZcashError {
+    ZcashError::Message {
+        error: format!(
+            "The funding transaction {} was stored, but the TEX payment failed: {}",
+            funding_txid, e
+        ),
+    }
+}
+
+fn wallet_err(e: impl Debug) -> ZcashError {
+    ZcashError::Message {
+        error: format!("TEX payment error: {:?}", e),
+    }
+}
+
+fn builder_err(e: impl Debug) -> ZcashError {
+    ZcashError::Message {
+        error: format!("Cannot build the TEX payment transaction: {:?}", e),
+    }
+}
@@ -2,6 +2,7 @@
 
 use zcash_client_backend::fees::fixed::SingleOutputChangeStrategy;
 use zcash_client_backend::fees::ChangeStrategy;
+use zcash_primitives::transaction::fees::fixed::FeeRule as FixedFeeRule;
 
 use crate::ZcashFixedFeeRule;
 
@@ -16,6 +17,10 @@ impl ZcashFixedSingleOutputChangeStrategy {
     pub fn new(fee_rule: Arc<ZcashFixedFeeRule>) -> Self {
         Self(SingleOutputChangeStrategy::new((*fee_rule).clone().into()))
     }
+
+    pub(crate) fn fee_rule(&self) -> FixedFeeRule {
+        *self.0.fee_rule()
+    }
 }
 
 impl Clone for ZcashFixedSingleOutputChangeStrategy {
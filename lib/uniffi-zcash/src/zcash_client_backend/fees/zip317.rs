@@ -2,6 +2,7 @@
 
 use zcash_client_backend::fees::zip317::SingleOutputChangeStrategy;
 use zcash_client_backend::fees::ChangeStrategy;
+use zcash_primitives::transaction::fees::zip317::FeeRule as Zip317FeeRule;
 
 use crate::ZcashZip317FeeRule;
 
@@ -16,6 +17,10 @@ impl ZcashZip317SingleOutputChangeStrategy {
     pub fn new(fee_rule: Arc<ZcashZip317FeeRule>) -> Self {
         Self(SingleOutputChangeStrategy::new((*fee_rule).clone().into()))
     }
+
+    pub(crate) fn fee_rule(&self) -> Zip317FeeRule {
+        self.0.fee_rule().clone()
+    }
 }
 
 impl Clone for ZcashZip317SingleOutputChangeStrategy {
@@ -8,6 +8,9 @@
 mod address;
 pub use self::address::*;
 
+mod compact_formats;
+pub use self::compact_formats::*;
+
 mod data_api;
 pub use self::data_api::*;
 
@@ -26,9 +29,18 @@
 mod zip321;
 pub use self::zip321::*;
 
+mod qr;
+pub use self::qr::*;
+
 mod decrypt;
 pub use self::decrypt::*;
 
+mod payment_disclosure;
+pub use self::payment_disclosure::*;
+
+mod scanning;
+pub use self::scanning::*;
+
 mod service;
 pub use self::service::*;
 
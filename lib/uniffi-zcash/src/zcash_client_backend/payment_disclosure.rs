@@ -0,0 +1,508 @@
+use std::io::{Read, Write};
+use std::sync::Arc;
+
+use blake2b_simd::Params as Blake2bParams;
+use group::GroupEncoding;
+use orchard::note_encryption::OrchardDomain;
+use orchard::primitives::redpallas::{self, SpendAuth};
+use orchard::Action;
+use rusqlite::{named_params, OptionalExtension};
+use zcash_client_backend::address::UnifiedAddress;
+use zcash_client_backend::data_api::WalletRead;
+use zcash_client_backend::keys::UnifiedFullViewingKey;
+use zcash_client_sqlite::WalletDb;
+use zcash_encoding::Vector;
+use zcash_note_encryption::{Domain, OutgoingCipherKey};
+use zcash_primitives::consensus::BlockHeight;
+use zcash_primitives::constants::SPENDING_KEY_GENERATOR;
+use zcash_primitives::memo::MemoBytes;
+use zcash_primitives::sapling::note_encryption::{prf_ock, try_sapling_output_recovery_with_ock};
+use zcash_primitives::sapling::redjubjub;
+use zcash_primitives::transaction::components::amount::NonNegativeAmount;
+use zcash_primitives::transaction::components::sapling::{GrothProofBytes, OutputDescription};
+use zcash_primitives::transaction::{Transaction, TxId};
+use zcash_primitives::zip32::Scope;
+
+use crate::{
+    ZcashBlockHeight, ZcashConsensusParameters, ZcashError, ZcashMemoBytes, ZcashNonNegativeAmount,
+    ZcashRecipientAddress, ZcashResult, ZcashTransaction, ZcashTxId, ZcashUnifiedFullViewingKey,
+    ZcashWalletDb,
+};
+
+const ENCODING_VERSION: u8 = 1;
+
+const SIGHASH_PERSONALIZATION: &[u8; 16] = b"ZcashPDisclosure";
+
+/// The shielded protocol of a disclosed output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ZcashDisclosureProtocol {
+    Sapling,
+    Orchard,
+}
+
+/// A disclosure of a shielded output of a transaction, as defined in [ZIP 311].
+///
+/// The disclosure holds the outgoing cipher key of a single output, which lets anyone holding
+/// the transaction recover the recipient, value and memo of that output without revealing any
+/// viewing key of the sender, and a message chosen by the sender.
+///
+/// To prove who sent the output, the disclosure is signed with the randomized spend authorizing
+/// key of one of the spends of the transaction, over [`Self::signature_hash`]. The randomizer
+/// must be the one used when the transaction was created, as given to a
+/// [`ZcashExternalSigner`](crate::ZcashExternalSigner), so that the signature verifies against
+/// the `rk` of that spend. Only signed disclosures can be verified.
+///
+/// [ZIP 311]: https://zips.z.cash/zip-0311
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ZcashPaymentDisclosure {
+    txid: TxId,
+    protocol: ZcashDisclosureProtocol,
+    output_index: u32,
+    ock: [u8; 32],
+    message: String,
+    spend_auth: Option<SpendAuthorization>,
+}
+
+/// The signature of a disclosure by the spend authorizing key of a spend of the transaction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct SpendAuthorization {
+    protocol: ZcashDisclosureProtocol,
+    signature: [u8; 64],
+}
+
+/// The payment revealed by a verified [`ZcashPaymentDisclosure`].
+pub struct ZcashDisclosedPayment {
+    pub protocol: ZcashDisclosureProtocol,
+    pub output_index: u32,
+    /// The recipient of the output. Orchard recipients are given as Orchard-only Unified
+    /// Addresses.
+    pub recipient: Arc<ZcashRecipientAddress>,
+    pub value: Arc<ZcashNonNegativeAmount>,
+    pub memo: Arc<ZcashMemoBytes>,
+}
+
+impl ZcashPaymentDisclosure {
+    /// Creates the unsigned disclosure of an output sent by the holder of `ufvk`.
+    ///
+    /// The output must be recoverable with either the external or the internal outgoing viewing
+    /// key of `ufvk`. `height` is the height at which the transaction was mined.
+    pub fn new(
+        params: ZcashConsensusParameters,
+        height: Arc<ZcashBlockHeight>,
+        tx: Arc<ZcashTransaction>,
+        ufvk: Arc<ZcashUnifiedFullViewingKey>,
+        protocol: ZcashDisclosureProtocol,
+        output_index: u32,
+        message: String,
+    ) -> ZcashResult<Self> {
+        let tx: &Transaction = tx.as_ref().into();
+        let ufvk: UnifiedFullViewingKey = (*ufvk).clone().into();
+
+        let ock = match protocol {
+            ZcashDisclosureProtocol::Sapling => {
+                let dfvk = ufvk
+                    .sapling()
+                    .ok_or("The viewing key has no Sapling component")?;
+                let output = sapling_output(tx, output_index)?;
+                [Scope::External, Scope::Internal]
+                    .into_iter()
+                    .map(|scope| {
+                        prf_ock(
+                            &dfvk.to_ovk(scope),
+                            output.cv(),
+                            &output.cmu().to_bytes(),
+                            output.ephemeral_key(),
+                        )
+                    })
+                    .find(|ock| {
+                        try_sapling_output_recovery_with_ock(&params, (*height).into(), ock, output)
+                            .is_some()
+                    })
+            }
+            ZcashDisclosureProtocol::Orchard => {
+                let fvk = ufvk
+                    .orchard()
+                    .ok_or("The viewing key has no Orchard component")?;
+                let action = orchard_action(tx, output_index)?;
+                let domain = OrchardDomain::for_action(action);
+                [
+                    orchard::keys::Scope::External,
+                    orchard::keys::Scope::Internal,
+                ]
+                .into_iter()
+                .map(|scope| {
+                    OrchardDomain::derive_ock(
+                        &fvk.to_ovk(scope),
+                        action.cv_net(),
+                        &action.cmx().to_bytes(),
+                        &action.encrypted_note().epk_bytes.into(),
+                    )
+                })
+                .find(|ock| {
+                    zcash_note_encryption::try_output_recovery_with_ock(
+                        &domain,
+                        ock,
+                        action,
+                        &action.encrypted_note().out_ciphertext,
+                    )
+                    .is_some()
+                })
+            }
+        }
+        .ok_or("The output was not sent with this viewing key")?;
+
+        Ok(ZcashPaymentDisclosure {
+            txid: tx.txid(),
+            protocol,
+            output_index,
+            ock: ock.0,
+            message,
+            spend_auth: None,
+        })
+    }
+
+    pub fn from_bytes(data: &[u8]) -> ZcashResult<Self> {
+        let mut r = data;
+        let version = read_u8(&mut r)?;
+        if version != ENCODING_VERSION {
+            return Err(ZcashError::Message {
+                error: format!("Unsupported payment disclosure version: {}", version),
+            });
+        }
+        let protocol = read_protocol(&mut r)?;
+        let txid = TxId::read(&mut r)?;
+        let output_index = read_u32(&mut r)?;
+        let ock = read_array(&mut r)?;
+        let message = String::from_utf8(Vector::read(&mut r, |r| read_u8(r).map_err(to_io))?)
+            .map_err(|_| "The disclosure message is not valid UTF-8")?;
+        let spend_auth = match read_u8(&mut r)? {
+            0 => None,
+            1 => Some(SpendAuthorization {
+                protocol: read_protocol(&mut r)?,
+                signature: read_array(&mut r)?,
+            }),
+            other => {
+                return Err(ZcashError::Message {
+                    error: format!("Invalid payment disclosure signature flag: {}", other),
+                })
+            }
+        };
+        if !r.is_empty() {
+            return Err("Trailing data after the payment disclosure".into());
+        }
+
+        Ok(ZcashPaymentDisclosure {
+            txid,
+            protocol,
+            output_index,
+            ock,
+            message,
+            spend_auth,
+        })
+    }
+
+    /// Decodes a disclosure from its hex encoding.
+    pub fn decode(encoding: &str) -> ZcashResult<Self> {
+        Self::from_bytes(&hex::decode(encoding).map_err(|e| ZcashError::Message {
+            error: format!("Invalid payment disclosure: {}", e),
+        })?)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data = self.payload();
+        match &self.spend_auth {
+            None => data.push(0),
+            Some(auth) => {
+                data.push(1);
+                data.push(protocol_byte(auth.protocol));
+                data.extend_from_slice(&auth.signature);
+            }
+        }
+        data
+    }
+
+    /// Returns the hex encoding of the disclosure.
+    pub fn encode(&self) -> String {
+        hex::encode(self.to_bytes())
+    }
+
+    /// Returns the hash signed by the spend authorizing key, which commits to every field of
+    /// the disclosure but the signature.
+    pub fn signature_hash(&self) -> Vec<u8> {
+        self.sighash().to_vec()
+    }
+
+    /// Returns a copy of the disclosure signed for a spend of the given protocol.
+    ///
+    /// `signature` is a spend authorization signature over [`Self::signature_hash`], as created
+    /// by `sign_spend_auth` with the randomizer of the spend.
+    pub fn signed(
+        &self,
+        protocol: ZcashDisclosureProtocol,
+        signature: Vec<u8>,
+    ) -> ZcashResult<Arc<Self>> {
+        let signature =
+            signature
+                .as_slice()
+                .try_into()
+                .map_err(|_| ZcashError::ArrayLengthMismatch {
+                    expected: 64,
+                    got: signature.len() as u64,
+                })?;
+        Ok(Arc::new(ZcashPaymentDisclosure {
+            spend_auth: Some(SpendAuthorization {
+                protocol,
+                signature,
+            }),
+            ..self.clone()
+        }))
+    }
+
+    pub fn txid(&self) -> Arc<ZcashTxId> {
+        Arc::new(self.txid.into())
+    }
+
+    pub fn protocol(&self) -> ZcashDisclosureProtocol {
+        self.protocol
+    }
+
+    pub fn output_index(&self) -> u32 {
+        self.output_index
+    }
+
+    pub fn message(&self) -> String {
+        self.message.clone()
+    }
+
+    pub fn is_signed(&self) -> bool {
+        self.spend_auth.is_some()
+    }
+
+    /// Returns the encoding of the disclosure without its signature.
+    fn payload(&self) -> Vec<u8> {
+        let mut data = vec![ENCODING_VERSION, protocol_byte(self.protocol)];
+        data.extend_from_slice(self.txid.as_ref());
+        data.extend_from_slice(&self.output_index.to_le_bytes());
+        data.extend_from_slice(&self.ock);
+        Vector::write(&mut data, self.message.as_bytes(), |w, b| {
+            w.write_all(&[*b])
+        })
+        .expect("Writing to a Vec cannot fail");
+        data
+    }
+
+    fn sighash(&self) -> [u8; 32] {
+        Blake2bParams::new()
+            .hash_length(32)
+            .personal(SIGHASH_PERSONALIZATION)
+            .hash(&self.payload())
+            .as_bytes()
+            .try_into()
+            .expect("The hash is 32 bytes long")
+    }
+}
+
+/// Checks `disclosure` against `tx`, and returns the payment it reveals.
+///
+/// `height` is the height at which the transaction was mined. Fails if the disclosure is for
+/// another transaction, is not signed by the spend authorizing key of one of its spends, or
+/// does not decrypt the disclosed output.
+pub fn verify_payment_disclosure(
+    params: ZcashConsensusParameters,
+    height: Arc<ZcashBlockHeight>,
+    tx: Arc<ZcashTransaction>,
+    disclosure: Arc<ZcashPaymentDisclosure>,
+) -> ZcashResult<ZcashDisclosedPayment> {
+    let tx: &Transaction = tx.as_ref().into();
+    if tx.txid() != disclosure.txid {
+        return Err("The disclosure is for another transaction".into());
+    }
+    let auth = disclosure
+        .spend_auth
+        .as_ref()
+        .ok_or("The disclosure is not signed")?;
+    let sighash = disclosure.sighash();
+    // Builders shuffle the spends, so the signature is checked against every one of them.
+    let authorized = match auth.protocol {
+        ZcashDisclosureProtocol::Sapling => {
+            let signature = redjubjub::Signature::read(&auth.signature[..])?;
+            tx.sapling_bundle().is_some_and(|b| {
+                b.shielded_spends().iter().any(|spend| {
+                    // Sapling spend authorization signatures are prefixed with the key.
+                    let mut data = spend.rk().0.to_bytes().to_vec();
+                    data.extend_from_slice(&sighash);
+                    spend.rk().verify(&data, &signature, SPENDING_KEY_GENERATOR)
+                })
+            })
+        }
+        ZcashDisclosureProtocol::Orchard => {
+            let signature = redpallas::Signature::from(auth.signature);
+            tx.orchard_bundle().is_some_and(|b| {
+                b.actions()
+                    .iter()
+                    .any(|action| action.rk().verify(&sighash, &signature).is_ok())
+            })
+        }
+    };
+    if !authorized {
+        return Err("The disclosure is not signed by a spender of the transaction".into());
+    }
+    let ock = OutgoingCipherKey(disclosure.ock);
+    let height: BlockHeight = (*height).into();
+
+    let (recipient, value, memo) = match disclosure.protocol {
+        ZcashDisclosureProtocol::Sapling => {
+            let output = sapling_output(tx, disclosure.output_index)?;
+            let (note, address, memo) =
+                try_sapling_output_recovery_with_ock(&params, height, &ock, output)
+                    .ok_or("The disclosure does not decrypt the output")?;
+            (
+                ZcashRecipientAddress::Shielded(Arc::new(address.into())),
+                note.value().inner(),
+                memo,
+            )
+        }
+        ZcashDisclosureProtocol::Orchard => {
+            let action = orchard_action(tx, disclosure.output_index)?;
+            let (note, address, memo) = zcash_note_encryption::try_output_recovery_with_ock(
+                &OrchardDomain::for_action(action),
+                &ock,
+                action,
+                &action.encrypted_note().out_ciphertext,
+            )
+            .ok_or("The disclosure does not decrypt the output")?;
+            let address = UnifiedAddress::from_receivers(Some(address), None, None)
+                .expect("An Orchard receiver is a valid Unified Address");
+            (
+                ZcashRecipientAddress::Unified(Arc::new(address.into())),
+                note.value().inner(),
+                MemoBytes::from_bytes(&memo).map_err(|e| ZcashError::Message {
+                    error: format!("Invalid memo: {:?}", e),
+                })?,
+            )
+        }
+    };
+
+    Ok(ZcashDisclosedPayment {
+        protocol: disclosure.protocol,
+        output_index: disclosure.output_index,
+        recipient: Arc::new(recipient),
+        value: Arc::new(
+            NonNegativeAmount::from_u64(value)
+                .map_err(|_| ZcashError::from("The disclosed value is out of range"))?
+                .into(),
+        ),
+        memo: Arc::new(memo.into()),
+    })
+}
+
+impl ZcashWalletDb {
+    /// Creates the unsigned disclosure of an output of a mined transaction sent by this wallet.
+    pub fn create_payment_disclosure(
+        &self,
+        txid: Arc<ZcashTxId>,
+        protocol: ZcashDisclosureProtocol,
+        output_index: u32,
+        message: String,
+    ) -> ZcashResult<Arc<ZcashPaymentDisclosure>> {
+        let height: u32 = self
+            .extensions_conn()?
+            .query_row(
+                "SELECT block FROM transactions WHERE txid = :txid",
+                named_params![":txid": TxId::from(*txid).as_ref().to_vec()],
+                |row| row.get::<_, Option<u32>>(0),
+            )
+            .optional()
+            .map_err(|e| ZcashError::Message {
+                error: format!("Cannot read the transaction height: {:?}", e),
+            })?
+            .ok_or("The transaction is not in the wallet")?
+            .ok_or("The transaction is not mined yet")?;
+
+        let db = WalletDb::for_path(&self.path, self.params).map_err(|e| ZcashError::Message {
+            error: format!("Cannot access the DB: {:?}", e),
+        })?;
+        let tx = db
+            .get_transaction((*txid).into())
+            .map_err(|e| ZcashError::Message {
+                error: format!("Cannot read the transaction: {:?}", e),
+            })?;
+        let ufvks = db
+            .get_unified_full_viewing_keys()
+            .map_err(|e| ZcashError::Message {
+                error: format!("Cannot read the viewing keys: {:?}", e),
+            })?;
+
+        let tx = Arc::new(ZcashTransaction::from(tx));
+        let mut last_err = ZcashError::from("The wallet has no accounts");
+        for ufvk in ufvks.into_values() {
+            match ZcashPaymentDisclosure::new(
+                self.params,
+                Arc::new(BlockHeight::from(height).into()),
+                tx.clone(),
+                Arc::new(ufvk.into()),
+                protocol,
+                output_index,
+                message.clone(),
+            ) {
+                Ok(disclosure) => return Ok(Arc::new(disclosure)),
+                Err(e) => last_err = e,
+            }
+        }
+        Err(last_err)
+    }
+}
+
+fn sapling_output(
+    tx: &Transaction,
+    index: u32,
+) -> ZcashResult<&OutputDescription<GrothProofBytes>> {
+    tx.sapling_bundle()
+        .and_then(|b| b.shielded_outputs().get(index as usize))
+        .ok_or_else(|| "The transaction has no Sapling output at this index".into())
+}
+
+fn orchard_action(
+    tx: &Transaction,
+    index: u32,
+) -> ZcashResult<&Action<redpallas::Signature<SpendAuth>>> {
+    tx.orchard_bundle()
+        .and_then(|b| b.actions().get(index as usize))
+        .ok_or_else(|| "The transaction has no Orchard action at this index".into())
+}
+
+fn protocol_byte(protocol: ZcashDisclosureProtocol) -> u8 {
+    match protocol {
+        ZcashDisclosureProtocol::Sapling => 0,
+        ZcashDisclosureProtocol::Orchard => 1,
+    }
+}
+
+fn read_protocol(r: &mut &[u8]) -> ZcashResult<ZcashDisclosureProtocol> {
+    match read_u8(r)? {
+        0 => Ok(ZcashDisclosureProtocol::Sapling),
+        1 => Ok(ZcashDisclosureProtocol::Orchard),
+        p => Err(ZcashError::Message {
+            error: format!("Unknown payment disclosure protocol: {}", p),
+        }),
+    }
+}
+
+fn read_u8(r: &mut &[u8]) -> ZcashResult<u8> {
+    Ok(read_array::<1>(r)?[0])
+}
+
+fn read_u32(r: &mut &[u8]) -> ZcashResult<u32> {
+    Ok(u32::from_le_bytes(read_array(r)?))
+}
+
+fn read_array<const N: usize>(r: &mut &[u8]) -> ZcashResult<[u8; N]> {
+    let mut data = [0; N];
+    r.read_exact(&mut data)
+        .map_err(|_| "The payment disclosure is truncated")?;
+    Ok(data)
+}
+
+fn to_io(e: ZcashError) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, e.to_string())
+}
@@ -0,0 +1,207 @@
+use std::sync::Arc;
+
+use qrcode::{render::svg, Color, EcLevel, QrCode};
+use zcash_address::ZcashAddress;
+
+use crate::{ZcashConsensusParameters, ZcashError, ZcashResult, ZcashTransactionRequest};
+
+/// The largest width and height of a rendered image, in pixels.
+const MAX_IMAGE_WIDTH: usize = 16384;
+
+/// The error correction level of a QR code, from the lowest to the highest redundancy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ZcashQrErrorCorrection {
+    /// Recovers from 7% of the code being damaged.
+    Low,
+    /// Recovers from 15% of the code being damaged.
+    Medium,
+    /// Recovers from 25% of the code being damaged.
+    Quartile,
+    /// Recovers from 30% of the code being damaged.
+    High,
+}
+
+impl From<ZcashQrErrorCorrection> for EcLevel {
+    fn from(level: ZcashQrErrorCorrection) -> Self {
+        match level {
+            ZcashQrErrorCorrection::Low => EcLevel::L,
+            ZcashQrErrorCorrection::Medium => EcLevel::M,
+            ZcashQrErrorCorrection::Quartile => EcLevel::Q,
+            ZcashQrErrorCorrection::High => EcLevel::H,
+        }
+    }
+}
+
+/// How a QR code is rendered to an image.
+pub struct ZcashQrRenderOptions {
+    /// The width and height of every module, in pixels.
+    pub module_size: u32,
+    /// Whether to surround the code with the 4-module light border that scanners expect.
+    pub quiet_zone: bool,
+}
+
+/// A QR code encoding a ZIP 321 payment request or an address.
+pub struct ZcashQrCode(QrCode);
+
+impl ZcashQrCode {
+    /// Encodes arbitrary text, usually a `zcash:` URI.
+    pub fn new(data: &str, error_correction: ZcashQrErrorCorrection) -> ZcashResult<Self> {
+        QrCode::with_error_correction_level(data, error_correction.into())
+            .map(ZcashQrCode)
+            .map_err(|e| ZcashError::Message {
+                error: format!("Cannot encode QR code: {}", e),
+            })
+    }
+
+    /// Encodes the ZIP 321 URI of a payment request.
+    pub fn from_transaction_request(
+        request: Arc<ZcashTransactionRequest>,
+        params: ZcashConsensusParameters,
+        error_correction: ZcashQrErrorCorrection,
+    ) -> ZcashResult<Self> {
+        let uri = request
+            .to_uri(params)
+            .ok_or("Cannot encode an empty payment request")?;
+        Self::new(&uri, error_correction)
+    }
+
+    /// Encodes a ZIP 321 URI paying `address`, leaving the amount to the payer.
+    pub fn from_address(
+        address: &str,
+        error_correction: ZcashQrErrorCorrection,
+    ) -> ZcashResult<Self> {
+        ZcashAddress::try_from_encoded(address).map_err(|e| ZcashError::Message {
+            error: format!("Invalid address: {}", e),
+        })?;
+        Self::new(&format!("zcash:{}", address), error_correction)
+    }
+
+    /// Returns the number of modules in every row and column, without a quiet zone.
+    pub fn width(&self) -> u32 {
+        self.0.width() as u32
+    }
+
+    /// Returns the rows of modules from top to bottom, where `true` is a dark module.
+    pub fn modules(&self) -> Vec<Vec<bool>> {
+        self.0
+            .to_colors()
+            .chunks(self.0.width())
+            .map(|row| row.iter().map(|&color| color == Color::Dark).collect())
+            .collect()
+    }
+
+    /// Checks that the code renders to an image of at most [`MAX_IMAGE_WIDTH`] pixels wide.
+    fn check_render_options(&self, options: &ZcashQrRenderOptions) -> ZcashResult<()> {
+        if options.module_size == 0 {
+            return Err("The module size must be at least one pixel".into());
+        }
+        let modules = if options.quiet_zone {
+            self.0.width().checked_add(8)
+        } else {
+            Some(self.0.width())
+        };
+        match modules.and_then(|m| m.checked_mul(options.module_size as usize)) {
+            Some(width) if width <= MAX_IMAGE_WIDTH => Ok(()),
+            _ => Err(ZcashError::Message {
+                error: format!(
+                    "The rendered image would be wider than {} pixels",
+                    MAX_IMAGE_WIDTH
+                ),
+            }),
+        }
+    }
+
+    /// Renders the code as a black on white SVG document.
+    pub fn to_svg(&self, options: ZcashQrRenderOptions) -> ZcashResult<String> {
+        self.check_render_options(&options)?;
+        Ok(self
+            .0
+            .render()
+            .quiet_zone(options.quiet_zone)
+            .module_dimensions(options.module_size, options.module_size)
+            .dark_color(svg::Color("#000000"))
+            .light_color(svg::Color("#ffffff"))
+            .build())
+    }
+
+    /// Renders the code as a black on white, 8-bit grayscale PNG image.
+    pub fn to_png(&self, options: ZcashQrRenderOptions) -> ZcashResult<Vec<u8>> {
+        self.check_render_options(&options)?;
+        let image = self
+            .0
+            .render::<Luma>()
+            .quiet_zone(options.quiet_zone)
+            .module_dimensions(options.module_size, options.module_size)
+            .build();
+
+        let mut data = Vec::new();
+        let width = image.width as u32;
+        let mut encoder = png::Encoder::new(&mut data, width, width);
+        encoder.set_color(png::ColorType::Grayscale);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder
+            .write_header()
+            .and_then(|mut writer| writer.write_image_data(&image.pixels))
+            .map_err(|e| ZcashError::Message {
+                error: format!("Cannot encode PNG image: {}", e),
+            })?;
+        Ok(data)
+    }
+}
+
+/// A grayscale pixel, for rendering codes to PNG images without an image library.
+#[derive(Clone, Copy)]
+struct Luma(u8);
+
+/// A square grayscale image, stored row by row.
+struct LumaImage {
+    width: usize,
+    pixels: Vec<u8>,
+}
+
+impl qrcode::render::Pixel for Luma {
+    type Image = LumaImage;
+    type Canvas = LumaCanvas;
+
+    fn default_color(color: Color) -> Self {
+        Luma(color.select(0x00, 0xFF))
+    }
+}
+
+struct LumaCanvas {
+    width: usize,
+    dark: u8,
+    pixels: Vec<u8>,
+}
+
+impl qrcode::render::Canvas for LumaCanvas {
+    type Pixel = Luma;
+    type Image = LumaImage;
+
+    fn new(width: u32, height: u32, dark_pixel: Luma, light_pixel: Luma) -> Self {
+        let (width, height) = (width as usize, height as usize);
+        let len = width
+            .checked_mul(height)
+            .expect("The image size is checked before rendering");
+        LumaCanvas {
+            width,
+            dark: dark_pixel.0,
+            pixels: vec![light_pixel.0; len],
+        }
+    }
+
+    fn draw_dark_pixel(&mut self, x: u32, y: u32) {
+        let index = (y as usize)
+            .checked_mul(self.width)
+            .and_then(|row| row.checked_add(x as usize))
+            .expect("The pixel lies within the canvas");
+        self.pixels[index] = self.dark;
+    }
+
+    fn into_image(self) -> LumaImage {
+        LumaImage {
+            width: self.width,
+            pixels: self.pixels,
+        }
+    }
+}
@@ -0,0 +1,131 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use zcash_client_backend::keys::UnifiedFullViewingKey;
+use zcash_client_backend::scanning;
+use zcash_client_backend::scanning::ScanningKey;
+use zcash_primitives::sapling::{self, NullifierDerivingKey, SaplingIvk};
+use zcash_primitives::zip32::{sapling::DiversifiableFullViewingKey, AccountId, Scope};
+
+use crate::{
+    TupleAccountIdAndSaplingNullifier, ZcashAccountId, ZcashBlockMetadata, ZcashCompactBlock,
+    ZcashConsensusParameters, ZcashError, ZcashNullifierDerivingKey, ZcashResult, ZcashSaplingIvk,
+    ZcashScannedBlock, ZcashScope, ZcashUnifiedFullViewingKey,
+};
+
+/// A Sapling incoming viewing key of an account, with the nullifier deriving key of the
+/// same scope so that the nullifiers of the received notes can be computed.
+pub struct ZcashSaplingScanningKey {
+    pub account_id: ZcashAccountId,
+    pub scope: ZcashScope,
+    pub ivk: Arc<ZcashSaplingIvk>,
+    pub nk: Arc<ZcashNullifierDerivingKey>,
+}
+
+/// Scans a [`ZcashCompactBlock`] with a set of unified full viewing keys, without touching
+/// any wallet or block database.
+///
+/// Returns a [`ZcashScannedBlock`] with the transactions relevant to the given keys, the
+/// nullifiers revealed by the block and its Sapling note commitments. Spends are detected
+/// by matching `sapling_nullifiers` against the nullifiers of the block.
+///
+/// `prior_block_metadata` describes the block preceding `block`; the note commitment tree
+/// positions of the received notes are computed from its Sapling tree size. If it is not
+/// provided, the tree size is taken from the block's chain metadata.
+///
+/// Only the Sapling outputs of the block are scanned: the scanned blocks of this version of
+/// librustzcash do not hold Orchard data, so Orchard actions are ignored.
+pub fn scan_block(
+    params: ZcashConsensusParameters,
+    block: Arc<ZcashCompactBlock>,
+    ufvks: HashMap<ZcashAccountId, Arc<ZcashUnifiedFullViewingKey>>,
+    sapling_nullifiers: Vec<TupleAccountIdAndSaplingNullifier>,
+    prior_block_metadata: Option<Arc<ZcashBlockMetadata>>,
+) -> ZcashResult<Arc<ZcashScannedBlock>> {
+    let dfvks = ufvks
+        .iter()
+        .filter_map(|(account, ufvk)| {
+            let ufvk: UnifiedFullViewingKey = (**ufvk).clone().into();
+            ufvk.sapling()
+                .map(|dfvk| (AccountId::from(*account), dfvk.clone()))
+        })
+        .collect::<Vec<(AccountId, DiversifiableFullViewingKey)>>();
+
+    let vks = dfvks
+        .iter()
+        .map(|(account, dfvk)| (account, dfvk))
+        .collect::<Vec<_>>();
+
+    scan_block_with_keys(
+        params,
+        block,
+        &vks,
+        sapling_nullifiers,
+        prior_block_metadata,
+    )
+}
+
+/// Scans a [`ZcashCompactBlock`] like [`scan_block`], with Sapling incoming viewing keys
+/// instead of unified full viewing keys.
+///
+/// Each key only detects the notes received in its scope, so both the external and the
+/// internal keys of an account are needed to find its change notes.
+pub fn scan_block_with_ivks(
+    params: ZcashConsensusParameters,
+    block: Arc<ZcashCompactBlock>,
+    keys: Vec<ZcashSaplingScanningKey>,
+    sapling_nullifiers: Vec<TupleAccountIdAndSaplingNullifier>,
+    prior_block_metadata: Option<Arc<ZcashBlockMetadata>>,
+) -> ZcashResult<Arc<ZcashScannedBlock>> {
+    let keys = keys
+        .into_iter()
+        .map(|key| {
+            (
+                AccountId::from(key.account_id),
+                (
+                    Scope::from(key.scope),
+                    SaplingIvk::from(key.ivk.as_ref()),
+                    NullifierDerivingKey::from(key.nk.as_ref()),
+                ),
+            )
+        })
+        .collect::<Vec<_>>();
+
+    let vks = keys
+        .iter()
+        .map(|(account, key)| (account, key))
+        .collect::<Vec<_>>();
+
+    scan_block_with_keys(
+        params,
+        block,
+        &vks,
+        sapling_nullifiers,
+        prior_block_metadata,
+    )
+}
+
+fn scan_block_with_keys<K: ScanningKey<Nf = sapling::Nullifier>>(
+    params: ZcashConsensusParameters,
+    block: Arc<ZcashCompactBlock>,
+    vks: &[(&AccountId, &K)],
+    sapling_nullifiers: Vec<TupleAccountIdAndSaplingNullifier>,
+    prior_block_metadata: Option<Arc<ZcashBlockMetadata>>,
+) -> ZcashResult<Arc<ZcashScannedBlock>> {
+    let sapling_nullifiers = sapling_nullifiers
+        .into_iter()
+        .map(|x| (x.account_id.into(), (*x.sapling_nullifier).into()))
+        .collect::<Vec<(AccountId, sapling::Nullifier)>>();
+
+    scanning::scan_block(
+        &params,
+        (*block).clone().into(),
+        vks,
+        &sapling_nullifiers[..],
+        prior_block_metadata.map(|x| (*x).into()).as_ref(),
+    )
+    .map(|scanned| Arc::new(scanned.into()))
+    .map_err(|e| ZcashError::Message {
+        error: format!("Error for scan_block: {}", e),
+    })
+}
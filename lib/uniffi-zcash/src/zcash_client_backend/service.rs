@@ -1,4 +1,6 @@
-use crate::{ZcashError, ZcashResult};
+use std::sync::Arc;
+
+use crate::{ZcashCommitmentTree, ZcashError, ZcashOrchardCommitmentTree, ZcashResult};
 use derive_more::{From, Into};
 use prost::Message;
 use zcash_client_backend::proto::service::TreeState;
@@ -31,4 +33,24 @@ pub fn from_bytes(bytes: Vec<u8>) -> ZcashResult<Self> {
         })?;
         Ok(Self(treestate))
     }
+
+    /// Deserializes and returns the Sapling note commitment tree field of the tree state.
+    pub fn sapling_tree(&self) -> ZcashResult<Arc<ZcashCommitmentTree>> {
+        Ok(Arc::new(self.0.sapling_tree()?.into()))
+    }
+
+    /// Deserializes and returns the Orchard note commitment tree field of the tree state.
+    ///
+    /// Tree states from before NU5 activation have an empty Orchard tree field, which is
+    /// parsed as an empty tree.
+    pub fn orchard_tree(&self) -> ZcashResult<Arc<ZcashOrchardCommitmentTree>> {
+        if self.0.orchard_tree.is_empty() {
+            return Ok(Arc::new(ZcashOrchardCommitmentTree::empty()));
+        }
+
+        let bytes = hex::decode(&self.0.orchard_tree).map_err(|e| ZcashError::Message {
+            error: format!("Hex decoding of Orchard tree bytes failed: {:?}", e),
+        })?;
+        Ok(Arc::new(ZcashOrchardCommitmentTree::from_bytes(&bytes)?))
+    }
 }
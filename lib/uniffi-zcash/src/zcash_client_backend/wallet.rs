@@ -10,8 +10,9 @@
 // use zcash_primitives::transaction::components::sapling::fees::InputView;
 
 use crate::{
-    ZcashAmount, ZcashBlockHeight, ZcashDiversifier, ZcashOutPoint, ZcashReceivedNoteId,
-    ZcashResult, ZcashTransparentAddress, ZcashTxId, ZcashTxOut,
+    ZcashAccountId, ZcashAmount, ZcashBlockHeight, ZcashDiversifier, ZcashOutPoint,
+    ZcashReceivedNoteId, ZcashResult, ZcashSaplingExtractedNoteCommitment, ZcashSaplingNote,
+    ZcashSaplingNullifier, ZcashTransparentAddress, ZcashTxId, ZcashTxOut,
 };
 use zcash_client_sqlite::ReceivedNoteId;
 
@@ -32,6 +33,23 @@ fn clone(&self) -> Self {
     }
 }
 
+impl ZcashWalletSaplingSpend {
+    /// Returns the index of the spend within its transaction.
+    pub fn index(&self) -> u32 {
+        self.0.index() as u32
+    }
+
+    /// Returns the nullifier revealed by the spend.
+    pub fn nf(&self) -> Arc<ZcashSaplingNullifier> {
+        Arc::new((*self.0.nf()).into())
+    }
+
+    /// Returns the account whose note was spent.
+    pub fn account(&self) -> ZcashAccountId {
+        self.0.account().into()
+    }
+}
+
 #[derive(From, Into)]
 pub struct ZcashWalletSaplingOutput(WalletSaplingOutput<sapling::Nullifier>);
 
@@ -50,6 +68,48 @@ fn clone(&self) -> Self {
     }
 }
 
+impl ZcashWalletSaplingOutput {
+    /// Returns the index of the output within its transaction.
+    pub fn index(&self) -> u32 {
+        self.0.index() as u32
+    }
+
+    /// Returns the note commitment of the output.
+    pub fn cmu(&self) -> Arc<ZcashSaplingExtractedNoteCommitment> {
+        Arc::new((*self.0.cmu()).into())
+    }
+
+    /// Returns the ephemeral public key of the output.
+    pub fn ephemeral_key(&self) -> Vec<u8> {
+        self.0.ephemeral_key().0.to_vec()
+    }
+
+    /// Returns the account that received the output.
+    pub fn account(&self) -> ZcashAccountId {
+        self.0.account().into()
+    }
+
+    /// Returns the decrypted note.
+    pub fn note(&self) -> Arc<ZcashSaplingNote> {
+        Arc::new(self.0.note().clone().into())
+    }
+
+    /// Returns whether the output is change from a transaction sent by the wallet.
+    pub fn is_change(&self) -> bool {
+        self.0.is_change()
+    }
+
+    /// Returns the position of the note in the Sapling note commitment tree.
+    pub fn note_commitment_tree_position(&self) -> Arc<MerkleTreePosition> {
+        Arc::new(self.0.note_commitment_tree_position().into())
+    }
+
+    /// Returns the nullifier of the note.
+    pub fn nf(&self) -> Arc<ZcashSaplingNullifier> {
+        Arc::new((*self.0.nf()).into())
+    }
+}
+
 /// A subset of a [`ZcashTransaction`] relevant to wallets and light clients.
 #[derive(From, Into)]
 pub struct ZcashWalletTx(WalletTx<sapling::Nullifier>);
@@ -74,6 +134,37 @@ pub fn new(
                 .collect(),
         })
     }
+
+    /// Returns the id of the transaction.
+    pub fn txid(&self) -> Arc<ZcashTxId> {
+        Arc::new(self.0.txid.into())
+    }
+
+    /// Returns the index of the transaction within its block.
+    pub fn index(&self) -> u32 {
+        self.0.index as u32
+    }
+
+    /// Returns the Sapling spends of the transaction that belong to the wallet.
+    pub fn sapling_spends(&self) -> Vec<Arc<ZcashWalletSaplingSpend>> {
+        self.0
+            .sapling_spends
+            .iter()
+            .map(|x| {
+                Arc::new(WalletSaplingSpend::from_parts(x.index(), *x.nf(), x.account()).into())
+            })
+            .collect()
+    }
+
+    /// Returns the Sapling outputs of the transaction received by the wallet.
+    pub fn sapling_outputs(&self) -> Vec<Arc<ZcashWalletSaplingOutput>> {
+        self.clone()
+            .0
+            .sapling_outputs
+            .into_iter()
+            .map(|x| Arc::new(x.into()))
+            .collect()
+    }
 }
 
 impl Clone for ZcashWalletTx {
@@ -157,6 +248,16 @@ pub fn value(&self) -> Arc<ZcashAmount> {
 #[derive(Copy, Clone, From, Into)]
 pub struct MerkleTreePosition(Position);
 
+impl MerkleTreePosition {
+    pub fn new(value: u64) -> Self {
+        Self(Position::from(value))
+    }
+
+    pub fn value(&self) -> u64 {
+        self.0.into()
+    }
+}
+
 #[derive(Debug, From, Into)]
 pub struct ZcashReceivedSaplingNote(ReceivedSaplingNote<ReceivedNoteId>);
 
@@ -1,10 +1,16 @@
 use std::fmt;
 
-use derive_more::{From, Into};
+use std::collections::BTreeSet;
+use std::sync::Arc;
 
-use zcash_client_backend::zip321::{TransactionRequest, Zip321Error};
+use zcash_client_backend::address::RecipientAddress;
+use zcash_client_backend::zip321::{Payment, TransactionRequest, Zip321Error};
+use zcash_primitives::legacy::TransparentAddress;
 
-use crate::{ZcashConsensusParameters, ZcashPayment};
+use crate::{
+    ZcashConsensusParameters, ZcashPayment, ZcashRecipientAddress, ZcashTexAddress,
+    ZcashTransparentAddress,
+};
 
 #[derive(Debug, thiserror::Error)]
 pub enum ZcashZip321Error {
@@ -95,8 +101,30 @@ fn from(value: Zip321Error) -> Self {
 /// When constructing a transaction in response to such a request,
 /// a separate output should be added to the transaction for each
 /// payment value in the request.
-#[derive(Debug, PartialEq, Eq, From, Into)]
-pub struct ZcashTransactionRequest(TransactionRequest);
+#[derive(Debug, PartialEq, Eq)]
+pub struct ZcashTransactionRequest {
+    request: TransactionRequest,
+    /// The public key hashes of the P2PKH recipients that were given as TEX addresses.
+    ///
+    /// Payments are matched by hash, so a P2PKH payment sharing the hash of a TEX payment of
+    /// the same request is also routed through an ephemeral address.
+    tex_recipients: BTreeSet<[u8; 20]>,
+}
+
+impl From<TransactionRequest> for ZcashTransactionRequest {
+    fn from(request: TransactionRequest) -> Self {
+        ZcashTransactionRequest {
+            request,
+            tex_recipients: BTreeSet::new(),
+        }
+    }
+}
+
+impl From<ZcashTransactionRequest> for TransactionRequest {
+    fn from(request: ZcashTransactionRequest) -> Self {
+        request.request
+    }
+}
 
 impl ZcashTransactionRequest {
     /// Constructs a new empty transaction request.
@@ -106,29 +134,119 @@ pub fn empty() -> Self {
 
     /// Constructs a new transaction request that obeys the ZIP-321 invariants
     pub fn new(payments: Vec<ZcashPayment>) -> ZcashZip321Result<Self> {
-        TransactionRequest::new(payments.into_iter().map(From::from).collect())
-            .map(From::from)
-            .map_err(From::from)
+        let tex_recipients = payments
+            .iter()
+            .filter_map(|payment| match payment.recipient_address.as_ref() {
+                ZcashRecipientAddress::Tex(addr) => addr.to_bytes().try_into().ok(),
+                _ => None,
+            })
+            .collect();
+        let request = TransactionRequest::new(payments.into_iter().map(From::from).collect())?;
+        Ok(ZcashTransactionRequest {
+            request,
+            tex_recipients,
+        })
     }
 
     /// Parse the provided URI to a payment request value.
+    ///
+    /// TEX recipients are accepted wherever a transparent address is.
     pub fn from_uri(params: ZcashConsensusParameters, uri: &str) -> ZcashZip321Result<Self> {
-        TransactionRequest::from_uri(&params, uri)
-            .map_err(From::from)
-            .map(From::from)
+        let mut tex_recipients = BTreeSet::new();
+        let uri = map_uri_addresses(uri, |address| {
+            let tex = ZcashTexAddress::decode(params, address).ok()?;
+            tex_recipients.insert(tex.to_bytes().try_into().ok()?);
+            Some(tex.to_transparent_address().encode(params))
+        });
+        let request = TransactionRequest::from_uri(&params, &uri)?;
+        Ok(ZcashTransactionRequest {
+            request,
+            tex_recipients,
+        })
     }
 
     /// Returns the slice of payments that make up this request.
     pub fn payments(&self) -> Vec<ZcashPayment> {
-        self.0.payments().iter().map(From::from).collect()
+        self.request
+            .payments()
+            .iter()
+            .map(|payment| {
+                let mut zpayment = ZcashPayment::from(payment);
+                if let Some(tex) = self.tex_recipient(payment) {
+                    zpayment.recipient_address =
+                        Arc::new(ZcashRecipientAddress::Tex(Arc::new(tex)));
+                }
+                zpayment
+            })
+            .collect()
     }
 
     /// Convert this request to a URI string.
     ///
     /// Returns None if the payment request is empty.
     pub fn to_uri(&self, params: ZcashConsensusParameters) -> Option<String> {
-        self.0.to_uri(&params)
+        let uri = self.request.to_uri(&params)?;
+        Some(map_uri_addresses(
+            &uri,
+            |address| match ZcashTransparentAddress::decode(params, address)
+                .ok()?
+                .into()
+            {
+                TransparentAddress::PublicKey(data) if self.tex_recipients.contains(&data) => {
+                    Some(ZcashTexAddress::from_bytes(&data).ok()?.encode(params))
+                }
+                _ => None,
+            },
+        ))
+    }
+
+    /// Returns the TEX address a payment of this request was requested to, if any.
+    pub(crate) fn tex_recipient(&self, payment: &Payment) -> Option<ZcashTexAddress> {
+        match payment.recipient_address {
+            RecipientAddress::Transparent(TransparentAddress::PublicKey(data))
+                if self.tex_recipients.contains(&data) =>
+            {
+                ZcashTexAddress::from_bytes(&data).ok()
+            }
+            _ => None,
+        }
+    }
+
+    pub(crate) fn has_tex_recipients(&self) -> bool {
+        self.request
+            .payments()
+            .iter()
+            .any(|payment| self.tex_recipient(payment).is_some())
+    }
+}
+
+/// Replaces the addresses of a ZIP 321 URI for which `f` returns a replacement.
+fn map_uri_addresses(uri: &str, mut f: impl FnMut(&str) -> Option<String>) -> String {
+    let (path, query) = match uri.split_once('?') {
+        Some((path, query)) => (path, Some(query)),
+        None => (uri, None),
+    };
+
+    let mut result = match path.strip_prefix("zcash:") {
+        Some(address) => format!(
+            "zcash:{}",
+            f(address).unwrap_or_else(|| address.to_string())
+        ),
+        None => path.to_string(),
+    };
+
+    if let Some(query) = query {
+        let params = query.split('&').map(|param| match param.split_once('=') {
+            Some((key, value)) if key == "address" || key.starts_with("address.") => {
+                format!("{}={}", key, f(value).unwrap_or_else(|| value.to_string()))
+            }
+            _ => param.to_string(),
+        });
+        result.push('?');
+        result.push_str(&params.collect::<Vec<_>>().join("&"));
     }
+
+    result
 }
 
 impl Clone for ZcashTransactionRequest {
@@ -0,0 +1,427 @@
+use std::collections::{BTreeSet, HashSet};
+use std::convert::Infallible;
+use std::ops::Range;
+use std::sync::Arc;
+
+use incrementalmerkletree::Retention;
+use orchard::note_encryption::OrchardDomain;
+use rusqlite::{named_params, Connection, Transaction, TransactionBehavior};
+use shardtree::error::ShardTreeError;
+use zcash_client_backend::data_api::chain::BlockSource;
+use zcash_client_backend::data_api::scanning::ScanPriority;
+use zcash_client_backend::data_api::{AccountBirthday, WalletCommitmentTrees};
+use zcash_client_backend::encoding::AddressCodec;
+use zcash_client_backend::keys::{UnifiedFullViewingKey, UnifiedSpendingKey};
+use zcash_client_sqlite::{FsBlockDb, WalletDb};
+use zcash_note_encryption::try_compact_note_decryption;
+use zcash_primitives::consensus::{BlockHeight, NetworkUpgrade, Parameters};
+use zcash_primitives::legacy::keys::IncomingViewingKey;
+use zcash_primitives::legacy::TransparentAddress;
+use zcash_primitives::sapling::note_encryption::{
+    try_sapling_compact_note_decryption, PreparedIncomingViewingKey,
+};
+use zcash_primitives::transaction::components::sapling::CompactOutputDescription;
+use zcash_primitives::zip32::{AccountId, Scope};
+
+use super::WalletCommitmentTreesError;
+use crate::{
+    compact_orchard_action, TupleAccountIdAndUnifiedSpendingKey, ZcashAccountBirthday,
+    ZcashBlockHeight, ZcashConsensusParameters, ZcashError, ZcashResult, ZcashWalletDb,
+    ZcashWalletTransparentOutput,
+};
+
+/// Number of external transparent addresses checked for each account,
+/// following the BIP 44 address gap limit.
+const TRANSPARENT_ADDRESS_GAP_LIMIT: u32 = 20;
+
+impl ZcashWalletDb {
+    /// Discovers the ZIP 32 accounts of `seed` that have been used, and creates them in the wallet.
+    ///
+    /// Accounts are derived in order starting at account 0. An account is considered used if
+    /// a Sapling output or an Orchard action in the blocks cached under `fsblockdb_root` (from
+    /// `from_height` on) can be decrypted with one of its incoming viewing keys, or if one of `transparent_outputs`
+    /// was received by one of its first external transparent addresses. Discovery stops after
+    /// `gap_limit` consecutive unused accounts.
+    ///
+    /// Candidate accounts are checked `gap_limit` at a time, with a single pass over the block
+    /// cache for each batch.
+    ///
+    /// Account identifiers are allocated sequentially by the wallet, so every account up to
+    /// and including the highest used one is created, each with the given `birthday`. Either
+    /// all of these accounts are created, or none is. The wallet must not contain any account
+    /// yet.
+    pub fn discover_accounts(
+        &self,
+        seed: Vec<u8>,
+        birthday: Arc<ZcashAccountBirthday>,
+        fsblockdb_root: String,
+        from_height: Arc<ZcashBlockHeight>,
+        transparent_outputs: Vec<Arc<ZcashWalletTransparentOutput>>,
+        gap_limit: u32,
+    ) -> ZcashResult<Vec<TupleAccountIdAndUnifiedSpendingKey>> {
+        if gap_limit == 0 {
+            return Err("gap_limit must be greater than zero".into());
+        }
+
+        if !self.get_unified_full_viewing_keys()?.is_empty() {
+            return Err("Account discovery requires a wallet without accounts".into());
+        }
+
+        let db_cache = FsBlockDb::for_path(fsblockdb_root).map_err(|e| ZcashError::Message {
+            error: format!("FsBlockDbError: {:?}", e),
+        })?;
+
+        let transparent_receivers = transparent_outputs
+            .iter()
+            .map(|output| *output.0.recipient_address())
+            .collect::<HashSet<TransparentAddress>>();
+
+        let mut last_used: Option<u32> = None;
+        let mut next_account = 0u32;
+
+        loop {
+            let end = last_used
+                .map_or(0, |last_used| last_used + 1)
+                .checked_add(gap_limit)
+                .ok_or("Account index out of range")?;
+            if next_account >= end {
+                break;
+            }
+
+            let ufvks = (next_account..end)
+                .map(|account| {
+                    UnifiedSpendingKey::from_seed(&self.params, &seed, AccountId::from(account))
+                        .map(|usk| (account, usk.to_unified_full_viewing_key()))
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+
+            if let Some(used) = last_used_account(
+                &self.params,
+                &db_cache,
+                from_height.as_ref().into(),
+                &ufvks,
+                &transparent_receivers,
+            )? {
+                last_used = Some(used);
+            }
+
+            next_account = end;
+        }
+
+        match last_used {
+            Some(last_used) => {
+                self.create_accounts(&seed, birthday.as_ref().clone().into(), last_used)
+            }
+            None => Ok(vec![]),
+        }
+    }
+
+    /// Creates the accounts `0..=last_account` of `seed`, all with the same `birthday`.
+    ///
+    /// The librustzcash wallet commits each account in its own SQL transaction, so the
+    /// accounts are written here in a single `BEGIN IMMEDIATE` transaction, the way
+    /// `WalletWrite::create_account` writes one. The birthday frontier is inserted in the
+    /// note commitment tree beforehand; it only describes the chain, so it does no harm if
+    /// the accounts are not created.
+    fn create_accounts(
+        &self,
+        seed: &[u8],
+        birthday: AccountBirthday,
+        last_account: u32,
+    ) -> ZcashResult<Vec<TupleAccountIdAndUnifiedSpendingKey>> {
+        let usks = (0..=last_account)
+            .map(|account| {
+                UnifiedSpendingKey::from_seed(&self.params, seed, AccountId::from(account))
+                    .map(|usk| (AccountId::from(account), usk))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if let Some(frontier) = birthday.sapling_frontier().value() {
+            WalletDb::for_path(&self.path, self.params)
+                .map_err(db_err)?
+                .with_sapling_tree_mut::<_, _, ShardTreeError<WalletCommitmentTreesError>>(|tree| {
+                    tree.insert_frontier_nodes(
+                        frontier.clone(),
+                        Retention::Checkpoint {
+                            id: birthday.height() - 1,
+                            is_marked: false,
+                        },
+                    )
+                })
+                .map_err(|e| ZcashError::Message {
+                    error: format!("ShardTreeError: {:?}", e),
+                })?;
+        }
+
+        let mut conn = Connection::open(&self.path).map_err(db_err)?;
+        let tx = conn
+            .transaction_with_behavior(TransactionBehavior::Immediate)
+            .map_err(db_err)?;
+
+        let account_count: u32 = tx
+            .query_row("SELECT COUNT(*) FROM accounts", [], |row| row.get(0))
+            .map_err(db_err)?;
+        if account_count > 0 {
+            return Err("Account discovery requires a wallet without accounts".into());
+        }
+
+        for (account, usk) in usks.iter() {
+            let ufvk = usk.to_unified_full_viewing_key();
+            tx.execute(
+                "INSERT INTO accounts (account, ufvk, birthday_height, recover_until_height)
+                VALUES (:account, :ufvk, :birthday_height, :recover_until_height)",
+                named_params![
+                    ":account": u32::from(*account),
+                    ":ufvk": ufvk.encode(&self.params),
+                    ":birthday_height": u32::from(birthday.height()),
+                    ":recover_until_height": birthday.recover_until().map(u32::from),
+                ],
+            )
+            .map_err(db_err)?;
+
+            // The diversifier index is stored in big-endian order, as the wallet does.
+            let (address, mut diversifier_index) = ufvk.default_address();
+            diversifier_index.0.reverse();
+            tx.execute(
+                "INSERT INTO addresses (
+                    account, diversifier_index_be, address, cached_transparent_receiver_address
+                )
+                VALUES (
+                    :account, :diversifier_index_be, :address, :cached_transparent_receiver_address
+                )",
+                named_params![
+                    ":account": u32::from(*account),
+                    ":diversifier_index_be": &diversifier_index.0[..],
+                    ":address": address.encode(&self.params),
+                    ":cached_transparent_receiver_address":
+                        address.transparent().map(|r| r.encode(&self.params)),
+                ],
+            )
+            .map_err(db_err)?;
+        }
+
+        let sapling_activation_height = self
+            .params
+            .activation_height(NetworkUpgrade::Sapling)
+            .ok_or("Sapling activation height must be available")?;
+        if sapling_activation_height < birthday.height() {
+            replace_scan_range(
+                &tx,
+                sapling_activation_height..birthday.height(),
+                ScanPriority::Ignored,
+            )?;
+        }
+
+        // Blocks from the birthday up to the chain tip known to the wallet, if any, are
+        // scanned again so that the notes of the new accounts are found.
+        let chain_end: Option<u32> = tx
+            .query_row("SELECT MAX(block_range_end) FROM scan_queue", [], |row| {
+                row.get(0)
+            })
+            .map_err(db_err)?;
+        if let Some(chain_end) = chain_end.map(BlockHeight::from) {
+            if birthday.height() < chain_end {
+                replace_scan_range(&tx, birthday.height()..chain_end, ScanPriority::Historic)?;
+            }
+        }
+
+        tx.commit().map_err(db_err)?;
+
+        Ok(usks
+            .into_iter()
+            .map(|(account, usk)| TupleAccountIdAndUnifiedSpendingKey {
+                account_id: account.into(),
+                unified_spending_key: Arc::new(usk.into()),
+            })
+            .collect())
+    }
+}
+
+fn db_err(e: rusqlite::Error) -> ZcashError {
+    ZcashError::Message {
+        error: format!("Cannot access the DB: {:?}", e),
+    }
+}
+
+/// Replaces the part of the scan queue covering `range` with a single range of `priority`,
+/// keeping the parts of the overlapped ranges that lie outside of it.
+fn replace_scan_range(
+    tx: &Transaction,
+    range: Range<BlockHeight>,
+    priority: ScanPriority,
+) -> ZcashResult<()> {
+    let (start, end) = (u32::from(range.start), u32::from(range.end));
+
+    let overlapped = tx
+        .prepare(
+            "SELECT block_range_start, block_range_end, priority FROM scan_queue
+            WHERE block_range_start < :end AND block_range_end > :start",
+        )
+        .and_then(|mut stmt| {
+            stmt.query_map(named_params![":start": start, ":end": end], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+            })?
+            .collect::<Result<Vec<(u32, u32, i64)>, _>>()
+        })
+        .map_err(db_err)?;
+
+    tx.execute(
+        "DELETE FROM scan_queue WHERE block_range_start < :end AND block_range_end > :start",
+        named_params![":start": start, ":end": end],
+    )
+    .map_err(db_err)?;
+
+    let kept = overlapped
+        .into_iter()
+        .flat_map(|(other_start, other_end, other_priority)| {
+            [
+                (other_start, other_end.min(start), other_priority),
+                (other_start.max(end), other_end, other_priority),
+            ]
+        });
+
+    for (range_start, range_end, range_priority) in kept
+        .chain([(start, end, scan_priority_code(priority))])
+        .filter(|(range_start, range_end, _)| range_start < range_end)
+    {
+        tx.execute(
+            "INSERT INTO scan_queue (block_range_start, block_range_end, priority)
+            VALUES (:start, :end, :priority)",
+            named_params![
+                ":start": range_start,
+                ":end": range_end,
+                ":priority": range_priority,
+            ],
+        )
+        .map_err(db_err)?;
+    }
+
+    Ok(())
+}
+
+/// Returns the code the wallet stores in the scan queue for `priority`.
+fn scan_priority_code(priority: ScanPriority) -> i64 {
+    match priority {
+        ScanPriority::Ignored => 0,
+        ScanPriority::Scanned => 10,
+        ScanPriority::Historic => 20,
+        ScanPriority::OpenAdjacent => 30,
+        ScanPriority::FoundNote => 40,
+        ScanPriority::ChainTip => 50,
+        ScanPriority::Verify => 60,
+    }
+}
+
+/// Returns the highest of `ufvks` accounts that received funds.
+fn last_used_account(
+    params: &ZcashConsensusParameters,
+    db_cache: &FsBlockDb,
+    from_height: BlockHeight,
+    ufvks: &[(u32, UnifiedFullViewingKey)],
+    transparent_receivers: &HashSet<TransparentAddress>,
+) -> ZcashResult<Option<u32>> {
+    let mut used = BTreeSet::new();
+
+    for (account, ufvk) in ufvks {
+        if let Some(tfvk) = ufvk.transparent() {
+            let external_ivk = tfvk.derive_external_ivk()?;
+
+            // Some child indices do not produce a valid address, those are just skipped.
+            if (0..TRANSPARENT_ADDRESS_GAP_LIMIT)
+                .filter_map(|child_index| external_ivk.derive_address(child_index).ok())
+                .any(|address| transparent_receivers.contains(&address))
+            {
+                used.insert(*account);
+            }
+        }
+    }
+
+    let unused_ufvks = ufvks
+        .iter()
+        .filter(|(account, _)| !used.contains(account))
+        .collect::<Vec<_>>();
+
+    let sapling_ivks = unused_ufvks
+        .iter()
+        .filter_map(|(account, ufvk)| ufvk.sapling().map(|dfvk| (*account, dfvk)))
+        .flat_map(|(account, dfvk)| {
+            [Scope::External, Scope::Internal]
+                .into_iter()
+                .map(move |scope| {
+                    (
+                        account,
+                        PreparedIncomingViewingKey::new(&dfvk.to_ivk(scope)),
+                    )
+                })
+        })
+        .collect::<Vec<_>>();
+
+    let orchard_ivks = unused_ufvks
+        .iter()
+        .filter_map(|(account, ufvk)| ufvk.orchard().map(|fvk| (*account, fvk)))
+        .flat_map(|(account, fvk)| {
+            [
+                orchard::keys::Scope::External,
+                orchard::keys::Scope::Internal,
+            ]
+            .into_iter()
+            .map(move |scope| {
+                (
+                    account,
+                    orchard::keys::PreparedIncomingViewingKey::new(&fvk.to_ivk(scope)),
+                )
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let max_cached_height = db_cache
+        .get_max_cached_height()
+        .map_err(|e| ZcashError::Message {
+            error: format!("FsBlockDbError: {:?}", e),
+        })?;
+
+    // Nothing to scan if the cache does not reach `from_height` yet.
+    if !(sapling_ivks.is_empty() && orchard_ivks.is_empty())
+        && max_cached_height.is_some_and(|h| h >= from_height)
+    {
+        db_cache
+            .with_blocks::<_, Infallible>(Some(from_height), None, |block| {
+                let height = block.height();
+
+                for tx in block.vtx {
+                    for output in tx
+                        .outputs
+                        .into_iter()
+                        .filter_map(|output| CompactOutputDescription::try_from(output).ok())
+                    {
+                        for (account, ivk) in sapling_ivks.iter() {
+                            if !used.contains(account)
+                                && try_sapling_compact_note_decryption(params, height, ivk, &output)
+                                    .is_some()
+                            {
+                                used.insert(*account);
+                            }
+                        }
+                    }
+
+                    for action in tx.actions.iter().filter_map(compact_orchard_action) {
+                        let domain = OrchardDomain::for_nullifier(action.nullifier());
+                        for (account, ivk) in orchard_ivks.iter() {
+                            if !used.contains(account)
+                                && try_compact_note_decryption(&domain, ivk, &action).is_some()
+                            {
+                                used.insert(*account);
+                            }
+                        }
+                    }
+                }
+
+                Ok(())
+            })
+            .map_err(|e| ZcashError::Message {
+                error: format!("Error while reading cached blocks: {:?}", e),
+            })?;
+    }
+
+    Ok(used.last().copied())
+}
@@ -0,0 +1,44 @@
+use rusqlite::Connection;
+
+use crate::{ZcashError, ZcashResult, ZcashWalletDb};
+
+/// The tables this library keeps next to the ones of `zcash_client_sqlite`.
+///
+/// They are created once by [`ZcashWalletDb::initialize`], after the wallet migrations, and
+/// only reference the accounts of the wallet.
+const EXTENSION_TABLES: &str = "CREATE TABLE IF NOT EXISTS merchant_invoices (
+    id_invoice INTEGER PRIMARY KEY,
+    account INTEGER NOT NULL,
+    address TEXT NOT NULL UNIQUE,
+    diversifier_index BLOB NOT NULL,
+    amount INTEGER NOT NULL,
+    label TEXT,
+    created_at INTEGER NOT NULL,
+    expires_at INTEGER NOT NULL,
+    FOREIGN KEY (account) REFERENCES accounts(account),
+    CONSTRAINT account_diversifier UNIQUE (account, diversifier_index)
+);
+CREATE TABLE IF NOT EXISTS tex_ephemeral_addresses (
+    account INTEGER NOT NULL,
+    address_index INTEGER NOT NULL,
+    address TEXT NOT NULL UNIQUE,
+    funding_txid BLOB,
+    PRIMARY KEY (account, address_index),
+    FOREIGN KEY (account) REFERENCES accounts(account)
+);";
+
+pub(crate) fn init_extension_tables(conn: &Connection) -> ZcashResult<()> {
+    conn.execute_batch(EXTENSION_TABLES)
+        .map_err(|e| ZcashError::Message {
+            error: format!("Error while initializing the extension tables: {:?}", e),
+        })
+}
+
+impl ZcashWalletDb {
+    /// Opens the wallet database for reading and writing the extension tables.
+    pub(crate) fn extensions_conn(&self) -> ZcashResult<Connection> {
+        Connection::open(&self.path).map_err(|e| ZcashError::Message {
+            error: format!("Cannot access the DB: {:?}", e),
+        })
+    }
+}
@@ -0,0 +1,358 @@
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rusqlite::{named_params, OptionalExtension, Row};
+use zcash_client_backend::address::{RecipientAddress, UnifiedAddress};
+use zcash_client_backend::data_api::WalletRead;
+use zcash_client_backend::encoding::AddressCodec;
+use zcash_client_backend::keys::UnifiedFullViewingKey;
+use zcash_client_backend::zip321::TransactionRequest;
+use zcash_client_sqlite::WalletDb;
+use zcash_primitives::transaction::components::{amount::NonNegativeAmount, Amount};
+use zcash_primitives::zip32::{AccountId, DiversifierIndex};
+
+use crate::{
+    ZcashAccountId, ZcashDiversifierIndex, ZcashError, ZcashNonNegativeAmount, ZcashPayment,
+    ZcashRecipientAddress, ZcashResult, ZcashTransactionRequest, ZcashUnifiedAddress,
+    ZcashWalletDb, ZcashZip321Result,
+};
+
+/// An invoice bound to its own diversified Unified Address.
+///
+/// Invoices are stored next to the wallet, in a table created by [`ZcashWalletDb::initialize`].
+pub struct ZcashInvoice {
+    pub id: i64,
+    pub account: ZcashAccountId,
+    pub address: Arc<ZcashUnifiedAddress>,
+    pub diversifier_index: Arc<ZcashDiversifierIndex>,
+    pub amount: Arc<ZcashNonNegativeAmount>,
+    pub label: Option<String>,
+    /// Creation time, in seconds since the Unix epoch.
+    pub created_at: u64,
+    /// Time after which payments are no longer accepted, in seconds since the Unix epoch.
+    pub expires_at: u64,
+}
+
+/// The payment state of an invoice.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ZcashInvoiceState {
+    /// Nothing was received yet, and the invoice has not expired.
+    Unpaid,
+    /// Less than the invoice amount was received, and the invoice has not expired.
+    PartiallyPaid,
+    /// Exactly the invoice amount was received.
+    Paid,
+    /// More than the invoice amount was received.
+    Overpaid,
+    /// Less than the invoice amount was received before the invoice expired.
+    Expired,
+}
+
+pub struct ZcashInvoiceStatus {
+    pub state: ZcashInvoiceState,
+    /// The value received by the invoice address before the invoice expired.
+    pub received: Arc<ZcashNonNegativeAmount>,
+}
+
+const INVOICE_COLUMNS: &str = "id_invoice, account, address, diversifier_index, amount, label,
+    created_at, expires_at";
+
+impl ZcashWalletDb {
+    /// Creates an invoice paid to the next available address of `account`.
+    pub fn create_invoice(
+        &self,
+        account: ZcashAccountId,
+        amount: Arc<ZcashNonNegativeAmount>,
+        expires_at: u64,
+        label: Option<String>,
+    ) -> ZcashResult<ZcashInvoice> {
+        let address = self
+            .get_next_available_address(account)?
+            .ok_or("The account does not exist")?;
+        let ua: UnifiedAddress = (*address).clone().into();
+        let diversifier_index = diversifier_index_of(&self.account_ufvk(account)?, &ua)?;
+
+        self.insert_invoice(account, ua, diversifier_index, amount, expires_at, label)
+    }
+
+    /// Creates an invoice paid to the address of `account` at `diversifier_index`.
+    ///
+    /// The address must already have been issued by the wallet, for instance through
+    /// [`ZcashWalletDb::get_next_available_address`], so that the wallet tracks its receivers.
+    /// Fails if the index does not produce a valid Sapling diversifier, if it is past the
+    /// current address of the account, or if another invoice already uses the address.
+    pub fn create_invoice_at(
+        &self,
+        account: ZcashAccountId,
+        diversifier_index: Arc<ZcashDiversifierIndex>,
+        amount: Arc<ZcashNonNegativeAmount>,
+        expires_at: u64,
+        label: Option<String>,
+    ) -> ZcashResult<ZcashInvoice> {
+        let diversifier_index: DiversifierIndex = diversifier_index.as_ref().into();
+        let ufvk = self.account_ufvk(account)?;
+        let ua = ufvk
+            .address(diversifier_index)
+            .ok_or("The diversifier index does not produce a valid address")?;
+
+        // The wallet issues addresses at increasing indices, skipping those without a valid
+        // Sapling diversifier, so every valid index up to the current one has been issued.
+        let current = self
+            .get_current_address(account)?
+            .ok_or("The account does not exist")?;
+        let current_index = diversifier_index_of(&ufvk, &(*current).clone().into())?;
+        if index_value(diversifier_index) > index_value(current_index) {
+            return Err("The wallet has not issued the address at this diversifier index".into());
+        }
+
+        self.insert_invoice(account, ua, diversifier_index, amount, expires_at, label)
+    }
+
+    pub fn get_invoice(&self, id: i64) -> ZcashResult<Option<ZcashInvoice>> {
+        self.extensions_conn()?
+            .query_row(
+                &format!(
+                    "SELECT {} FROM merchant_invoices WHERE id_invoice = :id",
+                    INVOICE_COLUMNS
+                ),
+                named_params![":id": id],
+                |row| self.read_invoice(row),
+            )
+            .optional()
+            .map_err(invoice_err)
+    }
+
+    /// Returns the invoices of `account`, from the oldest to the newest.
+    pub fn get_invoices(&self, account: ZcashAccountId) -> ZcashResult<Vec<ZcashInvoice>> {
+        let conn = self.extensions_conn()?;
+        let mut stmt = conn
+            .prepare(&format!(
+                "SELECT {} FROM merchant_invoices WHERE account = :account ORDER BY id_invoice",
+                INVOICE_COLUMNS
+            ))
+            .map_err(invoice_err)?;
+        let invoices = stmt
+            .query_map(named_params![":account": account.id], |row| {
+                self.read_invoice(row)
+            })
+            .and_then(|rows| rows.collect())
+            .map_err(invoice_err);
+        invoices
+    }
+
+    /// Returns the payment status of an invoice as of `now`, in seconds since the Unix epoch.
+    ///
+    /// Payments are the notes received by the Sapling receiver of the invoice address and the
+    /// outputs received by its transparent receiver, in blocks mined no later than the expiry
+    /// time of the invoice. Change notes and unmined transactions are not counted, so the
+    /// status follows the blocks scanned by the wallet.
+    pub fn get_invoice_status(&self, id: i64, now: u64) -> ZcashResult<ZcashInvoiceStatus> {
+        let invoice = self.get_invoice(id)?.ok_or("The invoice does not exist")?;
+        let ua: UnifiedAddress = (*invoice.address).clone().into();
+        // `WalletRead` only returns unspent notes and outputs, and no block times, so paid
+        // invoices would revert to unpaid once their funds are spent. The received values are
+        // summed from the wallet tables instead, without writing to them.
+        let conn = self.extensions_conn()?;
+
+        let sapling: i64 = match ua.sapling() {
+            Some(addr) => conn
+                .query_row(
+                    "SELECT COALESCE(SUM(rn.value), 0)
+                    FROM sapling_received_notes rn
+                    JOIN transactions t ON t.id_tx = rn.tx
+                    JOIN blocks b ON b.height = t.block
+                    WHERE rn.account = :account
+                    AND rn.diversifier = :diversifier
+                    AND rn.is_change = 0
+                    AND b.time <= :expires_at",
+                    named_params![
+                        ":account": invoice.account.id,
+                        ":diversifier": &addr.diversifier().0[..],
+                        ":expires_at": invoice.expires_at,
+                    ],
+                    |row| row.get(0),
+                )
+                .map_err(invoice_err)?,
+            None => 0,
+        };
+        let transparent: i64 = match ua.transparent() {
+            Some(addr) => conn
+                .query_row(
+                    "SELECT COALESCE(SUM(u.value_zat), 0)
+                    FROM utxos u
+                    JOIN blocks b ON b.height = u.height
+                    WHERE u.address = :address
+                    AND b.time <= :expires_at",
+                    named_params![
+                        ":address": addr.encode(&self.params),
+                        ":expires_at": invoice.expires_at,
+                    ],
+                    |row| row.get(0),
+                )
+                .map_err(invoice_err)?,
+            None => 0,
+        };
+
+        let received = NonNegativeAmount::from_nonnegative_i64(sapling + transparent)
+            .map_err(|_| "The received value is out of range")?;
+        let amount = NonNegativeAmount::from(*invoice.amount);
+        let state = if received > amount {
+            ZcashInvoiceState::Overpaid
+        } else if received == amount {
+            ZcashInvoiceState::Paid
+        } else if now > invoice.expires_at {
+            ZcashInvoiceState::Expired
+        } else if received > NonNegativeAmount::ZERO {
+            ZcashInvoiceState::PartiallyPaid
+        } else {
+            ZcashInvoiceState::Unpaid
+        };
+
+        Ok(ZcashInvoiceStatus {
+            state,
+            received: Arc::new(received.into()),
+        })
+    }
+
+    fn account_ufvk(&self, account: ZcashAccountId) -> ZcashResult<UnifiedFullViewingKey> {
+        WalletDb::for_path(&self.path, self.params)
+            .map_err(|e| ZcashError::Message {
+                error: format!("Cannot access the DB: {:?}", e),
+            })?
+            .get_unified_full_viewing_keys()
+            .map_err(|e| ZcashError::Message {
+                error: format!("Err: {:?}", e),
+            })?
+            .remove(&account.into())
+            .ok_or_else(|| "The account does not exist".into())
+    }
+
+    fn insert_invoice(
+        &self,
+        account: ZcashAccountId,
+        address: UnifiedAddress,
+        diversifier_index: DiversifierIndex,
+        amount: Arc<ZcashNonNegativeAmount>,
+        expires_at: u64,
+        label: Option<String>,
+    ) -> ZcashResult<ZcashInvoice> {
+        let created_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_err(|e| e.to_string())?
+            .as_secs();
+        if expires_at <= created_at {
+            return Err("The invoice must expire in the future".into());
+        }
+
+        let conn = self.extensions_conn()?;
+        conn.execute(
+            "INSERT INTO merchant_invoices (
+                account, address, diversifier_index, amount, label, created_at, expires_at
+            ) VALUES (
+                :account, :address, :diversifier_index, :amount, :label, :created_at, :expires_at
+            )",
+            named_params![
+                ":account": account.id,
+                ":address": address.encode(&self.params),
+                ":diversifier_index": &diversifier_index.0[..],
+                ":amount": amount.value(),
+                ":label": label,
+                ":created_at": created_at,
+                ":expires_at": expires_at,
+            ],
+        )
+        .map_err(|e| match e {
+            rusqlite::Error::SqliteFailure(f, _)
+                if f.code == rusqlite::ErrorCode::ConstraintViolation =>
+            {
+                "Another invoice already uses this address".into()
+            }
+            e => invoice_err(e),
+        })?;
+
+        Ok(ZcashInvoice {
+            id: conn.last_insert_rowid(),
+            account,
+            address: Arc::new(address.into()),
+            diversifier_index: Arc::new(diversifier_index.into()),
+            amount,
+            label,
+            created_at,
+            expires_at,
+        })
+    }
+
+    fn read_invoice(&self, row: &Row) -> rusqlite::Result<ZcashInvoice> {
+        let address: String = row.get(2)?;
+        let address = match RecipientAddress::decode(&self.params, &address) {
+            Some(RecipientAddress::Unified(ua)) => ua,
+            _ => {
+                return Err(rusqlite::Error::InvalidColumnType(
+                    2,
+                    "address".to_string(),
+                    rusqlite::types::Type::Text,
+                ))
+            }
+        };
+        let diversifier_index: Vec<u8> = row.get(3)?;
+        let diversifier_index = <[u8; 11]>::try_from(&diversifier_index[..]).map_err(|e| {
+            rusqlite::Error::FromSqlConversionFailure(3, rusqlite::types::Type::Blob, e.into())
+        })?;
+        let amount = NonNegativeAmount::from_nonnegative_i64(row.get(4)?).map_err(|_| {
+            rusqlite::Error::IntegralValueOutOfRange(4, row.get(4).unwrap_or_default())
+        })?;
+
+        Ok(ZcashInvoice {
+            id: row.get(0)?,
+            account: AccountId::from(row.get::<_, u32>(1)?).into(),
+            address: Arc::new(address.into()),
+            diversifier_index: Arc::new(DiversifierIndex(diversifier_index).into()),
+            amount: Arc::new(amount.into()),
+            label: row.get(5)?,
+            created_at: row.get(6)?,
+            expires_at: row.get(7)?,
+        })
+    }
+}
+
+impl ZcashTransactionRequest {
+    /// Constructs the ZIP 321 request paying an invoice.
+    pub fn from_invoice(invoice: ZcashInvoice) -> ZcashZip321Result<Self> {
+        TransactionRequest::new(vec![ZcashPayment {
+            recipient_address: Arc::new(ZcashRecipientAddress::unified(invoice.address)),
+            amount: Arc::new(Amount::from(NonNegativeAmount::from(*invoice.amount)).into()),
+            memo: None,
+            label: invoice.label,
+            message: None,
+            other_params: vec![],
+        }
+        .into()])
+        .map(From::from)
+        .map_err(From::from)
+    }
+}
+
+/// Returns the diversifier index of the Sapling receiver of `address`.
+fn diversifier_index_of(
+    ufvk: &UnifiedFullViewingKey,
+    address: &UnifiedAddress,
+) -> ZcashResult<DiversifierIndex> {
+    ufvk.sapling()
+        .zip(address.sapling())
+        .and_then(|(dfvk, addr)| dfvk.decrypt_diversifier(addr))
+        .map(|(index, _)| index)
+        .ok_or_else(|| "The account has no Sapling receiver to be paid to".into())
+}
+
+/// Returns the integer value of a little-endian diversifier index.
+fn index_value(index: DiversifierIndex) -> u128 {
+    let mut bytes = [0; 16];
+    bytes[..11].copy_from_slice(&index.0);
+    u128::from_le_bytes(bytes)
+}
+
+fn invoice_err(e: rusqlite::Error) -> ZcashError {
+    ZcashError::Message {
+        error: format!("Invoice database error: {:?}", e),
+    }
+}
@@ -3,18 +3,30 @@
 use std::sync::{Arc, Mutex};
 
 use zcash_client_backend::address::AddressMetadata;
-use zcash_client_backend::data_api::chain::CommitmentTreeRoot;
+use zcash_client_backend::data_api::chain::{
+    error::Error as ChainError, BlockSource, CommitmentTreeRoot,
+};
 use zcash_client_backend::data_api::scanning::ScanRange;
 use zcash_client_backend::data_api::ScannedBlock;
 use zcash_client_backend::data_api::{NoteId, WalletCommitmentTrees, WalletRead, WalletWrite};
 use zcash_client_backend::encoding::AddressCodec;
 use zcash_client_backend::keys::UnifiedFullViewingKey;
+use zcash_client_backend::proto::service::TreeState;
 use zcash_client_backend::wallet::WalletTransparentOutput;
 
+use incrementalmerkletree::frontier::CommitmentTree;
+use incrementalmerkletree::witness::IncrementalWitness;
+use incrementalmerkletree::Position;
+use orchard::note::ExtractedNoteCommitment;
+use orchard::tree::MerkleHashOrchard;
+use rusqlite::{named_params, Connection};
+use shardtree::error::ShardTreeError;
+
 use zcash_client_sqlite::chain::init::init_blockmeta_db;
 use zcash_client_sqlite::wallet::init::init_wallet_db;
 use zcash_client_sqlite::{chain::BlockMeta, FsBlockDb, ReceivedNoteId, WalletDb};
 
+use zcash_primitives::consensus::BlockHeight;
 use zcash_primitives::legacy::TransparentAddress;
 use zcash_primitives::sapling;
 use zcash_primitives::transaction::components::{Amount, OutPoint};
@@ -29,13 +41,22 @@
 mod wallet;
 pub use self::wallet::*;
 
+mod discovery;
+
+mod extensions;
+use self::extensions::init_extension_tables;
+
+mod invoice;
+pub use self::invoice::*;
+
 use crate::{
     ZcashAccountBirthday, ZcashAccountId, ZcashAddressMetadata, ZcashAmount, ZcashBlockHash,
     ZcashBlockHeight, ZcashBlockMetadata, ZcashCommitmentTreeRoot, ZcashConsensusParameters,
-    ZcashDecryptedTransaction, ZcashError, ZcashExtendedFullViewingKey, ZcashMemo,
-    ZcashNullifierQuery, ZcashOutPoint, ZcashReceivedSaplingNote, ZcashResult,
+    ZcashDecryptedTransaction, ZcashError, ZcashExtendedFullViewingKey,
+    ZcashExtractedNoteCommitment, ZcashMemo, ZcashNullifierQuery, ZcashOrchardMerklePath,
+    ZcashOutPoint, ZcashReceivedSaplingNote, ZcashResult, ZcashSaplingMerklePath,
     ZcashSaplingNullifier, ZcashScanRange, ZcashScannedBlock, ZcashSentTransaction,
-    ZcashShieldedProtocol, ZcashTransaction, ZcashTransparentAddress, ZcashTxId,
+    ZcashShieldedProtocol, ZcashTransaction, ZcashTransparentAddress, ZcashTreeState, ZcashTxId,
     ZcashUnifiedAddress, ZcashUnifiedFullViewingKey, ZcashUnifiedSpendingKey, ZcashWalletSummary,
     ZcashWalletTransparentOutput,
 };
@@ -66,6 +87,10 @@ pub struct ZcashWalletDb {
     pub params: ZcashConsensusParameters,
 }
 
+/// Error type of the storage backing the wallet's note commitment trees.
+type WalletCommitmentTreesError =
+    <WalletDb<Connection, ZcashConsensusParameters> as WalletCommitmentTrees>::Error;
+
 fn cast_err(e: zcash_client_sqlite::error::SqliteClientError) -> ZcashError {
     ZcashError::Message {
         error: format!("Err: {:?}", e),
@@ -96,7 +121,7 @@ pub fn for_path(path: String, params: ZcashConsensusParameters) -> ZcashResult<S
         Ok(ZcashWalletDb { path, params })
     }
 
-    /// From wallet::init
+    /// From wallet::init, followed by the creation of the extension tables of this library.
     pub fn initialize(&self, seed: Vec<u8>) -> ZcashResult<()> {
         let mut db_data =
             WalletDb::for_path(&self.path, self.params).expect("Cannot access the DB!");
@@ -104,7 +129,8 @@ pub fn initialize(&self, seed: Vec<u8>) -> ZcashResult<()> {
 
         init_wallet_db(&mut db_data, Some(secvec)).map_err(|e| ZcashError::Message {
             error: format!("Error while initializing data DB: {:?}", e),
-        })
+        })?;
+        init_extension_tables(&self.extensions_conn()?)
     }
 
     // ####################################
@@ -548,6 +574,93 @@ pub fn get_checkpoint_depth(&self, min_confirmations: u32) -> ZcashResult<u32> {
                 error: format!("ShardTreeError: {:?}", e),
             })
     }
+
+    /// Returns the Merkle path of a spendable Sapling note to the root of the Sapling note
+    /// commitment tree, at the checkpoint transactions are anchored to with
+    /// `min_confirmations` confirmations.
+    ///
+    /// `note` is one of the notes returned by [`Self::get_spendable_sapling_notes`] for the
+    /// anchor height returned by [`Self::get_target_and_anchor_heights`] with the same
+    /// `min_confirmations`. The resulting path can be used with
+    /// `ZcashTransactionBuilder::add_sapling_spend` for a transaction anchored at that height.
+    pub fn get_sapling_witness(
+        &self,
+        note: Arc<ZcashReceivedSaplingNote>,
+        min_confirmations: u32,
+    ) -> ZcashResult<Arc<ZcashSaplingMerklePath>> {
+        let min_confirmations =
+            NonZeroU32::new(min_confirmations).ok_or("At least one confirmation is required")?;
+
+        let checkpoint_depth = WalletDb::for_path(&self.path, self.params)
+            .map_err(db_err)?
+            .get_checkpoint_depth(min_confirmations)
+            .map_err(|e| ZcashError::Message {
+                error: format!("ShardTreeError: {:?}", e),
+            })?;
+
+        self.sapling_witness(&note, checkpoint_depth)
+    }
+
+    /// Returns the Merkle path of a spendable Sapling note to the root of the Sapling note
+    /// commitment tree, as of the end of the block at `anchor_height`.
+    ///
+    /// The wallet only keeps the tree states of the recently scanned blocks, so
+    /// `anchor_height` must be one of them. The resulting path can be used with
+    /// `ZcashTransactionBuilder::add_sapling_spend` for a transaction anchored at that height.
+    pub fn get_sapling_witness_at_height(
+        &self,
+        note: Arc<ZcashReceivedSaplingNote>,
+        anchor_height: Arc<ZcashBlockHeight>,
+    ) -> ZcashResult<Arc<ZcashSaplingMerklePath>> {
+        let anchor_height = u32::from(BlockHeight::from(anchor_height.as_ref()));
+
+        // Checkpoint depths count from the most recent checkpoint, which is at depth 1.
+        let (is_checkpoint, later_checkpoints): (bool, usize) = Connection::open(&self.path)
+            .and_then(|conn| {
+                conn.query_row(
+                    "SELECT
+                        EXISTS(SELECT 1 FROM sapling_tree_checkpoints WHERE checkpoint_id = :height),
+                        (SELECT COUNT(*) FROM sapling_tree_checkpoints WHERE checkpoint_id > :height)",
+                    named_params![":height": anchor_height],
+                    |row| Ok((row.get(0)?, row.get(1)?)),
+                )
+            })
+            .map_err(db_err)?;
+
+        if !is_checkpoint {
+            return Err(format!(
+                "The wallet has no Sapling tree state at height {}",
+                anchor_height
+            )
+            .into());
+        }
+
+        self.sapling_witness(&note, later_checkpoints + 1)
+    }
+
+    fn sapling_witness(
+        &self,
+        note: &ZcashReceivedSaplingNote,
+        checkpoint_depth: usize,
+    ) -> ZcashResult<Arc<ZcashSaplingMerklePath>> {
+        let position: Position = (*note.note_commitment_tree_position()).into();
+
+        WalletDb::for_path(&self.path, self.params)
+            .map_err(db_err)?
+            .with_sapling_tree_mut::<_, _, ShardTreeError<WalletCommitmentTreesError>>(|tree| {
+                tree.witness(position, checkpoint_depth)
+            })
+            .map(|path| Arc::new(path.into()))
+            .map_err(|e| ZcashError::Message {
+                error: format!("ShardTreeError: {:?}", e),
+            })
+    }
+}
+
+fn db_err(e: rusqlite::Error) -> ZcashError {
+    ZcashError::Message {
+        error: format!("Cannot access the DB: {:?}", e),
+    }
 }
 
 pub struct ZcashFsBlockDb {
@@ -620,4 +733,85 @@ pub fn write_block_metadata(
                 error: format!("FsBlockDbError: {:?}", e),
             })
     }
+
+    /// Returns the Merkle path of an Orchard note to the root of the Orchard note commitment
+    /// tree, as of the end of the block at `anchor_height`.
+    ///
+    /// The wallet database does not keep an Orchard note commitment tree, so the path is
+    /// computed from the Orchard actions of the cached blocks: `tree_state` is the state of
+    /// the chain at a height before the note was mined, and the blocks after it up to
+    /// `anchor_height` must all be in the cache. `cmx` is the note commitment of the note.
+    pub fn get_orchard_witness(
+        &self,
+        tree_state: Arc<ZcashTreeState>,
+        cmx: Arc<ZcashExtractedNoteCommitment>,
+        anchor_height: Arc<ZcashBlockHeight>,
+    ) -> ZcashResult<Arc<ZcashOrchardMerklePath>> {
+        let mut tree = CommitmentTree::from(tree_state.orchard_tree()?.as_ref());
+        let tree_state: TreeState = tree_state.as_ref().clone().into();
+        let start_height = BlockHeight::from(u32::try_from(tree_state.height)? + 1);
+        let anchor_height = BlockHeight::from(anchor_height.as_ref());
+        if anchor_height < start_height {
+            return Err("The anchor height must be after the height of the tree state".into());
+        }
+
+        let leaf = MerkleHashOrchard::from_cmx(&(*cmx).clone().into());
+        let mut witness: Option<IncrementalWitness<MerkleHashOrchard, 32>> = None;
+        let mut next_height = start_height;
+
+        self.fs_block_db
+            .lock()
+            .unwrap()
+            .with_blocks::<_, ZcashError>(
+                Some(start_height),
+                Some((u32::from(anchor_height) - u32::from(start_height) + 1).try_into()?),
+                |block| {
+                    if block.height() != next_height {
+                        return Err(ChainError::Wallet(
+                            format!("Block {} is missing from the cache", next_height).into(),
+                        ));
+                    }
+                    next_height = next_height + 1;
+
+                    for action in block.vtx.iter().flat_map(|tx| tx.actions.iter()) {
+                        let node = <[u8; 32]>::try_from(&action.cmx[..])
+                            .ok()
+                            .and_then(|cmx| ExtractedNoteCommitment::from_bytes(&cmx).into())
+                            .map(|cmx| MerkleHashOrchard::from_cmx(&cmx))
+                            .ok_or_else(|| {
+                                ChainError::Wallet(
+                                    "Invalid Orchard action in a cached block".into(),
+                                )
+                            })?;
+
+                        match witness.as_mut() {
+                            Some(witness) => witness.append(node),
+                            None => tree.append(node).map(|_| {
+                                if node == leaf {
+                                    witness = Some(IncrementalWitness::from_tree(tree.clone()));
+                                }
+                            }),
+                        }
+                        .map_err(|_| ChainError::Wallet("Orchard tree is full".into()))?;
+                    }
+
+                    Ok(())
+                },
+            )
+            .map_err(|e| match e {
+                ChainError::Wallet(e) => e,
+                e => ZcashError::Message {
+                    error: format!("Error while reading cached blocks: {:?}", e),
+                },
+            })?;
+
+        if next_height <= anchor_height {
+            return Err(format!("Block {} is missing from the cache", next_height).into());
+        }
+
+        witness
+            .and_then(|witness| witness.path())
+            .map(|path| Arc::new(path.into()))
+            .ok_or_else(|| "The note commitment was not found in the cached blocks".into())
+    }
 }
@@ -8,4 +8,8 @@ impl ZcashBlockHash {
     pub fn from_slice(bytes: &[u8]) -> Self {
         ZcashBlockHash(BlockHash::from_slice(bytes))
     }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.0 .0.to_vec()
+    }
 }
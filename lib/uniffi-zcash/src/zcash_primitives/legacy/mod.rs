@@ -1,12 +1,25 @@
 mod keys;
 pub use self::keys::*;
 
+mod script;
+pub use self::script::*;
+
 mod transparent_address;
 pub use self::transparent_address::*;
 
+use secp256k1::PublicKey;
 use zcash_primitives::legacy::Script;
 
-use crate::ZcashResult;
+use crate::{ZcashError, ZcashResult};
+
+/// `OP_1`, the first of the opcodes pushing the numbers 1 to 16.
+const OP_1: u8 = 0x51;
+
+/// `OP_CHECKMULTISIG`.
+const OP_CHECKMULTISIG: u8 = 0xae;
+
+/// Maximum number of compressed public keys fitting in a standard P2SH redeem script.
+const MAX_MULTISIG_KEYS: usize = 15;
 
 pub struct ZcashScript(Script);
 
@@ -15,6 +28,28 @@ pub fn from_bytes(data: &[u8]) -> ZcashResult<Self> {
         Ok(Script::read(data)?.into())
     }
 
+    /// Creates a `threshold`-of-`pubkeys.len()` multisig redeem script, to be used with
+    /// [`ZcashTransparentAddress::from_redeem_script`](crate::ZcashTransparentAddress::from_redeem_script).
+    ///
+    /// Public keys are included in the given order, in their compressed form.
+    pub fn multisig(threshold: u32, pubkeys: Vec<Vec<u8>>) -> ZcashResult<Self> {
+        let threshold = threshold as usize;
+        if threshold == 0 || threshold > pubkeys.len() || pubkeys.len() > MAX_MULTISIG_KEYS {
+            return Err(ZcashError::ValueOutOfRange {
+                val: threshold as i64,
+                from: 1,
+                to: pubkeys.len().min(MAX_MULTISIG_KEYS) as i64,
+            });
+        }
+
+        let pubkeys = pubkeys
+            .iter()
+            .map(|pubkey| PublicKey::from_slice(pubkey))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(multisig_script(threshold, &pubkeys).into())
+    }
+
     pub fn to_bytes(&self) -> ZcashResult<Vec<u8>> {
         let mut data = Vec::new();
         self.0.write(&mut data)?;
@@ -22,6 +57,43 @@ pub fn to_bytes(&self) -> ZcashResult<Vec<u8>> {
     }
 }
 
+/// Encodes `OP_m <pubkey>... OP_n OP_CHECKMULTISIG`.
+fn multisig_script(threshold: usize, pubkeys: &[PublicKey]) -> Script {
+    let script = Script(vec![OP_1 + threshold as u8 - 1]);
+    let script = pubkeys
+        .iter()
+        .fold(script, |script, pubkey| script << &pubkey.serialize()[..]);
+
+    let mut data = script.0;
+    data.push(OP_1 + pubkeys.len() as u8 - 1);
+    data.push(OP_CHECKMULTISIG);
+    Script(data)
+}
+
+/// Parses a multisig redeem script created by [`ZcashScript::multisig`], returning
+/// its threshold and public keys.
+pub(crate) fn parse_multisig_script(script: &Script) -> Option<(usize, Vec<PublicKey>)> {
+    let (&first, rest) = script.0.split_first()?;
+    let threshold = first.checked_sub(OP_1)? as usize + 1;
+
+    let pubkeys = rest
+        .chunks(34)
+        .take_while(|chunk| chunk.len() == 34 && chunk[0] == 33)
+        .map(|chunk| PublicKey::from_slice(&chunk[1..]).ok())
+        .collect::<Option<Vec<_>>>()?;
+
+    if threshold > pubkeys.len() || pubkeys.len() > MAX_MULTISIG_KEYS {
+        return None;
+    }
+
+    // The canonical encoding also rejects trailing data after OP_CHECKMULTISIG.
+    if multisig_script(threshold, &pubkeys) == *script {
+        Some((threshold, pubkeys))
+    } else {
+        None
+    }
+}
+
 impl From<Script> for ZcashScript {
     fn from(inner: Script) -> Self {
         ZcashScript(inner)
@@ -0,0 +1,396 @@
+use std::sync::Arc;
+
+use zcash_primitives::{
+    legacy::{Script, TransparentAddress},
+    transaction::components::{Amount, TxOut},
+};
+
+use super::{parse_multisig_script, ZcashScript};
+use crate::{utils::cast_slice, ZcashResult, ZcashTransparentAddress};
+
+const OP_0: u8 = 0x00;
+const OP_PUSHDATA1: u8 = 0x4c;
+const OP_PUSHDATA2: u8 = 0x4d;
+const OP_PUSHDATA4: u8 = 0x4e;
+const OP_1NEGATE: u8 = 0x4f;
+const OP_1: u8 = 0x51;
+const OP_16: u8 = 0x60;
+const OP_RETURN: u8 = 0x6a;
+const OP_DUP: u8 = 0x76;
+const OP_EQUAL: u8 = 0x87;
+const OP_EQUALVERIFY: u8 = 0x88;
+const OP_HASH160: u8 = 0xa9;
+const OP_CHECKSIG: u8 = 0xac;
+
+/// The standard script templates.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ZcashScriptKind {
+    /// `OP_DUP OP_HASH160 <pubkey hash> OP_EQUALVERIFY OP_CHECKSIG`
+    PubkeyHash,
+    /// `OP_HASH160 <script hash> OP_EQUAL`
+    ScriptHash,
+    /// `OP_m <pubkey>... OP_n OP_CHECKMULTISIG`
+    Multisig,
+    /// `OP_RETURN` followed by data pushes only.
+    NullData,
+    /// Any other script.
+    Unknown,
+}
+
+/// A single operation of a script. `data` is set for the opcodes pushing data,
+/// `OP_0` and `OP_PUSHDATA1` to `OP_PUSHDATA4`.
+pub struct ZcashScriptInstruction {
+    pub opcode: u8,
+    pub data: Option<Vec<u8>>,
+}
+
+impl ZcashScript {
+    /// Creates a script from its bytes, without the length prefix expected by
+    /// [`Self::from_bytes`].
+    pub fn from_raw_bytes(data: &[u8]) -> Self {
+        Script(data.to_vec()).into()
+    }
+
+    /// Creates a P2PKH script paying to the given 20-byte public key hash.
+    pub fn p2pkh(pubkey_hash: &[u8]) -> ZcashResult<Self> {
+        Ok(TransparentAddress::PublicKey(cast_slice(pubkey_hash)?)
+            .script()
+            .into())
+    }
+
+    /// Creates a P2SH script paying to the given 20-byte redeem script hash.
+    pub fn p2sh(script_hash: &[u8]) -> ZcashResult<Self> {
+        Ok(TransparentAddress::Script(cast_slice(script_hash)?)
+            .script()
+            .into())
+    }
+
+    /// Creates an unspendable `OP_RETURN` script carrying `data`.
+    pub fn op_return(data: &[u8]) -> Self {
+        (Script(vec![OP_RETURN]) << data).into()
+    }
+
+    /// Returns the bytes of the script, without the length prefix written by
+    /// [`Self::to_bytes`].
+    pub fn raw_bytes(&self) -> Vec<u8> {
+        self.0 .0.clone()
+    }
+
+    /// Splits the script into its operations.
+    ///
+    /// Fails if a data push runs past the end of the script.
+    pub fn instructions(&self) -> ZcashResult<Vec<ZcashScriptInstruction>> {
+        parse_instructions(&self.0 .0)
+            .map_err(|offset| format!("Truncated data push at offset {}", offset).into())
+    }
+
+    /// Returns the human-readable form of the script, as printed by zcashd for a
+    /// `scriptPubKey`.
+    ///
+    /// Data pushes of up to 4 bytes are printed as script numbers, longer ones are hex
+    /// encoded. A truncated data push is printed as `[error]`.
+    pub fn disassemble(&self) -> String {
+        self.to_asm(false)
+    }
+
+    /// Returns the human-readable form of the script, as printed by zcashd for a
+    /// `scriptSig`.
+    ///
+    /// This is [`Self::disassemble`], except that the pushes that are signatures end with
+    /// the name of their sighash type, like `[ALL]`, instead of its hex encoding.
+    pub fn disassemble_script_sig(&self) -> String {
+        self.to_asm(self.0 .0.first() != Some(&OP_RETURN))
+    }
+
+    fn to_asm(&self, decode_sighash: bool) -> String {
+        let mut asm = Vec::new();
+        let mut rest = self.0 .0.as_slice();
+
+        while !rest.is_empty() {
+            match next_instruction(rest) {
+                Some((instruction, remaining)) => {
+                    asm.push(instruction.to_asm(decode_sighash));
+                    rest = remaining;
+                }
+                None => {
+                    asm.push("[error]".to_string());
+                    break;
+                }
+            }
+        }
+
+        asm.join(" ")
+    }
+
+    /// Classifies the script against the standard templates.
+    pub fn kind(&self) -> ZcashScriptKind {
+        script_kind(&self.0)
+    }
+
+    /// Returns the address paid by a P2PKH or P2SH script.
+    pub fn address(&self) -> Option<Arc<ZcashTransparentAddress>> {
+        TxOut {
+            value: Amount::zero(),
+            script_pubkey: self.0.clone(),
+        }
+        .recipient_address()
+        .map(From::from)
+        .map(Arc::new)
+    }
+
+    /// Returns the data carried by an `OP_RETURN` script, concatenating its pushes.
+    pub fn op_return_data(&self) -> Option<Vec<u8>> {
+        let (&first, rest) = self.0 .0.split_first()?;
+        if first != OP_RETURN {
+            return None;
+        }
+
+        parse_instructions(rest)
+            .ok()?
+            .into_iter()
+            .map(|instruction| instruction.data)
+            .collect::<Option<Vec<_>>>()
+            .map(|pushes| pushes.concat())
+    }
+}
+
+impl ZcashScriptInstruction {
+    fn to_asm(&self, decode_sighash: bool) -> String {
+        match (&self.data, self.opcode) {
+            (Some(data), _) if data.len() <= 4 => script_num(data).to_string(),
+            (Some(data), _) if decode_sighash && is_signature(data) => {
+                let (sighash_type, signature) = data.split_last().unwrap();
+                format!(
+                    "{}[{}]",
+                    hex::encode(signature),
+                    sighash_name(*sighash_type).unwrap()
+                )
+            }
+            (Some(data), _) => hex::encode(data),
+            (None, OP_1NEGATE) => "-1".to_string(),
+            (None, OP_1..=OP_16) => (self.opcode - OP_1 + 1).to_string(),
+            (None, opcode) => opcode_name(opcode).unwrap_or("OP_UNKNOWN").to_string(),
+        }
+    }
+}
+
+/// Decodes a minimal-length script number, little-endian with a sign bit.
+fn script_num(data: &[u8]) -> i64 {
+    let Some((&last, _)) = data.split_last() else {
+        return 0;
+    };
+    let value = data
+        .iter()
+        .rev()
+        .fold(0i64, |value, byte| (value << 8) | i64::from(*byte));
+    let sign_bit = 0x80i64 << (8 * (data.len() - 1));
+
+    if last & 0x80 != 0 {
+        -(value & !sign_bit)
+    } else {
+        value
+    }
+}
+
+fn sighash_name(sighash_type: u8) -> Option<&'static str> {
+    Some(match sighash_type {
+        0x01 => "ALL",
+        0x02 => "NONE",
+        0x03 => "SINGLE",
+        0x81 => "ALL|ANYONECANPAY",
+        0x82 => "NONE|ANYONECANPAY",
+        0x83 => "SINGLE|ANYONECANPAY",
+        _ => return None,
+    })
+}
+
+/// Returns whether `data` is a strict DER signature followed by a defined sighash type,
+/// following BIP 66.
+fn is_signature(data: &[u8]) -> bool {
+    let len = data.len();
+    if !(9..=73).contains(&len) || data[0] != 0x30 || data[1] as usize != len - 3 {
+        return false;
+    }
+
+    let len_r = data[3] as usize;
+    if 5 + len_r >= len {
+        return false;
+    }
+    let len_s = data[5 + len_r] as usize;
+    if len_r + len_s + 7 != len {
+        return false;
+    }
+
+    let is_integer = |tag: u8, int: &[u8]| {
+        tag == 0x02
+            && !int.is_empty()
+            && int[0] & 0x80 == 0
+            && !(int.len() > 1 && int[0] == 0x00 && int[1] & 0x80 == 0)
+    };
+
+    is_integer(data[2], &data[4..4 + len_r])
+        && is_integer(data[4 + len_r], &data[6 + len_r..6 + len_r + len_s])
+        && sighash_name(data[len - 1]).is_some()
+}
+
+/// Classifies a script against the standard templates.
+pub(crate) fn script_kind(script: &Script) -> ZcashScriptKind {
+    let bytes = script.0.as_slice();
+
+    match bytes {
+        [OP_DUP, OP_HASH160, 0x14, .., OP_EQUALVERIFY, OP_CHECKSIG] if bytes.len() == 25 => {
+            ZcashScriptKind::PubkeyHash
+        }
+        [OP_HASH160, 0x14, .., OP_EQUAL] if bytes.len() == 23 => ZcashScriptKind::ScriptHash,
+        [OP_RETURN, rest @ ..]
+            if parse_instructions(rest)
+                .is_ok_and(|instructions| instructions.iter().all(|i| i.data.is_some())) =>
+        {
+            ZcashScriptKind::NullData
+        }
+        _ if parse_multisig_script(script).is_some() => ZcashScriptKind::Multisig,
+        _ => ZcashScriptKind::Unknown,
+    }
+}
+
+/// Splits `script` into its operations, returning the offset of the first truncated
+/// data push on failure.
+fn parse_instructions(script: &[u8]) -> Result<Vec<ZcashScriptInstruction>, usize> {
+    let mut instructions = Vec::new();
+    let mut rest = script;
+
+    while !rest.is_empty() {
+        let (instruction, remaining) = next_instruction(rest).ok_or(script.len() - rest.len())?;
+        instructions.push(instruction);
+        rest = remaining;
+    }
+
+    Ok(instructions)
+}
+
+fn next_instruction(script: &[u8]) -> Option<(ZcashScriptInstruction, &[u8])> {
+    let (&opcode, rest) = script.split_first()?;
+
+    let (len, rest) = match opcode {
+        OP_0..=0x4b => (opcode as usize, rest),
+        OP_PUSHDATA1 => {
+            let (len, rest) = rest.split_first()?;
+            (*len as usize, rest)
+        }
+        OP_PUSHDATA2 => {
+            let len: [u8; 2] = rest.get(..2)?.try_into().ok()?;
+            (u16::from_le_bytes(len) as usize, &rest[2..])
+        }
+        OP_PUSHDATA4 => {
+            let len: [u8; 4] = rest.get(..4)?.try_into().ok()?;
+            (u32::from_le_bytes(len) as usize, &rest[4..])
+        }
+        _ => return Some((ZcashScriptInstruction { opcode, data: None }, rest)),
+    };
+
+    let data = rest.get(..len)?.to_vec();
+    Some((
+        ZcashScriptInstruction {
+            opcode,
+            data: Some(data),
+        },
+        &rest[len..],
+    ))
+}
+
+fn opcode_name(opcode: u8) -> Option<&'static str> {
+    Some(match opcode {
+        0x50 => "OP_RESERVED",
+        0x61 => "OP_NOP",
+        0x62 => "OP_VER",
+        0x63 => "OP_IF",
+        0x64 => "OP_NOTIF",
+        0x65 => "OP_VERIF",
+        0x66 => "OP_VERNOTIF",
+        0x67 => "OP_ELSE",
+        0x68 => "OP_ENDIF",
+        0x69 => "OP_VERIFY",
+        0x6a => "OP_RETURN",
+        0x6b => "OP_TOALTSTACK",
+        0x6c => "OP_FROMALTSTACK",
+        0x6d => "OP_2DROP",
+        0x6e => "OP_2DUP",
+        0x6f => "OP_3DUP",
+        0x70 => "OP_2OVER",
+        0x71 => "OP_2ROT",
+        0x72 => "OP_2SWAP",
+        0x73 => "OP_IFDUP",
+        0x74 => "OP_DEPTH",
+        0x75 => "OP_DROP",
+        0x76 => "OP_DUP",
+        0x77 => "OP_NIP",
+        0x78 => "OP_OVER",
+        0x79 => "OP_PICK",
+        0x7a => "OP_ROLL",
+        0x7b => "OP_ROT",
+        0x7c => "OP_SWAP",
+        0x7d => "OP_TUCK",
+        0x7e => "OP_CAT",
+        0x7f => "OP_SUBSTR",
+        0x80 => "OP_LEFT",
+        0x81 => "OP_RIGHT",
+        0x82 => "OP_SIZE",
+        0x83 => "OP_INVERT",
+        0x84 => "OP_AND",
+        0x85 => "OP_OR",
+        0x86 => "OP_XOR",
+        0x87 => "OP_EQUAL",
+        0x88 => "OP_EQUALVERIFY",
+        0x89 => "OP_RESERVED1",
+        0x8a => "OP_RESERVED2",
+        0x8b => "OP_1ADD",
+        0x8c => "OP_1SUB",
+        0x8d => "OP_2MUL",
+        0x8e => "OP_2DIV",
+        0x8f => "OP_NEGATE",
+        0x90 => "OP_ABS",
+        0x91 => "OP_NOT",
+        0x92 => "OP_0NOTEQUAL",
+        0x93 => "OP_ADD",
+        0x94 => "OP_SUB",
+        0x95 => "OP_MUL",
+        0x96 => "OP_DIV",
+        0x97 => "OP_MOD",
+        0x98 => "OP_LSHIFT",
+        0x99 => "OP_RSHIFT",
+        0x9a => "OP_BOOLAND",
+        0x9b => "OP_BOOLOR",
+        0x9c => "OP_NUMEQUAL",
+        0x9d => "OP_NUMEQUALVERIFY",
+        0x9e => "OP_NUMNOTEQUAL",
+        0x9f => "OP_LESSTHAN",
+        0xa0 => "OP_GREATERTHAN",
+        0xa1 => "OP_LESSTHANOREQUAL",
+        0xa2 => "OP_GREATERTHANOREQUAL",
+        0xa3 => "OP_MIN",
+        0xa4 => "OP_MAX",
+        0xa5 => "OP_WITHIN",
+        0xa6 => "OP_RIPEMD160",
+        0xa7 => "OP_SHA1",
+        0xa8 => "OP_SHA256",
+        0xa9 => "OP_HASH160",
+        0xaa => "OP_HASH256",
+        0xab => "OP_CODESEPARATOR",
+        0xac => "OP_CHECKSIG",
+        0xad => "OP_CHECKSIGVERIFY",
+        0xae => "OP_CHECKMULTISIG",
+        0xaf => "OP_CHECKMULTISIGVERIFY",
+        0xb0 => "OP_NOP1",
+        0xb1 => "OP_CHECKLOCKTIMEVERIFY",
+        0xb2 => "OP_NOP3",
+        0xb3 => "OP_NOP4",
+        0xb4 => "OP_NOP5",
+        0xb5 => "OP_NOP6",
+        0xb6 => "OP_NOP7",
+        0xb7 => "OP_NOP8",
+        0xb8 => "OP_NOP9",
+        0xb9 => "OP_NOP10",
+        0xff => "OP_INVALIDOPCODE",
+        _ => return None,
+    })
+}
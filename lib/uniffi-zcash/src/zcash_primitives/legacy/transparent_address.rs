@@ -1,9 +1,16 @@
 use std::sync::Arc;
 
+use ripemd::Ripemd160;
+use sha2::{Digest, Sha256};
 use zcash_client_backend::encoding;
-use zcash_primitives::{consensus::Parameters, legacy::TransparentAddress};
+use zcash_primitives::{
+    consensus::Parameters,
+    legacy::{Script, TransparentAddress},
+};
 
-use crate::{utils, ZcashConsensusParameters, ZcashError, ZcashResult, ZcashScript};
+use crate::{
+    recover_message_signer, utils, ZcashConsensusParameters, ZcashError, ZcashResult, ZcashScript,
+};
 
 use derive_more::{From, Into};
 
@@ -30,6 +37,13 @@ pub fn from_script(input: Vec<u8>) -> ZcashResult<Self> {
         Ok(TransparentAddress::Script(buf).into())
     }
 
+    /// Create the P2SH address of a redeem script
+    pub fn from_redeem_script(redeem_script: Arc<ZcashScript>) -> Self {
+        let script = Script::from(redeem_script.as_ref());
+        let hash = Ripemd160::digest(Sha256::digest(&script.0));
+        TransparentAddress::Script(hash.into()).into()
+    }
+
     pub fn script(&self) -> Arc<ZcashScript> {
         Arc::new(self.0.script().into())
     }
@@ -63,6 +77,18 @@ pub fn is_script(&self) -> bool {
         matches!(self.0, TransparentAddress::Script(_))
     }
 
+    /// Verifies a message signature like the `verifymessage` RPC method of `zcashd`.
+    ///
+    /// Returns whether the message was signed by the key of this P2PKH address, and fails
+    /// if the signature is malformed or the address is a P2SH address.
+    pub fn verify_message(&self, message: &str, signature: &str) -> ZcashResult<bool> {
+        if self.is_script() {
+            return Err("Only P2PKH addresses can verify messages".into());
+        }
+        let (key, compressed) = recover_message_signer(message, signature)?;
+        Ok(key.p2pkh_address(compressed) == self.0)
+    }
+
     /// Return raw bytes corresponding to given address
     pub fn to_bytes(&self) -> Vec<u8> {
         match self.0 {
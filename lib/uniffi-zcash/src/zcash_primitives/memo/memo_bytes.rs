@@ -1,6 +1,6 @@
-use zcash_primitives::memo::MemoBytes;
+use zcash_primitives::memo::{Memo, MemoBytes};
 
-use crate::{ZcashError, ZcashResult};
+use crate::{ZcashError, ZcashMemo, ZcashMemoContent, ZcashMemoFormat, ZcashResult};
 
 use derive_more::{From, Into};
 
@@ -31,10 +31,35 @@ pub fn empty() -> Self {
         ZcashMemoBytes(MemoBytes::empty())
     }
 
+    /// Encodes a memo, failing if its text or data does not fit in a memo field.
+    pub fn from_memo(memo: ZcashMemo) -> ZcashResult<Self> {
+        Ok(ZcashMemoBytes(Memo::try_from(memo)?.encode()))
+    }
+
+    /// Encodes the content of a memo, appending the reply-to address to text memos.
+    pub fn from_content(content: ZcashMemoContent) -> ZcashResult<Self> {
+        MemoBytes::try_from(content).map(ZcashMemoBytes)
+    }
+
     /// Returns a slice of the raw bytes, excluding null padding.
     pub fn data(&self) -> Vec<u8> {
         self.0.as_slice().to_owned()
     }
+
+    /// Returns the ZIP 302 format of the memo, based on its first byte.
+    pub fn format(&self) -> ZcashMemoFormat {
+        (&self.0).into()
+    }
+
+    /// Decodes the memo, failing if it is a text memo that is not valid UTF-8.
+    pub fn to_memo(&self) -> ZcashResult<ZcashMemo> {
+        Ok(Memo::try_from(self.0.clone())?.into())
+    }
+
+    /// Decodes the memo, splitting the reply-to address from text memos.
+    pub fn content(&self) -> ZcashResult<ZcashMemoContent> {
+        ZcashMemoContent::try_from(&self.0)
+    }
 }
 
 impl From<&ZcashMemoBytes> for MemoBytes {
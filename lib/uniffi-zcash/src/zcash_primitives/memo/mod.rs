@@ -1,8 +1,12 @@
 mod memo_bytes;
 pub use self::memo_bytes::*;
 
-use zcash_primitives::memo::Memo;
-use zcash_primitives::memo::MemoBytes;
+use std::str::FromStr;
+
+use zcash_address::ZcashAddress;
+use zcash_primitives::memo::{Memo, MemoBytes};
+
+use crate::{ZcashError, ZcashResult};
 
 pub enum ZcashMemo {
     /// An empty memo field.
@@ -12,22 +16,30 @@ pub enum ZcashMemo {
     /// Some unknown memo format from ✨*the future*✨ that we can't parse.
     Future { v: Vec<u8> },
     /// A memo field containing arbitrary bytes.
-    Arbitrary { v: Vec<u8> }, // max 512
+    Arbitrary { v: Vec<u8> }, // max 511
 }
 
-use std::str::FromStr;
+impl TryFrom<ZcashMemo> for Memo {
+    type Error = ZcashError;
 
-impl From<ZcashMemo> for Memo {
-    fn from(e: ZcashMemo) -> Self {
+    fn try_from(e: ZcashMemo) -> ZcashResult<Self> {
         match e {
-            ZcashMemo::Empty => Self::Empty,
-            // NOTE an occasion to handle better errors here
-            ZcashMemo::Text { v } => Memo::from_str(&v).unwrap(),
-            ZcashMemo::Future { v } => Self::Future(MemoBytes::from_bytes(&v[..]).unwrap()),
+            ZcashMemo::Empty => Ok(Self::Empty),
+            ZcashMemo::Text { v } => Ok(Memo::from_str(&v)?),
+            ZcashMemo::Future { v } => match Memo::from_bytes(&v)? {
+                memo @ Memo::Future(_) => Ok(memo),
+                _ => Err("The bytes are not in a reserved memo format".into()),
+            },
             ZcashMemo::Arbitrary { v } => {
-                let boxed_slice: Box<[u8]> = v.into_boxed_slice();
-                let arr: Box<[u8; 511]> = boxed_slice.try_into().unwrap();
-                Self::Arbitrary(arr)
+                if v.len() > 511 {
+                    return Err(ZcashError::ArrayLengthMismatch {
+                        expected: 511,
+                        got: v.len() as u64,
+                    });
+                }
+                let mut arr = Box::new([0u8; 511]);
+                arr[..v.len()].copy_from_slice(&v);
+                Ok(Self::Arbitrary(arr))
             }
         }
     }
@@ -47,3 +59,126 @@ fn from(e: Memo) -> Self {
         }
     }
 }
+
+/// The memo formats defined by [ZIP 302], as indicated by the first byte of a memo.
+///
+/// [ZIP 302]: https://zips.z.cash/zip-0302
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ZcashMemoFormat {
+    /// `0xF6` followed by 511 zero bytes.
+    Empty,
+    /// A first byte of at most `0xF4`, which starts a UTF-8 string.
+    Text,
+    /// `0xFF` followed by 511 bytes of arbitrary data.
+    Arbitrary,
+    /// Any other first byte, reserved for future use.
+    Future,
+}
+
+impl From<&MemoBytes> for ZcashMemoFormat {
+    fn from(memo: &MemoBytes) -> Self {
+        let bytes = memo.as_array();
+        match bytes[0] {
+            0xF6 if bytes[1..].iter().all(|&b| b == 0) => Self::Empty,
+            0xFF => Self::Arbitrary,
+            b if b <= 0xF4 => Self::Text,
+            _ => Self::Future,
+        }
+    }
+}
+
+/// The header of the reply-to convention, which ends a text memo with the address at which
+/// the sender can be reached:
+///
+/// ```text
+/// Thanks for the coffee!
+/// Reply-To:
+/// u1...
+/// ```
+const REPLY_TO_HEADER: &str = "Reply-To:\n";
+
+/// The content of a memo, following the conventions used by wallets on top of [ZIP 302].
+///
+/// [ZIP 302]: https://zips.z.cash/zip-0302
+pub enum ZcashMemoContent {
+    /// An empty memo field.
+    Empty,
+    /// A text memo, with the reply-to address of the sender if one was attached.
+    Text {
+        text: String,
+        reply_to: Option<String>,
+    },
+    /// Arbitrary data, without the `0xFF` format byte and trailing zero padding.
+    Arbitrary { data: Vec<u8> },
+    /// A memo in a reserved format, including the format byte.
+    Future { bytes: Vec<u8> },
+}
+
+impl TryFrom<&MemoBytes> for ZcashMemoContent {
+    type Error = ZcashError;
+
+    fn try_from(memo: &MemoBytes) -> ZcashResult<Self> {
+        Ok(match Memo::try_from(memo.clone())? {
+            Memo::Empty => Self::Empty,
+            Memo::Text(text) => {
+                let (text, reply_to) = split_reply_to(&text);
+                Self::Text {
+                    text: text.to_string(),
+                    reply_to: reply_to.map(str::to_string),
+                }
+            }
+            Memo::Arbitrary(_) => Self::Arbitrary {
+                data: memo.as_slice()[1..].to_vec(),
+            },
+            Memo::Future(bytes) => Self::Future {
+                bytes: bytes.as_array().to_vec(),
+            },
+        })
+    }
+}
+
+impl TryFrom<ZcashMemoContent> for MemoBytes {
+    type Error = ZcashError;
+
+    fn try_from(content: ZcashMemoContent) -> ZcashResult<Self> {
+        let memo = match content {
+            ZcashMemoContent::Empty => ZcashMemo::Empty,
+            ZcashMemoContent::Text { text, reply_to } => ZcashMemo::Text {
+                v: match reply_to {
+                    Some(address) => {
+                        ZcashAddress::try_from_encoded(&address).map_err(|e| {
+                            ZcashError::Message {
+                                error: format!("Invalid reply-to address: {}", e),
+                            }
+                        })?;
+                        if text.is_empty() {
+                            format!("{}{}", REPLY_TO_HEADER, address)
+                        } else {
+                            format!("{}\n{}{}", text, REPLY_TO_HEADER, address)
+                        }
+                    }
+                    None => text,
+                },
+            },
+            ZcashMemoContent::Arbitrary { data } => ZcashMemo::Arbitrary { v: data },
+            ZcashMemoContent::Future { bytes } => ZcashMemo::Future { v: bytes },
+        };
+        Ok(Memo::try_from(memo)?.encode())
+    }
+}
+
+/// Splits a trailing reply-to address from a text memo, if it ends with a valid one.
+fn split_reply_to(text: &str) -> (&str, Option<&str>) {
+    let Some((body, address)) = text.rsplit_once(REPLY_TO_HEADER) else {
+        return (text, None);
+    };
+    let body = match body.strip_suffix('\n') {
+        Some(body) => body,
+        None if body.is_empty() => body,
+        None => return (text, None),
+    };
+    match ZcashAddress::try_from_encoded(address) {
+        Ok(_) => (body, Some(address)),
+        Err(_) => (text, None),
+    }
+}
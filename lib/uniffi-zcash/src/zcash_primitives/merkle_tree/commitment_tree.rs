@@ -1,6 +1,7 @@
 use std::sync::{Arc, RwLock};
 
 use incrementalmerkletree::frontier::CommitmentTree;
+use zcash_primitives::merkle_tree::{read_commitment_tree, write_commitment_tree};
 use zcash_primitives::sapling::Node;
 
 use crate::{ZcashResult, ZcashSaplingNode};
@@ -14,6 +15,29 @@ pub fn empty() -> Self {
         ZcashCommitmentTree(RwLock::new(CommitmentTree::empty()))
     }
 
+    /// Reads a tree in the legacy `CommitmentTree` encoding used by zcashd.
+    pub fn from_bytes(data: &[u8]) -> ZcashResult<Self> {
+        let tree = read_commitment_tree::<Node, _, DEPTH>(data)?;
+        Ok(tree.into())
+    }
+
+    /// Writes the tree in the legacy `CommitmentTree` encoding used by zcashd.
+    pub fn to_bytes(&self) -> ZcashResult<Vec<u8>> {
+        let mut data = Vec::new();
+        write_commitment_tree(&*self.0.read().unwrap(), &mut data)?;
+        Ok(data)
+    }
+
+    /// Returns the number of leaf nodes in the tree.
+    pub fn size(&self) -> u64 {
+        self.0.read().unwrap().size() as u64
+    }
+
+    /// Returns the current root of the tree.
+    pub fn root(&self) -> Arc<ZcashSaplingNode> {
+        Arc::new(self.0.read().unwrap().root().into())
+    }
+
     /// Adds a leaf node to the tree.
     ///
     /// Returns an error if the tree is full.
@@ -27,6 +51,12 @@ pub fn append(&self, node: Arc<ZcashSaplingNode>) -> ZcashResult<()> {
     }
 }
 
+impl From<CommitmentTree<Node, DEPTH>> for ZcashCommitmentTree {
+    fn from(value: CommitmentTree<Node, DEPTH>) -> Self {
+        ZcashCommitmentTree(RwLock::new(value))
+    }
+}
+
 impl From<&ZcashCommitmentTree> for CommitmentTree<Node, DEPTH> {
     fn from(value: &ZcashCommitmentTree) -> Self {
         value.0.read().unwrap().clone()
@@ -1,6 +1,7 @@
 use std::sync::{Arc, RwLock};
 
 use incrementalmerkletree::witness::IncrementalWitness;
+use zcash_primitives::merkle_tree::{read_incremental_witness, write_incremental_witness};
 use zcash_primitives::sapling::Node;
 
 use crate::{ZcashCommitmentTree, ZcashResult, ZcashSaplingMerklePath, ZcashSaplingNode};
@@ -14,6 +15,29 @@ pub fn from_tree(tree: Arc<ZcashCommitmentTree>) -> Self {
         IncrementalWitness::from_tree((&(*tree)).into()).into()
     }
 
+    /// Reads a witness in the legacy `IncrementalWitness` encoding used by zcashd.
+    pub fn from_bytes(data: &[u8]) -> ZcashResult<Self> {
+        let witness = read_incremental_witness::<Node, _, DEPTH>(data)?;
+        Ok(witness.into())
+    }
+
+    /// Writes the witness in the legacy `IncrementalWitness` encoding used by zcashd.
+    pub fn to_bytes(&self) -> ZcashResult<Vec<u8>> {
+        let mut data = Vec::new();
+        write_incremental_witness(&*self.0.read().unwrap(), &mut data)?;
+        Ok(data)
+    }
+
+    /// Returns the position of the witnessed leaf node in the commitment tree.
+    pub fn witnessed_position(&self) -> u64 {
+        self.0.read().unwrap().witnessed_position().into()
+    }
+
+    /// Returns the current root of the tree corresponding to the witness.
+    pub fn root(&self) -> Arc<ZcashSaplingNode> {
+        Arc::new(self.0.read().unwrap().root().into())
+    }
+
     /// Tracks a leaf node that has been added to the underlying tree.
     ///
     /// Returns an error if the tree is full.
@@ -12,6 +12,12 @@ fn from(key: NullifierDerivingKey) -> Self {
     }
 }
 
+impl From<&ZcashNullifierDerivingKey> for NullifierDerivingKey {
+    fn from(value: &ZcashNullifierDerivingKey) -> Self {
+        value.0
+    }
+}
+
 impl ZcashNullifierDerivingKey {
     pub fn from_bytes(bytes: &[u8]) -> ZcashResult<Self> {
         let array = utils::cast_slice(bytes)?;
@@ -1,8 +1,9 @@
 use std::sync::Arc;
 
+use group::GroupEncoding;
 use zcash_primitives::sapling::ProofGenerationKey;
 
-use crate::ZcashViewingKey;
+use crate::{utils::cast_slice, ZcashResult, ZcashViewingKey};
 
 pub struct ZcashProofGenerationKey(ProofGenerationKey);
 
@@ -12,8 +13,37 @@ fn from(key: ProofGenerationKey) -> Self {
     }
 }
 
+impl From<&ZcashProofGenerationKey> for ProofGenerationKey {
+    fn from(key: &ZcashProofGenerationKey) -> Self {
+        key.0.clone()
+    }
+}
+
 impl ZcashProofGenerationKey {
+    /// Parses the 64-byte encoding of `ak` followed by `nsk`, as written by [`Self::to_bytes`].
+    pub fn from_bytes(bytes: &[u8]) -> ZcashResult<Self> {
+        let bytes: [u8; 64] = cast_slice(bytes)?;
+        Self::read(&bytes).ok_or_else(|| "Invalid proof generation key".into())
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        Self::write(&self.0).to_vec()
+    }
+
     pub fn to_viewing_key(&self) -> Arc<ZcashViewingKey> {
         Arc::new(self.0.to_viewing_key().into())
     }
+
+    pub(crate) fn write(key: &ProofGenerationKey) -> [u8; 64] {
+        let mut bytes = [0u8; 64];
+        bytes[..32].copy_from_slice(&key.ak.to_bytes());
+        bytes[32..].copy_from_slice(&key.nsk.to_bytes());
+        bytes
+    }
+
+    pub(crate) fn read(bytes: &[u8; 64]) -> Option<Self> {
+        let ak = jubjub::SubgroupPoint::from_bytes(bytes[..32].try_into().unwrap());
+        let nsk = jubjub::Fr::from_bytes(bytes[32..].try_into().unwrap());
+        Option::from(ak.and_then(|ak| nsk.map(|nsk| ProofGenerationKey { ak, nsk }))).map(Self)
+    }
 }
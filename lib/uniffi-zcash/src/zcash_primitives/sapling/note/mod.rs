@@ -5,8 +5,8 @@
 pub use self::nullifier::*;
 
 use crate::{
-    utils::cast_slice, ZcashError, ZcashJubjubFr, ZcashPaymentAddress, ZcashResult,
-    ZcashSaplingNoteValue,
+    utils::cast_slice, ZcashError, ZcashJubjubFr, ZcashNullifierDerivingKey, ZcashPaymentAddress,
+    ZcashResult, ZcashSaplingNoteValue,
 };
 
 use std::sync::Arc;
@@ -52,6 +52,16 @@ pub fn value(&self) -> Arc<ZcashSaplingNoteValue> {
     pub fn cmu(&self) -> Arc<ZcashSaplingExtractedNoteCommitment> {
         Arc::new(self.0.cmu().into())
     }
+
+    /// Computes the nullifier given the nullifier deriving key and
+    /// note position
+    pub fn nf(
+        &self,
+        nk: Arc<ZcashNullifierDerivingKey>,
+        position: u64,
+    ) -> Arc<ZcashSaplingNullifier> {
+        Arc::new(self.0.nf(&nk.as_ref().into(), position).into())
+    }
 }
 
 impl From<&ZcashSaplingNote> for Note {
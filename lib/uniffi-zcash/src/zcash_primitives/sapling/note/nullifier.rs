@@ -1,12 +1,24 @@
 use zcash_primitives::sapling::Nullifier;
 
+use crate::{utils::cast_slice, ZcashResult};
+
 use derive_more::{From, Into};
 
 #[derive(Copy, Clone, PartialEq, Eq, From, Into)]
 pub struct ZcashSaplingNullifier(Nullifier);
 
 impl ZcashSaplingNullifier {
+    pub fn from_bytes(bytes: &[u8]) -> ZcashResult<Self> {
+        Ok(Nullifier(cast_slice(bytes)?).into())
+    }
+
     pub fn to_bytes(&self) -> Vec<u8> {
         self.0.to_vec()
     }
 }
+
+impl From<&ZcashSaplingNullifier> for Nullifier {
+    fn from(value: &ZcashSaplingNullifier) -> Self {
+        value.0
+    }
+}
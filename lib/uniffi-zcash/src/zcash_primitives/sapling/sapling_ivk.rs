@@ -1,8 +1,9 @@
 use std::sync::Arc;
 
+use group::ff::PrimeField;
 use zcash_primitives::sapling::SaplingIvk;
 
-use crate::{ZcashDiversifier, ZcashPaymentAddress};
+use crate::{utils, ZcashDiversifier, ZcashPaymentAddress, ZcashResult};
 
 pub struct ZcashSaplingIvk(SaplingIvk);
 
@@ -12,7 +13,20 @@ fn from(ivk: SaplingIvk) -> Self {
     }
 }
 
+impl From<&ZcashSaplingIvk> for SaplingIvk {
+    fn from(value: &ZcashSaplingIvk) -> Self {
+        value.0.clone()
+    }
+}
+
 impl ZcashSaplingIvk {
+    /// Parses an incoming viewing key serialized with [`Self::to_repr`].
+    pub fn from_repr(bytes: &[u8]) -> ZcashResult<Self> {
+        let repr = utils::cast_slice(bytes)?;
+        let ivk: Option<jubjub::Fr> = jubjub::Fr::from_repr(repr).into();
+        Ok(SaplingIvk(ivk.ok_or("unable to parse Sapling incoming viewing key")?).into())
+    }
+
     pub fn to_payment_address(
         &self,
         diversifier: Arc<ZcashDiversifier>,
@@ -1,7 +1,8 @@
 use std::sync::Arc;
+use zcash_primitives::merkle_tree::HashSer;
 use zcash_primitives::sapling::Node;
 
-use crate::ZcashSaplingExtractedNoteCommitment;
+use crate::{ZcashResult, ZcashSaplingExtractedNoteCommitment};
 
 use derive_more::{From, Into};
 
@@ -13,4 +14,16 @@ impl ZcashSaplingNode {
     pub fn from_cmu(cmu: Arc<ZcashSaplingExtractedNoteCommitment>) -> Self {
         Self(Node::from_cmu(&cmu.0))
     }
+
+    /// Reads a node from its 32-byte encoding.
+    pub fn from_bytes(data: &[u8]) -> ZcashResult<Self> {
+        Ok(Self(Node::read(data)?))
+    }
+
+    /// Returns the 32-byte encoding of the node.
+    pub fn to_bytes(&self) -> ZcashResult<Vec<u8>> {
+        let mut data = Vec::with_capacity(32);
+        self.0.write(&mut data)?;
+        Ok(data)
+    }
 }
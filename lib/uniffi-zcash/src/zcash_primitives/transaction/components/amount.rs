@@ -1,6 +1,6 @@
 use std::sync::Arc;
 use zcash_primitives::transaction::components::{
-    amount::{NonNegativeAmount, MAX_MONEY},
+    amount::{BalanceError, NonNegativeAmount, COIN, MAX_MONEY},
     Amount,
 };
 
@@ -9,6 +9,9 @@
 use crate::{ZcashError, ZcashResult};
 use derive_more::{From, Into};
 
+/// The most decimal places an amount can be formatted with, as for 128-bit decimal types.
+const MAX_DECIMALS: u8 = 38;
+
 /// A type-safe representation of some quantity of Zcash.
 ///
 /// An Amount can only be constructed from an integer that is within the valid monetary
@@ -57,13 +60,43 @@ pub fn new(value: i64) -> crate::ZcashResult<Self> {
         Ok(ZcashAmount(amount))
     }
 
-    // pub fn from_u64(value: u64) -> Self {
-    //     ZcashAmount(self.0.from_u64(value))
-    // }
+    /// Creates an Amount from a u64.
+    ///
+    /// Returns an error if the amount is outside the range `{0..MAX_MONEY}`.
+    pub fn from_u64(value: u64) -> ZcashResult<Self> {
+        Amount::from_u64(value)
+            .map(ZcashAmount)
+            .map_err(|_| out_of_range(value, 0))
+    }
+
+    /// Creates an Amount from an i64.
+    ///
+    /// Returns an error if the amount is outside the range `{0..MAX_MONEY}`.
+    pub fn from_nonnegative_i64(value: i64) -> ZcashResult<Self> {
+        Amount::from_nonnegative_i64(value)
+            .map(ZcashAmount)
+            .map_err(|_| ZcashError::ValueOutOfRange {
+                val: value,
+                from: 0,
+                to: MAX_MONEY,
+            })
+    }
+
+    /// Parses a decimal amount of ZEC such as `"1.2345"` or `"-0.5"`, with at most
+    /// 8 decimal places.
+    pub fn parse_zec(value: &str) -> ZcashResult<Self> {
+        Self::new(parse_zatoshis(value)?)
+    }
 
-    // pub fn from_nonnegative_i64(value: i64) -> Self {
-    //     ZcashAmount(self.0.from_nonnegative_i64(value))
-    // }
+    /// Returns the sum of `amounts`, failing if it leaves the valid monetary range.
+    pub fn sum(amounts: Vec<Arc<ZcashAmount>>) -> ZcashResult<Self> {
+        amounts
+            .iter()
+            .try_fold(Arc::new(Self::zero()), |total, amount| {
+                total.checked_add(amount.clone())
+            })
+            .map(|total| *total)
+    }
 
     /// Returns a zero-valued Amount.
     pub fn zero() -> Self {
@@ -78,6 +111,33 @@ pub const fn is_negative(self) -> bool {
     pub fn value(&self) -> i64 {
         self.0.into()
     }
+
+    /// Adds `other`, failing if the result leaves the valid monetary range.
+    pub fn checked_add(&self, other: Arc<ZcashAmount>) -> ZcashResult<Arc<Self>> {
+        checked_result(self.value() + other.value()).map(Arc::new)
+    }
+
+    /// Subtracts `other`, failing if the result leaves the valid monetary range.
+    pub fn checked_sub(&self, other: Arc<ZcashAmount>) -> ZcashResult<Arc<Self>> {
+        checked_result(self.value() - other.value()).map(Arc::new)
+    }
+
+    pub fn negate(&self) -> Arc<Self> {
+        Arc::new(ZcashAmount(-self.0))
+    }
+
+    /// Formats the amount in `unit` with exactly `decimals` decimal places, rounding
+    /// as specified by `rounding`.
+    ///
+    /// Fails if `decimals` is above 38.
+    pub fn format(
+        &self,
+        unit: ZcashAmountUnit,
+        decimals: u8,
+        rounding: ZcashRounding,
+    ) -> ZcashResult<String> {
+        format_zatoshis(self.value(), unit, decimals, rounding)
+    }
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, PartialOrd, Eq, Ord, From, Into)]
@@ -92,9 +152,7 @@ pub fn zero() -> Self {
     pub fn from_u64(amount: u64) -> ZcashResult<Self> {
         NonNegativeAmount::from_u64(amount)
             .map(Self)
-            .map_err(|_| ZcashError::Message {
-                error: "maybe it is negative".to_string(),
-            })
+            .map_err(|_| out_of_range(amount, 0))
     }
 
     /// Creates a NonNegativeAmount from an i64.
@@ -103,15 +161,59 @@ pub fn from_u64(amount: u64) -> ZcashResult<Self> {
     pub fn from_nonnegative_i64(amount: i64) -> ZcashResult<Self> {
         NonNegativeAmount::from_nonnegative_i64(amount)
             .map(Self)
-            .map_err(|_| ZcashError::Message {
-                error: "maybe it is negative".to_string(),
+            .map_err(|_| ZcashError::ValueOutOfRange {
+                val: amount,
+                from: 0,
+                to: MAX_MONEY,
+            })
+    }
+
+    /// Parses a decimal amount of ZEC such as `"1.2345"`, with at most 8 decimal places.
+    pub fn parse_zec(value: &str) -> ZcashResult<Self> {
+        Self::from_nonnegative_i64(parse_zatoshis(value)?)
+    }
+
+    /// Returns the sum of `amounts`, failing if it exceeds `MAX_MONEY`.
+    pub fn sum(amounts: Vec<Arc<ZcashNonNegativeAmount>>) -> ZcashResult<Self> {
+        amounts
+            .iter()
+            .try_fold(Arc::new(Self::zero()), |total, amount| {
+                total.checked_add(amount.clone())
             })
+            .map(|total| *total)
     }
 
     pub fn value(&self) -> u64 {
         let amount: Amount = self.0.into();
         amount.into()
     }
+
+    /// Adds `other`, failing if the result exceeds `MAX_MONEY`.
+    pub fn checked_add(&self, other: Arc<ZcashNonNegativeAmount>) -> ZcashResult<Arc<Self>> {
+        (self.0 + other.0)
+            .map(|amount| Arc::new(Self(amount)))
+            .ok_or(BalanceError::Overflow.into())
+    }
+
+    /// Subtracts `other`, failing if the result is negative.
+    pub fn checked_sub(&self, other: Arc<ZcashNonNegativeAmount>) -> ZcashResult<Arc<Self>> {
+        (self.0 - other.0)
+            .map(|amount| Arc::new(Self(amount)))
+            .ok_or(BalanceError::Underflow.into())
+    }
+
+    /// Formats the amount in `unit` with exactly `decimals` decimal places, rounding
+    /// as specified by `rounding`.
+    ///
+    /// Fails if `decimals` is above 38.
+    pub fn format(
+        &self,
+        unit: ZcashAmountUnit,
+        decimals: u8,
+        rounding: ZcashRounding,
+    ) -> ZcashResult<String> {
+        format_zatoshis(self.value() as i64, unit, decimals, rounding)
+    }
 }
 
 impl TryFrom<ZcashAmount> for ZcashNonNegativeAmount {
@@ -130,6 +232,147 @@ fn try_from(value: ZcashAmount) -> Result<Self, Self::Error> {
     }
 }
 
+/// The units in which amounts can be formatted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ZcashAmountUnit {
+    /// 1 ZEC = 10⁸ zatoshis.
+    Zec,
+    /// 1 mZEC = 10⁵ zatoshis.
+    MilliZec,
+    /// 1 µZEC = 10² zatoshis.
+    MicroZec,
+    Zatoshi,
+}
+
+impl ZcashAmountUnit {
+    /// Returns the number of decimal places of the unit in zatoshis.
+    fn exponent(self) -> u32 {
+        match self {
+            ZcashAmountUnit::Zec => 8,
+            ZcashAmountUnit::MilliZec => 5,
+            ZcashAmountUnit::MicroZec => 2,
+            ZcashAmountUnit::Zatoshi => 0,
+        }
+    }
+}
+
+/// How to round an amount that has more decimal places than are formatted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ZcashRounding {
+    /// Rounds towards zero.
+    Down,
+    /// Rounds away from zero.
+    Up,
+    /// Rounds to the nearest value, and ties away from zero.
+    HalfUp,
+    /// Rounds to the nearest value, and ties to the even neighbour.
+    HalfEven,
+}
+
+/// Parses a decimal amount of ZEC into zatoshis, without checking the monetary range.
+fn parse_zatoshis(value: &str) -> ZcashResult<i64> {
+    let invalid = || -> ZcashError { format!("Invalid ZEC amount {:?}", value).into() };
+
+    let (negative, digits) = match value.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, value),
+    };
+    let (whole, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+
+    let is_digits = |part: &str| part.bytes().all(|b| b.is_ascii_digit());
+    if whole.is_empty() || !is_digits(whole) || !is_digits(fraction) || digits.ends_with('.') {
+        return Err(invalid());
+    }
+    if fraction.len() > 8 {
+        return Err(format!("Invalid ZEC amount {:?}: more than 8 decimal places", value).into());
+    }
+
+    // MAX_MONEY has 8 whole digits, anything longer is out of range and could overflow below.
+    let whole: i64 = if whole.trim_start_matches('0').len() > 8 {
+        return Err(out_of_range(
+            u64::MAX,
+            if negative { -MAX_MONEY } else { 0 },
+        ));
+    } else {
+        whole.parse().map_err(|_| invalid())?
+    };
+    let fraction: i64 = format!("{:0<8}", fraction).parse().map_err(|_| invalid())?;
+
+    let zatoshis = whole * COIN + fraction;
+    Ok(if negative { -zatoshis } else { zatoshis })
+}
+
+/// Formats `zatoshis` in `unit` with exactly `decimals` decimal places.
+fn format_zatoshis(
+    zatoshis: i64,
+    unit: ZcashAmountUnit,
+    decimals: u8,
+    rounding: ZcashRounding,
+) -> ZcashResult<String> {
+    if decimals > MAX_DECIMALS {
+        return Err(format!(
+            "Cannot format an amount with more than {} decimal places",
+            MAX_DECIMALS
+        )
+        .into());
+    }
+
+    let exponent = unit.exponent();
+    let decimals = u32::from(decimals);
+    let magnitude = u128::from(zatoshis.unsigned_abs());
+
+    // The decimal places beyond the precision of `unit` are always zero, and are padded below.
+    let precision = decimals.min(exponent);
+
+    // The amount in units of 10^-precision of `unit`, before and after rounding.
+    let divisor = 10u128.pow(exponent - precision);
+    let (scaled, remainder) = (magnitude / divisor, magnitude % divisor);
+    let round_up = match rounding {
+        ZcashRounding::Down => false,
+        ZcashRounding::Up => remainder > 0,
+        ZcashRounding::HalfUp => 2 * remainder >= divisor && remainder > 0,
+        ZcashRounding::HalfEven => {
+            2 * remainder > divisor || (2 * remainder == divisor && scaled % 2 == 1)
+        }
+    };
+    let scaled = scaled + u128::from(round_up);
+
+    let sign = if zatoshis < 0 && scaled > 0 { "-" } else { "" };
+    let unit_size = 10u128.pow(precision);
+    let mut formatted = format!("{}{}", sign, scaled / unit_size);
+    if decimals > 0 {
+        formatted.push('.');
+        if precision > 0 {
+            formatted.push_str(&format!(
+                "{:0width$}",
+                scaled % unit_size,
+                width = precision as usize
+            ));
+        }
+        formatted.push_str(&"0".repeat((decimals - precision) as usize));
+    }
+    Ok(formatted)
+}
+
+/// Checks that an amount computed in i64 is within the valid monetary range.
+fn checked_result(value: i64) -> ZcashResult<ZcashAmount> {
+    Amount::from_i64(value).map(ZcashAmount).map_err(|_| {
+        if value > 0 {
+            BalanceError::Overflow.into()
+        } else {
+            BalanceError::Underflow.into()
+        }
+    })
+}
+
+fn out_of_range(value: u64, from: i64) -> ZcashError {
+    ZcashError::ValueOutOfRange {
+        val: value.try_into().unwrap_or(i64::MAX),
+        from,
+        to: MAX_MONEY,
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct ZcashBalance(Balance);
 
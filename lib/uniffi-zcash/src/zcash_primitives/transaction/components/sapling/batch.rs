@@ -0,0 +1,62 @@
+use std::sync::{Arc, RwLock};
+
+use hdwallet::rand_core::OsRng;
+use zcash_proofs::sapling::BatchValidator;
+
+use crate::{utils::cast_slice, ZcashResult, ZcashSaplingBundle, ZcashSaplingVerifyingKeys};
+
+/// Validates the proofs and signatures of many Sapling bundles together.
+///
+/// Signatures are verified assuming ZIP 216 is active.
+pub struct ZcashSaplingBatchValidator {
+    bundles: RwLock<Vec<(Arc<ZcashSaplingBundle>, [u8; 32])>>,
+}
+
+impl ZcashSaplingBatchValidator {
+    pub fn new() -> Self {
+        Self {
+            bundles: RwLock::new(Vec::new()),
+        }
+    }
+
+    /// Adds a bundle to the batch, along with the signature hash of its transaction.
+    pub fn add_bundle(&self, bundle: Arc<ZcashSaplingBundle>, sighash: Vec<u8>) -> ZcashResult<()> {
+        let sighash = cast_slice(&sighash)?;
+        self.bundles.write().unwrap().push((bundle, sighash));
+        Ok(())
+    }
+
+    /// Validates all the bundles added so far.
+    ///
+    /// Returns the indices, in order of addition, of the bundles that are invalid. When the
+    /// batch fails, each bundle is validated on its own to find the culprits.
+    pub fn validate(&self, keys: Arc<ZcashSaplingVerifyingKeys>) -> Vec<u32> {
+        let bundles = self.bundles.read().unwrap();
+
+        let mut batch = BatchValidator::new();
+        let consensus_valid = bundles
+            .iter()
+            .all(|(bundle, sighash)| batch.check_bundle(bundle.0.clone(), *sighash));
+
+        if consensus_valid && batch.validate(&keys.spend_vk, &keys.output_vk, OsRng) {
+            return Vec::new();
+        }
+
+        bundles
+            .iter()
+            .enumerate()
+            .filter(|(_, (bundle, sighash))| {
+                let mut single = BatchValidator::new();
+                !(single.check_bundle(bundle.0.clone(), *sighash)
+                    && single.validate(&keys.spend_vk, &keys.output_vk, OsRng))
+            })
+            .map(|(index, _)| index as u32)
+            .collect()
+    }
+}
+
+impl Default for ZcashSaplingBatchValidator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
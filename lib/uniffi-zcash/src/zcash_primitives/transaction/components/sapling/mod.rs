@@ -1,16 +1,31 @@
+mod batch;
+pub use self::batch::*;
+
 mod builder;
 pub use self::builder::*;
 
 use std::sync::Arc;
 
+use zcash_primitives::consensus::BlockHeight;
+use zcash_primitives::memo::MemoBytes;
+use zcash_primitives::sapling::{
+    keys::OutgoingViewingKey,
+    note_encryption::{
+        try_sapling_compact_note_decryption, try_sapling_note_decryption,
+        try_sapling_output_recovery, PreparedIncomingViewingKey,
+    },
+    Note, PaymentAddress, SaplingIvk,
+};
 use zcash_primitives::transaction::components::{
-    sapling::{Authorized, Bundle, GrothProofBytes, SpendDescription},
+    sapling::{Authorized, Bundle, CompactOutputDescription, GrothProofBytes, SpendDescription},
     OutputDescription,
 };
 
 use crate::{
-    ZcashAmount, ZcashSaplingExtractedNoteCommitment, ZcashSaplingNullifier, ZcashSaplingPublicKey,
-    ZcashSaplingValueCommitment,
+    utils::cast_slice, ZcashAmount, ZcashBlockHeight, ZcashConsensusParameters, ZcashError,
+    ZcashMemoBytes, ZcashOutgoingViewingKey, ZcashPaymentAddress, ZcashResult,
+    ZcashSaplingExtractedNoteCommitment, ZcashSaplingIvk, ZcashSaplingNote, ZcashSaplingNullifier,
+    ZcashSaplingPublicKey, ZcashSaplingValueCommitment,
 };
 
 pub struct ZcashSaplingBundle(Bundle<Authorized>);
@@ -40,6 +55,149 @@ pub fn shielded_outputs(&self) -> Vec<Arc<ZcashSaplingOutputDescription>> {
     pub fn value_balance(&self) -> Arc<ZcashAmount> {
         Arc::new(self.0.value_balance().into())
     }
+
+    /// Matches the nullifiers revealed by the spends of this bundle against the
+    /// nullifiers of known notes, and returns the index of each matching spend along
+    /// with the index of the note it spends.
+    pub fn match_spends(
+        &self,
+        known_nullifiers: Vec<Arc<ZcashSaplingNullifier>>,
+    ) -> Vec<ZcashSaplingSpendMatch> {
+        self.0
+            .shielded_spends()
+            .iter()
+            .enumerate()
+            .filter_map(|(spend_idx, spend)| {
+                known_nullifiers
+                    .iter()
+                    .position(|nf| *spend.nullifier() == nf.as_ref().into())
+                    .map(|note_idx| ZcashSaplingSpendMatch {
+                        spend_idx: spend_idx as u64,
+                        note_idx: note_idx as u64,
+                    })
+            })
+            .collect()
+    }
+
+    /// Performs trial decryption of the output at `output_idx` in the bundle with the
+    /// specified incoming viewing key, and returns the decrypted note plaintext
+    /// contents if successful.
+    ///
+    /// `height` is the height of the block containing the transaction, which determines
+    /// whether [ZIP 212](https://zips.z.cash/zip-0212) note plaintexts are accepted.
+    pub fn decrypt_output_with_key(
+        &self,
+        params: ZcashConsensusParameters,
+        height: Arc<ZcashBlockHeight>,
+        output_idx: u64,
+        ivk: Arc<ZcashSaplingIvk>,
+    ) -> ZcashResult<ZcashSaplingDecryptOutput> {
+        let output = self
+            .0
+            .shielded_outputs()
+            .get(usize::try_from(output_idx)?)
+            .ok_or("Output index out of range")?;
+
+        match try_sapling_note_decryption(
+            &params,
+            height.as_ref().into(),
+            &PreparedIncomingViewingKey::new(&ivk.as_ref().into()),
+            output,
+        ) {
+            Some(result) => Ok(result.into()),
+            None => Err("Cannot decrypt output".into()),
+        }
+    }
+
+    /// Performs trial decryption of each output in the bundle with each of the
+    /// specified incoming viewing keys, and returns a vector of each decrypted
+    /// note plaintext contents along with the index of the output from which it
+    /// was derived.
+    pub fn decrypt_outputs_with_keys(
+        &self,
+        params: ZcashConsensusParameters,
+        height: Arc<ZcashBlockHeight>,
+        ivks: Vec<Arc<ZcashSaplingIvk>>,
+    ) -> Vec<ZcashSaplingDecryptOutputForIncomingKeys> {
+        let height: BlockHeight = height.as_ref().into();
+        let keys = ivks
+            .into_iter()
+            .map(|ivk| {
+                let ivk: SaplingIvk = ivk.as_ref().into();
+                (PreparedIncomingViewingKey::new(&ivk), ivk)
+            })
+            .collect::<Vec<(PreparedIncomingViewingKey, SaplingIvk)>>();
+
+        self.0
+            .shielded_outputs()
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, output)| {
+                keys.iter().find_map(|(prepared_ivk, ivk)| {
+                    try_sapling_note_decryption(&params, height, prepared_ivk, output)
+                        .map(|(note, address, memo)| (idx, ivk.clone(), note, address, memo))
+                })
+            })
+            .map(|e| e.try_into().unwrap())
+            .collect()
+    }
+
+    /// Attempts to decrypt the output at the specified index with the specified
+    /// outgoing viewing key, and returns the decrypted note plaintext contents
+    /// if successful.
+    pub fn recover_output_with_ovk(
+        &self,
+        params: ZcashConsensusParameters,
+        height: Arc<ZcashBlockHeight>,
+        output_idx: u64,
+        ovk: Arc<ZcashOutgoingViewingKey>,
+    ) -> ZcashResult<ZcashSaplingDecryptOutput> {
+        let output = self
+            .0
+            .shielded_outputs()
+            .get(usize::try_from(output_idx)?)
+            .ok_or("Output index out of range")?;
+
+        match try_sapling_output_recovery(
+            &params,
+            height.as_ref().into(),
+            &ovk.as_ref().into(),
+            output,
+        ) {
+            Some(result) => Ok(result.into()),
+            None => Err("Cannot recover output".into()),
+        }
+    }
+
+    /// Performs trial decryption of each output in the bundle with each of the
+    /// specified outgoing viewing keys, and returns a vector of each decrypted
+    /// note plaintext contents along with the index of the output from which it
+    /// was derived.
+    pub fn recover_outputs_with_ovks(
+        &self,
+        params: ZcashConsensusParameters,
+        height: Arc<ZcashBlockHeight>,
+        ovks: Vec<Arc<ZcashOutgoingViewingKey>>,
+    ) -> Vec<ZcashSaplingDecryptOutputForOutgoingKeys> {
+        let height: BlockHeight = height.as_ref().into();
+        let keys = ovks
+            .into_iter()
+            .map(|key| key.as_ref().into())
+            .collect::<Vec<OutgoingViewingKey>>();
+
+        self.0
+            .shielded_outputs()
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, output)| {
+                keys.iter().find_map(|ovk| {
+                    try_sapling_output_recovery(&params, height, ovk, output)
+                        .map(|(note, address, memo)| (idx, *ovk, note, address, memo))
+                })
+            })
+            .map(|e| e.try_into().unwrap())
+            .collect()
+    }
 }
 
 impl From<&Bundle<Authorized>> for ZcashSaplingBundle {
@@ -88,6 +246,27 @@ pub fn cv(&self) -> Arc<ZcashSaplingValueCommitment> {
     pub fn cmu(&self) -> Arc<ZcashSaplingExtractedNoteCommitment> {
         Arc::new(self.0.cmu().into())
     }
+
+    pub fn ephemeral_key(&self) -> Vec<u8> {
+        self.0.ephemeral_key().0.to_vec()
+    }
+
+    pub fn enc_ciphertext(&self) -> Vec<u8> {
+        self.0.enc_ciphertext().to_vec()
+    }
+
+    pub fn out_ciphertext(&self) -> Vec<u8> {
+        self.0.out_ciphertext().to_vec()
+    }
+
+    pub fn zkproof(&self) -> Vec<u8> {
+        self.0.zkproof().to_vec()
+    }
+
+    /// Returns the compact form of this output, as found in compact blocks.
+    pub fn to_compact(&self) -> Arc<ZcashSaplingCompactOutputDescription> {
+        Arc::new(CompactOutputDescription::from(self.0.clone()).into())
+    }
 }
 
 impl From<&OutputDescription<GrothProofBytes>> for ZcashSaplingOutputDescription {
@@ -95,3 +274,147 @@ fn from(inner: &OutputDescription<GrothProofBytes>) -> Self {
         ZcashSaplingOutputDescription(inner.clone())
     }
 }
+
+/// The compact form of a Sapling output, carrying only the first 52 bytes of the
+/// note ciphertext.
+#[derive(Clone)]
+pub struct ZcashSaplingCompactOutputDescription(CompactOutputDescription);
+
+impl ZcashSaplingCompactOutputDescription {
+    pub fn from_parts(
+        ephemeral_key: Vec<u8>,
+        cmu: Arc<ZcashSaplingExtractedNoteCommitment>,
+        enc_ciphertext: Vec<u8>,
+    ) -> ZcashResult<Self> {
+        let ephemeral_key: [u8; 32] = cast_slice(&ephemeral_key)?;
+        Ok(Self(CompactOutputDescription {
+            ephemeral_key: ephemeral_key.into(),
+            cmu: cmu.0,
+            enc_ciphertext: cast_slice(&enc_ciphertext)?,
+        }))
+    }
+
+    pub fn ephemeral_key(&self) -> Vec<u8> {
+        self.0.ephemeral_key.0.to_vec()
+    }
+
+    pub fn cmu(&self) -> Arc<ZcashSaplingExtractedNoteCommitment> {
+        Arc::new(self.0.cmu.into())
+    }
+
+    pub fn enc_ciphertext(&self) -> Vec<u8> {
+        self.0.enc_ciphertext.to_vec()
+    }
+
+    /// Performs trial decryption of this output with the specified incoming viewing
+    /// key, and returns the decrypted note and recipient if successful.
+    ///
+    /// The compact ciphertext does not contain the memo field.
+    pub fn decrypt_with_key(
+        &self,
+        params: ZcashConsensusParameters,
+        height: Arc<ZcashBlockHeight>,
+        ivk: Arc<ZcashSaplingIvk>,
+    ) -> ZcashResult<ZcashSaplingCompactDecryptOutput> {
+        match try_sapling_compact_note_decryption(
+            &params,
+            height.as_ref().into(),
+            &PreparedIncomingViewingKey::new(&ivk.as_ref().into()),
+            &self.0,
+        ) {
+            Some(result) => Ok(result.into()),
+            None => Err("Cannot decrypt output".into()),
+        }
+    }
+}
+
+impl From<CompactOutputDescription> for ZcashSaplingCompactOutputDescription {
+    fn from(inner: CompactOutputDescription) -> Self {
+        ZcashSaplingCompactOutputDescription(inner)
+    }
+}
+
+pub struct ZcashSaplingDecryptOutput {
+    pub note: Arc<ZcashSaplingNote>,
+    pub address: Arc<ZcashPaymentAddress>,
+    pub memo: Arc<ZcashMemoBytes>,
+}
+
+impl From<(Note, PaymentAddress, MemoBytes)> for ZcashSaplingDecryptOutput {
+    fn from((note, address, memo): (Note, PaymentAddress, MemoBytes)) -> Self {
+        Self {
+            note: Arc::new(note.into()),
+            address: Arc::new(address.into()),
+            memo: Arc::new(memo.into()),
+        }
+    }
+}
+
+pub struct ZcashSaplingCompactDecryptOutput {
+    pub note: Arc<ZcashSaplingNote>,
+    pub address: Arc<ZcashPaymentAddress>,
+}
+
+impl From<(Note, PaymentAddress)> for ZcashSaplingCompactDecryptOutput {
+    fn from((note, address): (Note, PaymentAddress)) -> Self {
+        Self {
+            note: Arc::new(note.into()),
+            address: Arc::new(address.into()),
+        }
+    }
+}
+
+pub struct ZcashSaplingDecryptOutputForIncomingKeys {
+    pub idx: u64,
+    pub key: Arc<ZcashSaplingIvk>,
+    pub note: Arc<ZcashSaplingNote>,
+    pub address: Arc<ZcashPaymentAddress>,
+    pub memo: Arc<ZcashMemoBytes>,
+}
+
+impl TryFrom<(usize, SaplingIvk, Note, PaymentAddress, MemoBytes)>
+    for ZcashSaplingDecryptOutputForIncomingKeys
+{
+    type Error = ZcashError;
+    fn try_from(
+        value: (usize, SaplingIvk, Note, PaymentAddress, MemoBytes),
+    ) -> Result<Self, Self::Error> {
+        Ok(Self {
+            idx: value.0.try_into()?,
+            key: Arc::new(value.1.into()),
+            note: Arc::new(value.2.into()),
+            address: Arc::new(value.3.into()),
+            memo: Arc::new(value.4.into()),
+        })
+    }
+}
+
+pub struct ZcashSaplingDecryptOutputForOutgoingKeys {
+    pub idx: u64,
+    pub key: Arc<ZcashOutgoingViewingKey>,
+    pub note: Arc<ZcashSaplingNote>,
+    pub address: Arc<ZcashPaymentAddress>,
+    pub memo: Arc<ZcashMemoBytes>,
+}
+
+impl TryFrom<(usize, OutgoingViewingKey, Note, PaymentAddress, MemoBytes)>
+    for ZcashSaplingDecryptOutputForOutgoingKeys
+{
+    type Error = ZcashError;
+    fn try_from(
+        value: (usize, OutgoingViewingKey, Note, PaymentAddress, MemoBytes),
+    ) -> Result<Self, Self::Error> {
+        Ok(Self {
+            idx: value.0.try_into()?,
+            key: Arc::new(value.1.into()),
+            note: Arc::new(value.2.into()),
+            address: Arc::new(value.3.into()),
+            memo: Arc::new(value.4.into()),
+        })
+    }
+}
+
+pub struct ZcashSaplingSpendMatch {
+    pub spend_idx: u64,
+    pub note_idx: u64,
+}
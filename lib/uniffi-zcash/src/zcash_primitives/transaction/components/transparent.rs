@@ -17,6 +17,16 @@ pub fn new(hash: &[u8], n: u32) -> ZcashResult<Self> {
         let casted_data = cast_slice(hash)?;
         Ok(OutPoint::new(casted_data, n).into())
     }
+
+    /// Returns the ID of the transaction that created the output.
+    pub fn hash(&self) -> Vec<u8> {
+        self.0.hash().to_vec()
+    }
+
+    /// Returns the index of the output in the transaction that created it.
+    pub fn n(&self) -> u32 {
+        self.0.n()
+    }
 }
 
 #[derive(From)]
@@ -65,6 +75,22 @@ fn from(inner: &TxOut) -> Self {
 pub struct ZcashTxIn(TxIn<Authorized>);
 
 impl ZcashTxIn {
+    /// Returns the outpoint of the output spent by this input.
+    pub fn prevout(&self) -> Arc<ZcashOutPoint> {
+        Arc::new(self.0.prevout.clone().into())
+    }
+
+    /// Returns the script satisfying the conditions of the spent output.
+    pub fn script_sig(&self) -> Arc<ZcashScript> {
+        Arc::new(self.0.script_sig.clone().into())
+    }
+
+    /// Returns the sequence number of the input, `0xFFFFFFFF` unless the transaction
+    /// uses its lock time.
+    pub fn sequence(&self) -> u32 {
+        self.0.sequence
+    }
+
     pub fn to_bytes(&self) -> ZcashResult<Vec<u8>> {
         let mut data = Vec::new();
         self.0.write(&mut data)?;
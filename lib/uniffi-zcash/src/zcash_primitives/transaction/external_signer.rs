@@ -0,0 +1,393 @@
+use std::sync::Arc;
+
+use group::ff::PrimeField;
+use orchard::{bundle::Flags, primitives::redpallas};
+use rand::rngs::OsRng;
+use secp256k1::{ecdsa::Signature, Message, PublicKey, Secp256k1, SecretKey};
+use zcash_primitives::{
+    legacy::{Script, TransparentAddress},
+    memo::MemoBytes,
+    sapling::{
+        redjubjub::{self, PrivateKey},
+        spend_sig,
+    },
+    transaction::components::TxOut,
+    zip32::ExtendedSpendingKey,
+};
+use zcash_proofs::prover::LocalTxProver;
+
+use super::unauthorized::{
+    ChangeAddress, OrchardOutput, OrchardSpend, SaplingOutput, SaplingSpend,
+    UnauthorizedTransaction, UnprovenTransaction,
+};
+use super::{transparent_signing, OrchardOutputs, OrchardSpends};
+use crate::{
+    SecpPublicKey, ZcashFeeRules, ZcashLocalTxProver, ZcashMemoBytes, ZcashOrchardAddress,
+    ZcashOrchardFullViewingKey, ZcashOrchardMerklePath, ZcashOrchardNote, ZcashOrchardNoteValue,
+    ZcashOrchardOutgoingViewingKey, ZcashOrchardTransactionBuilder, ZcashOutPoint,
+    ZcashOutgoingViewingKey, ZcashPaymentAddress, ZcashProofGenerationKey, ZcashResult,
+    ZcashSaplingMerklePath, ZcashSaplingNote, ZcashTransaction, ZcashTransactionBuilder,
+    ZcashTransparentAddress, ZcashTxOut,
+};
+
+/// Signer that keeps spending keys outside of this library, for instance on a
+/// hardware wallet.
+///
+/// Each method returns `None` if the signer declines to sign. The signatures are
+/// verified before being added to the transaction.
+pub trait ZcashExternalSigner: Send + Sync {
+    /// Signs the ZIP 244 signature hash of the transparent input at `input_index`
+    /// with `SIGHASH_ALL`, returning the DER-encoded ECDSA signature.
+    fn sign_transparent(&self, sighash: Vec<u8>, input_index: u32) -> Option<Vec<u8>>;
+
+    /// Signs the shielded signature hash of the transaction for a Sapling spend,
+    /// returning the 64-byte RedJubjub signature made with the spend authorizing key
+    /// randomized by `alpha`.
+    ///
+    /// `spend_index` is the index of the spend among those added for the signer.
+    fn sign_sapling(&self, sighash: Vec<u8>, alpha: Vec<u8>, spend_index: u32) -> Option<Vec<u8>>;
+
+    /// Signs the shielded signature hash of the transaction for an Orchard spend,
+    /// returning the 64-byte RedPallas signature made with the spend authorizing key
+    /// randomized by `alpha`.
+    ///
+    /// `spend_index` is the index of the spend among those added for the signer.
+    fn sign_orchard(&self, sighash: Vec<u8>, alpha: Vec<u8>, spend_index: u32) -> Option<Vec<u8>>;
+}
+
+/// The address receiving the change of a transaction built with an external signer.
+pub struct ZcashChangeAddress(ChangeAddress);
+
+impl ZcashChangeAddress {
+    pub fn transparent(address: Arc<ZcashTransparentAddress>) -> Self {
+        Self(ChangeAddress::Transparent(address.as_ref().into()))
+    }
+
+    pub fn sapling(
+        ovk: Option<Arc<ZcashOutgoingViewingKey>>,
+        address: Arc<ZcashPaymentAddress>,
+    ) -> Self {
+        Self(ChangeAddress::Sapling(
+            ovk.map(|ovk| ovk.as_ref().into()),
+            address.as_ref().into(),
+        ))
+    }
+
+    pub fn orchard(
+        ovk: Option<Arc<ZcashOrchardOutgoingViewingKey>>,
+        address: Arc<ZcashOrchardAddress>,
+    ) -> Self {
+        Self(ChangeAddress::Orchard(
+            ovk.map(|ovk| ovk.as_ref().into()),
+            address.0,
+        ))
+    }
+}
+
+impl ZcashTransactionBuilder {
+    /// Adds a P2PKH input whose spending key is held by a [`ZcashExternalSigner`].
+    ///
+    /// `pubkey` is the serialized secp256k1 public key the signature will be verified against.
+    pub fn add_transparent_input_for_signer(
+        &self,
+        pubkey: Vec<u8>,
+        utxo: Arc<ZcashOutPoint>,
+        coin: Arc<ZcashTxOut>,
+    ) -> ZcashResult<()> {
+        let pubkey = PublicKey::from_slice(pubkey.as_slice())?;
+
+        match TxOut::from(coin.as_ref()).recipient_address() {
+            Some(TransparentAddress::PublicKey(_)) => {
+                self.external_transparent_input
+                    .write()
+                    .unwrap()
+                    .push((pubkey, utxo, coin));
+                Ok(())
+            }
+            _ => Err("Only P2PKH coins can be spent with an external signer".into()),
+        }
+    }
+
+    /// Adds a Sapling note whose spend authorizing key is held by a [`ZcashExternalSigner`].
+    ///
+    /// `proof_generation_key` must be the one of the scope the note was received with.
+    pub fn add_sapling_spend_for_signer(
+        &self,
+        proof_generation_key: Arc<ZcashProofGenerationKey>,
+        note: Arc<ZcashSaplingNote>,
+        merkle_path: Arc<ZcashSaplingMerklePath>,
+    ) {
+        self.external_sapling_spends.write().unwrap().push((
+            proof_generation_key,
+            note,
+            merkle_path,
+        ))
+    }
+
+    /// Adds an Orchard note whose spend authorizing key is held by a [`ZcashExternalSigner`].
+    pub fn add_orchard_spend_for_signer(
+        &self,
+        fvk: Arc<ZcashOrchardFullViewingKey>,
+        note: Arc<ZcashOrchardNote>,
+        merkle_path: Arc<ZcashOrchardMerklePath>,
+    ) {
+        self.external_orchard_spends
+            .write()
+            .unwrap()
+            .push((fvk, note, merkle_path))
+    }
+
+    /// Adds an Orchard address to send funds to. Orchard outputs can only be built
+    /// with [`Self::build_with_signer`].
+    pub fn add_orchard_output(
+        &self,
+        ovk: Option<Arc<ZcashOrchardOutgoingViewingKey>>,
+        to: Arc<ZcashOrchardAddress>,
+        value: Arc<ZcashOrchardNoteValue>,
+        memo: Arc<ZcashMemoBytes>,
+    ) {
+        self.orchard_outputs.write().unwrap().push((
+            ovk,
+            to,
+            value,
+            Some(*MemoBytes::from(memo.as_ref()).as_array()),
+        ))
+    }
+
+    /// Builds a v5 transaction, asking `signer` for the signatures of the spends added
+    /// through [`Self::add_transparent_input_for_signer`], [`Self::add_sapling_spend_for_signer`]
+    /// and [`Self::add_orchard_spend_for_signer`]. The inputs and Sapling spends added with
+    /// their spending keys are signed by the builder.
+    ///
+    /// Without `change`, the inputs must exactly cover the outputs plus the fee.
+    /// Otherwise, the value left is sent to `change`. `prover` is only needed for
+    /// Sapling spends and outputs.
+    pub fn build_with_signer(
+        &self,
+        signer: Box<dyn ZcashExternalSigner>,
+        fee_rule: ZcashFeeRules,
+        change: Option<Arc<ZcashChangeAddress>>,
+        prover: Option<Arc<ZcashLocalTxProver>>,
+    ) -> ZcashResult<Arc<ZcashTransaction>> {
+        if !self.multisig_transparent_input.read().unwrap().is_empty() {
+            return Err("Multisig inputs must be built with build_multisig()".into());
+        }
+
+        let secp = Secp256k1::new();
+
+        // The inputs signed by the builder come first, then the ones of the signer.
+        let mut transparent_keys = Vec::new();
+        let mut transparent_inputs = Vec::new();
+        for (sk, utxo, coin) in self.transparent_input.read().unwrap().iter() {
+            let sk = SecretKey::from(sk.as_ref());
+            let pubkey = PublicKey::from_secret_key(&secp, &sk);
+            let coin = TxOut::from(coin.as_ref());
+            if SecpPublicKey::from(pubkey).p2pkh_address(true).script() != coin.script_pubkey {
+                return Err("A transparent input is not received by the address of its key".into());
+            }
+            transparent_keys.push((pubkey, Some(sk)));
+            transparent_inputs.push(((*utxo.as_ref()).clone().into(), coin));
+        }
+        for (pubkey, utxo, coin) in self.external_transparent_input.read().unwrap().iter() {
+            transparent_keys.push((*pubkey, None));
+            transparent_inputs.push(((*utxo.as_ref()).clone().into(), coin.as_ref().into()));
+        }
+
+        let mut sapling_asks = Vec::new();
+        let mut sapling_spends = Vec::new();
+        for (extsk, _, note, merkle_path) in self.sapling_spends.read().unwrap().iter() {
+            let extsk = ExtendedSpendingKey::from((*extsk.as_ref()).clone());
+            sapling_asks.push(extsk.expsk.ask);
+            sapling_spends.push(SaplingSpend {
+                proof_generation_key: extsk.expsk.proof_generation_key(),
+                note: note.as_ref().into(),
+                merkle_path: merkle_path.as_ref().into(),
+            });
+        }
+        let local_sapling_spends = sapling_spends.len();
+        for (pgk, note, merkle_path) in self.external_sapling_spends.read().unwrap().iter() {
+            sapling_spends.push(SaplingSpend {
+                proof_generation_key: pgk.as_ref().into(),
+                note: note.as_ref().into(),
+                merkle_path: merkle_path.as_ref().into(),
+            });
+        }
+
+        let sapling_outputs = self
+            .sapling_outputs
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(ovk, to, value, memo)| {
+                Ok(SaplingOutput {
+                    ovk: ovk.as_ref().map(|ovk| ovk.as_ref().into()),
+                    to: to.as_ref().into(),
+                    value: super::partial::note_value(value.as_ref().into())?,
+                    memo: memo.as_ref().into(),
+                })
+            })
+            .collect::<ZcashResult<Vec<_>>>()?;
+
+        let mut unproven = UnprovenTransaction {
+            transparent_inputs,
+            transparent_outputs: self.transparent_outputs(),
+            sapling_spends,
+            sapling_outputs,
+            orchard_spends: orchard_spends(&self.external_orchard_spends),
+            orchard_outputs: orchard_outputs(&self.orchard_outputs),
+            ..Default::default()
+        };
+
+        if let Some(change) = change {
+            unproven.add_change(&fee_rule, &change.0)?;
+        }
+        unproven.check_balance(unproven.fee(&fee_rule)?)?;
+
+        let proven = unproven.prove(
+            &self.parameters,
+            (*self.target_height).into(),
+            prover.as_ref().map(|prover| &prover.internal),
+        )?;
+        let sighash = proven.shielded_sighash()?;
+
+        let script_sigs = transparent_keys
+            .into_iter()
+            .enumerate()
+            .map(|(index, (pubkey, sk))| -> ZcashResult<Script> {
+                let sighash = proven.transparent_sighash(index)?;
+                let message = Message::from_slice(&sighash)?;
+
+                let signature = match sk {
+                    Some(sk) => secp.sign_ecdsa(&message, &sk),
+                    None => {
+                        let signature = signer
+                            .sign_transparent(sighash.to_vec(), index.try_into()?)
+                            .ok_or("The external signer declined to sign")?;
+                        let mut signature = Signature::from_der(signature.as_slice())?;
+                        signature.normalize_s();
+
+                        secp.verify_ecdsa(&message, &signature, &pubkey)?;
+                        signature
+                    }
+                };
+
+                Ok(transparent_signing::p2pkh_script_sig(&signature, &pubkey))
+            })
+            .collect::<ZcashResult<Vec<_>>>()?;
+
+        let sapling_sigs = proven
+            .sapling_alphas()
+            .iter()
+            .map(|(index, alpha)| -> ZcashResult<redjubjub::Signature> {
+                match sapling_asks.get(*index) {
+                    Some(ask) => Ok(spend_sig(PrivateKey(*ask), *alpha, &sighash, &mut OsRng)),
+                    None => {
+                        let signature = signer
+                            .sign_sapling(
+                                sighash.to_vec(),
+                                alpha.to_bytes().to_vec(),
+                                (index - local_sapling_spends).try_into()?,
+                            )
+                            .ok_or("The external signer declined to sign")?;
+                        Ok(redjubjub::Signature::read(signature.as_slice())?)
+                    }
+                }
+            })
+            .collect::<ZcashResult<Vec<_>>>()?;
+
+        let orchard_sigs = sign_orchard(&proven, signer.as_ref(), &sighash)?;
+
+        let tx = proven.authorize(script_sigs, sapling_sigs, orchard_sigs)?;
+        Ok(Arc::new(tx.into()))
+    }
+}
+
+impl ZcashOrchardTransactionBuilder {
+    /// Builds a v5 transaction with only an Orchard bundle, asking `signer` for the
+    /// spend authorization signature of each spend.
+    ///
+    /// The spends must have the anchor of the builder, and both spends and outputs
+    /// must be enabled by its flags. The value balance of the bundle is the value of
+    /// the spends minus the value of the outputs.
+    pub fn build_with_signer(
+        &self,
+        signer: Box<dyn ZcashExternalSigner>,
+    ) -> ZcashResult<Arc<ZcashTransaction>> {
+        let flags = Flags::from(self.flags.as_ref());
+        if !flags.spends_enabled() || !flags.outputs_enabled() {
+            return Err("Spends and outputs must be enabled to build with a signer".into());
+        }
+
+        let unproven = UnprovenTransaction {
+            orchard_spends: orchard_spends(&self.spends),
+            orchard_outputs: orchard_outputs(&self.outputs),
+            orchard_anchor: Some(self.anchor.as_ref().into()),
+            expiry_height: Some(self.expiry_height.as_ref().into()),
+            ..Default::default()
+        };
+        let proven = unproven.prove(
+            &self.parameters,
+            self.target_height.as_ref().into(),
+            None::<&LocalTxProver>,
+        )?;
+        let sighash = proven.shielded_sighash()?;
+
+        let orchard_sigs = sign_orchard(&proven, signer.as_ref(), &sighash)?;
+
+        let tx = proven.authorize(Vec::new(), Vec::new(), orchard_sigs)?;
+        Ok(Arc::new(tx.into()))
+    }
+}
+
+fn orchard_spends(spends: &OrchardSpends) -> Vec<OrchardSpend> {
+    spends
+        .read()
+        .unwrap()
+        .iter()
+        .map(|(fvk, note, merkle_path)| OrchardSpend {
+            fvk: fvk.as_ref().into(),
+            note: note.as_ref().into(),
+            merkle_path: merkle_path.as_ref().into(),
+        })
+        .collect()
+}
+
+fn orchard_outputs(outputs: &OrchardOutputs) -> Vec<OrchardOutput> {
+    outputs
+        .read()
+        .unwrap()
+        .iter()
+        .map(|(ovk, to, value, memo)| OrchardOutput {
+            ovk: ovk.as_ref().map(|ovk| ovk.as_ref().into()),
+            to: to.0,
+            value: (**value).into(),
+            memo: memo.unwrap_or_else(|| *MemoBytes::empty().as_array()),
+        })
+        .collect()
+}
+
+/// Asks `signer` for the spend authorization signature of each Orchard spend.
+fn sign_orchard(
+    proven: &UnauthorizedTransaction,
+    signer: &dyn ZcashExternalSigner,
+    sighash: &[u8; 32],
+) -> ZcashResult<Vec<redpallas::Signature<redpallas::SpendAuth>>> {
+    proven
+        .orchard_alphas()
+        .iter()
+        .flatten()
+        .map(|(index, alpha)| {
+            let signature = signer
+                .sign_orchard(
+                    sighash.to_vec(),
+                    alpha.to_repr().to_vec(),
+                    (*index).try_into()?,
+                )
+                .ok_or("The external signer declined to sign")?;
+            let signature: [u8; 64] = signature
+                .try_into()
+                .or(Err("An Orchard signature must be 64 bytes"))?;
+            Ok(signature.into())
+        })
+        .collect()
+}
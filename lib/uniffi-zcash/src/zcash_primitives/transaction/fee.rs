@@ -0,0 +1,85 @@
+use std::sync::Arc;
+
+use zcash_primitives::transaction::{
+    components::{amount::BalanceError, Amount},
+    fees::zip317::FeeRule,
+};
+
+use super::transparent_signing;
+use crate::{
+    ZcashAmount, ZcashError, ZcashOutPoint, ZcashResult, ZcashTransaction, ZcashZip317FeeRule,
+};
+
+/// Looks up the value of the transparent outputs spent by a transaction, for instance from
+/// a wallet database or a block explorer.
+pub trait ZcashPrevoutLookup: Send + Sync {
+    /// Returns the value in zatoshis of the output at `outpoint`, or `None` if it is unknown.
+    fn prevout_value(&self, outpoint: Arc<ZcashOutPoint>) -> Option<u64>;
+}
+
+impl ZcashTransaction {
+    /// Returns the total fees paid by the transaction, looking up the value of the
+    /// transparent outputs it spends with `lookup`.
+    pub fn fee_paid(&self, lookup: Box<dyn ZcashPrevoutLookup>) -> ZcashResult<Arc<ZcashAmount>> {
+        let fee = self.0.fee_paid(|outpoint| {
+            let value = lookup
+                .prevout_value(Arc::new(outpoint.clone().into()))
+                .ok_or_else(|| ZcashError::from("Prevout value not found"))?;
+            Amount::from_u64(value).map_err(|_| ZcashError::from(BalanceError::Overflow))
+        })?;
+
+        Ok(Arc::new(fee.into()))
+    }
+
+    /// Returns the number of ZIP 317 logical actions of the transaction.
+    pub fn zip317_logical_actions(&self, fee_rule: Arc<ZcashZip317FeeRule>) -> u64 {
+        let fee_rule = &FeeRule::from(fee_rule.as_ref().clone());
+
+        let transparent = self.0.transparent_bundle().map_or(0, |bundle| {
+            transparent_signing::transparent_logical_actions(
+                fee_rule,
+                bundle
+                    .vin
+                    .iter()
+                    .map(|txin| transparent_signing::input_size(txin.script_sig.0.len()))
+                    .sum(),
+                bundle
+                    .vout
+                    .iter()
+                    .map(transparent_signing::output_size)
+                    .sum(),
+            )
+        });
+        let sprout = self
+            .0
+            .sprout_bundle()
+            .map_or(0, |bundle| 2 * bundle.joinsplits.len());
+        let sapling = self.0.sapling_bundle().map_or(0, |bundle| {
+            bundle
+                .shielded_spends()
+                .len()
+                .max(bundle.shielded_outputs().len())
+        });
+        let orchard = self
+            .0
+            .orchard_bundle()
+            .map_or(0, |bundle| bundle.actions().len());
+
+        (transparent + sprout + sapling + orchard) as u64
+    }
+
+    /// Returns the ZIP 317 conventional fee of the transaction, which can be compared to
+    /// [`Self::fee_paid`] to check whether the transaction pays it.
+    pub fn zip317_conventional_fee(
+        &self,
+        fee_rule: Arc<ZcashZip317FeeRule>,
+    ) -> ZcashResult<Arc<ZcashAmount>> {
+        let logical_actions = self.zip317_logical_actions(fee_rule.clone()).try_into()?;
+        let fee = transparent_signing::conventional_fee(
+            &FeeRule::from(fee_rule.as_ref().clone()),
+            logical_actions,
+        )?;
+
+        Ok(Arc::new(fee.into()))
+    }
+}
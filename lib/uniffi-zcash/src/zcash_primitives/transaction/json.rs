@@ -0,0 +1,356 @@
+use std::{collections::HashMap, sync::Arc};
+
+use orchard::keys::Scope as OrchardScope;
+use serde_json::{json, Map, Value};
+use zcash_client_backend::{
+    address::UnifiedAddress, decrypt_transaction, encoding, keys::UnifiedFullViewingKey,
+    TransferType,
+};
+use zcash_primitives::{
+    consensus::{BlockHeight, BranchId},
+    legacy::Script,
+    memo::{Memo, MemoBytes},
+    transaction::{components::Amount, TxId},
+    zip32::AccountId,
+};
+
+use crate::{
+    script_kind, ZcashConsensusParameters, ZcashResult, ZcashScript, ZcashScriptKind,
+    ZcashTransaction, ZcashUnifiedFullViewingKey,
+};
+
+impl ZcashTransaction {
+    /// Decodes the transaction to JSON, in a layout similar to zcashd's `decoderawtransaction`.
+    ///
+    /// Hashes are displayed in reversed byte order, as block explorers do; every other field is
+    /// the hex encoding of its serialized bytes. Shielded outputs that can be decrypted by any of
+    /// `ufvks` are annotated with the index of the key, and the value, recipient and memo of
+    /// their note.
+    pub fn to_json(
+        &self,
+        params: ZcashConsensusParameters,
+        ufvks: Vec<Arc<ZcashUnifiedFullViewingKey>>,
+    ) -> ZcashResult<String> {
+        let tx = &self.0;
+        let ufvks: HashMap<AccountId, UnifiedFullViewingKey> = ufvks
+            .into_iter()
+            .enumerate()
+            .map(|(index, ufvk)| (AccountId::from(index as u32), (*ufvk).clone().into()))
+            .collect();
+
+        let mut json = Map::new();
+        json.insert("txid".into(), json!(tx.txid().to_string()));
+        json.insert(
+            "authdigest".into(),
+            json!(TxId::from_bytes(cast_hash(tx.auth_commitment().as_bytes())).to_string()),
+        );
+        json.insert("size".into(), json!(self.to_bytes()?.len()));
+        json.insert("overwintered".into(), json!(tx.version().has_overwinter()));
+        json.insert("version".into(), json!(tx.version().header() & 0x7FFFFFFF));
+        json.insert(
+            "versiongroupid".into(),
+            json!(format!("{:08x}", tx.version().version_group_id())),
+        );
+        json.insert(
+            "consensusbranchid".into(),
+            json!(format!("{:08x}", u32::from(tx.consensus_branch_id()))),
+        );
+        json.insert("locktime".into(), json!(tx.lock_time()));
+        json.insert("expiryheight".into(), json!(u32::from(tx.expiry_height())));
+
+        let (vin, vout) = tx.transparent_bundle().map_or((vec![], vec![]), |bundle| {
+            let vin = bundle
+                .vin
+                .iter()
+                .map(|txin| {
+                    json!({
+                        "txid": TxId::from_bytes(*txin.prevout.hash()).to_string(),
+                        "vout": txin.prevout.n(),
+                        "scriptSig": script_fields(
+                            &txin.script_sig,
+                            ZcashScript::from(txin.script_sig.clone()).disassemble_script_sig(),
+                        ),
+                        "sequence": txin.sequence,
+                    })
+                })
+                .collect();
+            let vout = bundle
+                .vout
+                .iter()
+                .enumerate()
+                .map(|(n, txout)| {
+                    let mut script_pubkey = script_fields(
+                        &txout.script_pubkey,
+                        ZcashScript::from(txout.script_pubkey.clone()).disassemble(),
+                    );
+                    script_pubkey["type"] = json!(script_type(&txout.script_pubkey));
+                    script_pubkey["addresses"] = json!(txout
+                        .recipient_address()
+                        .map(|address| encoding::encode_transparent_address_p(&params, &address))
+                        .into_iter()
+                        .collect::<Vec<_>>());
+
+                    let mut output = value_fields("value", txout.value);
+                    output.insert("n".into(), json!(n));
+                    output.insert("scriptPubKey".into(), script_pubkey);
+                    Value::Object(output)
+                })
+                .collect();
+            (vin, vout)
+        });
+        json.insert("vin".into(), Value::Array(vin));
+        json.insert("vout".into(), Value::Array(vout));
+
+        if let Some(bundle) = tx.sapling_bundle() {
+            let height = decryption_height(&params, tx.consensus_branch_id());
+            let mut decrypted: HashMap<usize, Value> =
+                decrypt_transaction(&params, height, tx, &ufvks)
+                    .into_iter()
+                    .map(|output| {
+                        let address =
+                            encoding::encode_payment_address_p(&params, &output.note.recipient());
+                        (
+                            output.index,
+                            note_fields(
+                                output.account,
+                                output.transfer_type,
+                                output.note.value().inner(),
+                                address,
+                                &output.memo,
+                            ),
+                        )
+                    })
+                    .collect();
+
+            json.extend(value_fields("valueBalance", *bundle.value_balance()));
+            json.insert(
+                "vShieldedSpend".into(),
+                bundle
+                    .shielded_spends()
+                    .iter()
+                    .map(|spend| {
+                        let mut rk = vec![];
+                        spend.rk().write(&mut rk)?;
+                        let mut spend_auth_sig = vec![];
+                        spend.spend_auth_sig().write(&mut spend_auth_sig)?;
+                        Ok(json!({
+                            "cv": hex::encode(spend.cv().to_bytes()),
+                            "anchor": hex::encode(spend.anchor().to_bytes()),
+                            "nullifier": hex::encode(spend.nullifier().0),
+                            "rk": hex::encode(rk),
+                            "proof": hex::encode(spend.zkproof()),
+                            "spendAuthSig": hex::encode(spend_auth_sig),
+                        }))
+                    })
+                    .collect::<ZcashResult<Vec<_>>>()?
+                    .into(),
+            );
+            json.insert(
+                "vShieldedOutput".into(),
+                bundle
+                    .shielded_outputs()
+                    .iter()
+                    .enumerate()
+                    .map(|(index, output)| {
+                        let mut fields = json!({
+                            "cv": hex::encode(output.cv().to_bytes()),
+                            "cmu": hex::encode(output.cmu().to_bytes()),
+                            "ephemeralKey": hex::encode(output.ephemeral_key().0),
+                            "encCiphertext": hex::encode(output.enc_ciphertext()),
+                            "outCiphertext": hex::encode(output.out_ciphertext()),
+                            "proof": hex::encode(output.zkproof()),
+                        });
+                        if let Some(note) = decrypted.remove(&index) {
+                            fields["decrypted"] = note;
+                        }
+                        fields
+                    })
+                    .collect::<Vec<_>>()
+                    .into(),
+            );
+            let mut binding_sig = vec![];
+            bundle.authorization().binding_sig.write(&mut binding_sig)?;
+            json.insert("bindingSig".into(), json!(hex::encode(binding_sig)));
+        }
+
+        if let Some(bundle) = tx.orchard_bundle() {
+            let mut decrypted: HashMap<usize, Value> = HashMap::new();
+            for (account, ufvk) in ufvks.iter() {
+                let Some(fvk) = ufvk.orchard() else {
+                    continue;
+                };
+                let scopes = [
+                    (OrchardScope::External, TransferType::Incoming),
+                    (OrchardScope::Internal, TransferType::WalletInternal),
+                ];
+                for (scope, transfer_type) in scopes {
+                    for (index, _, note, address, memo) in
+                        bundle.decrypt_outputs_with_keys(&[fvk.to_ivk(scope)])
+                    {
+                        decrypted.entry(index).or_insert_with(|| {
+                            orchard_note_fields(
+                                &params,
+                                *account,
+                                transfer_type,
+                                &note,
+                                address,
+                                memo,
+                            )
+                        });
+                    }
+                }
+                // Like decrypt_transaction does for Sapling, outgoing notes are recovered with
+                // the OVKs of both scopes, as change and shielding transactions use the
+                // internal one.
+                let ovks = [
+                    fvk.to_ovk(OrchardScope::External),
+                    fvk.to_ovk(OrchardScope::Internal),
+                ];
+                for (index, _, note, address, memo) in bundle.recover_outputs_with_ovks(&ovks) {
+                    decrypted.entry(index).or_insert_with(|| {
+                        orchard_note_fields(
+                            &params,
+                            *account,
+                            TransferType::Outgoing,
+                            &note,
+                            address,
+                            memo,
+                        )
+                    });
+                }
+            }
+
+            let mut orchard = value_fields("valueBalance", *bundle.value_balance());
+            orchard.insert(
+                "actions".into(),
+                bundle
+                    .actions()
+                    .iter()
+                    .enumerate()
+                    .map(|(index, action)| {
+                        let mut fields = json!({
+                            "cv": hex::encode(action.cv_net().to_bytes()),
+                            "nullifier": hex::encode(action.nullifier().to_bytes()),
+                            "rk": hex::encode(<[u8; 32]>::from(action.rk())),
+                            "cmx": hex::encode(action.cmx().to_bytes()),
+                            "ephemeralKey": hex::encode(action.encrypted_note().epk_bytes),
+                            "encCiphertext": hex::encode(action.encrypted_note().enc_ciphertext),
+                            "outCiphertext": hex::encode(action.encrypted_note().out_ciphertext),
+                            "spendAuthSig": hex::encode(<[u8; 64]>::from(action.authorization())),
+                        });
+                        if let Some(note) = decrypted.remove(&index) {
+                            fields["decrypted"] = note;
+                        }
+                        fields
+                    })
+                    .collect::<Vec<_>>()
+                    .into(),
+            );
+            orchard.insert(
+                "flags".into(),
+                json!({
+                    "enableSpends": bundle.flags().spends_enabled(),
+                    "enableOutputs": bundle.flags().outputs_enabled(),
+                }),
+            );
+            orchard.insert(
+                "anchor".into(),
+                json!(hex::encode(bundle.anchor().to_bytes())),
+            );
+            orchard.insert(
+                "proof".into(),
+                json!(hex::encode(bundle.authorization().proof().as_ref())),
+            );
+            orchard.insert(
+                "bindingSig".into(),
+                json!(hex::encode(<[u8; 64]>::from(
+                    bundle.authorization().binding_signature()
+                ))),
+            );
+            json.insert("orchard".into(), Value::Object(orchard));
+        }
+
+        Ok(serde_json::to_string_pretty(&json).expect("JSON maps can always be serialized"))
+    }
+}
+
+fn cast_hash(bytes: &[u8]) -> [u8; 32] {
+    bytes.try_into().expect("BLAKE2b digests are 32 bytes")
+}
+
+/// Shielded outputs are decrypted as of the activation of the transaction's consensus branch,
+/// which determines whether ZIP 212 note plaintexts are accepted.
+fn decryption_height(params: &ZcashConsensusParameters, branch_id: BranchId) -> BlockHeight {
+    branch_id
+        .height_bounds(params)
+        .map_or(BlockHeight::from_u32(0), |(start, _)| start)
+}
+
+fn script_fields(script: &Script, asm: String) -> Value {
+    json!({
+        "asm": asm,
+        "hex": hex::encode(&script.0),
+    })
+}
+
+/// Returns the script type names used by zcashd.
+fn script_type(script: &Script) -> &'static str {
+    match script_kind(script) {
+        ZcashScriptKind::PubkeyHash => "pubkeyhash",
+        ZcashScriptKind::ScriptHash => "scripthash",
+        ZcashScriptKind::Multisig => "multisig",
+        ZcashScriptKind::NullData => "nulldata",
+        ZcashScriptKind::Unknown => "nonstandard",
+    }
+}
+
+fn value_fields(name: &str, value: Amount) -> Map<String, Value> {
+    let zat = i64::from(value);
+    let mut fields = Map::new();
+    fields.insert(name.into(), json!(zat as f64 / 100_000_000.0));
+    fields.insert(format!("{}Zat", name), json!(zat));
+    fields
+}
+
+fn note_fields(
+    account: AccountId,
+    transfer_type: TransferType,
+    value: u64,
+    address: String,
+    memo: &MemoBytes,
+) -> Value {
+    let memo = match Memo::try_from(memo.clone()) {
+        Ok(Memo::Empty) => Value::Null,
+        Ok(Memo::Text(text)) => json!(text.to_string()),
+        _ => json!(hex::encode(memo.as_slice())),
+    };
+    let transfer_type = match transfer_type {
+        TransferType::Incoming => "incoming",
+        TransferType::WalletInternal => "wallet_internal",
+        TransferType::Outgoing => "outgoing",
+    };
+
+    json!({
+        "key": u32::from(account),
+        "transferType": transfer_type,
+        "valueZat": value,
+        "address": address,
+        "memo": memo,
+    })
+}
+
+fn orchard_note_fields(
+    params: &ZcashConsensusParameters,
+    account: AccountId,
+    transfer_type: TransferType,
+    note: &orchard::Note,
+    address: orchard::Address,
+    memo: [u8; 512],
+) -> Value {
+    let address = UnifiedAddress::from_receivers(Some(address), None, None)
+        .expect("an Orchard receiver is a valid unified address")
+        .encode(params);
+    let memo = MemoBytes::from_bytes(&memo).expect("memos are 512 bytes");
+
+    note_fields(account, transfer_type, note.value().inner(), address, &memo)
+}
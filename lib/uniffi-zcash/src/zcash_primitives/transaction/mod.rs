@@ -1,9 +1,33 @@
 mod components;
 pub use self::components::*;
 
+mod external_signer;
+pub use self::external_signer::*;
+
+mod fee;
+pub use self::fee::*;
+
 mod fees;
 pub use self::fees::*;
 
+mod json;
+
+mod multisig;
+pub use self::multisig::*;
+
+mod partial;
+pub use self::partial::*;
+
+mod sighash;
+pub use self::sighash::*;
+
+mod transparent_signing;
+
+mod unauthorized;
+
+mod verify;
+pub use self::verify::*;
+
 use std::sync::{Arc, RwLock};
 
 use derive_more::{From, Into};
@@ -31,8 +55,8 @@
     ZcashConsensusParameters, ZcashDiversifier, ZcashError, ZcashExtendedSpendingKey,
     ZcashLocalTxProver, ZcashMemoBytes, ZcashOrchardAddress, ZcashOrchardFullViewingKey,
     ZcashOrchardMerklePath, ZcashOrchardNote, ZcashOrchardOutgoingViewingKey,
-    ZcashOrchardSpendingKey, ZcashOutgoingViewingKey, ZcashPaymentAddress, ZcashResult,
-    ZcashSaplingMerklePath, ZcashSaplingNote, ZcashTransparentAddress,
+    ZcashOrchardSpendingKey, ZcashOutgoingViewingKey, ZcashPaymentAddress, ZcashProofGenerationKey,
+    ZcashResult, ZcashSaplingMerklePath, ZcashSaplingNote, ZcashScript, ZcashTransparentAddress,
 };
 
 pub use self::components::*;
@@ -43,7 +67,12 @@ pub struct ZcashTransactionBuilder {
     sapling_spends: SaplingSpends,
     sapling_outputs: SaplingOutputs,
     transparent_input: TransparentInput,
+    external_transparent_input: ExternalTransparentInput,
+    multisig_transparent_input: MultisigTransparentInput,
     transparent_output: TransparentOutput,
+    external_sapling_spends: ExternalSaplingSpends,
+    external_orchard_spends: OrchardSpends,
+    orchard_outputs: OrchardOutputs,
 }
 
 impl ZcashTransactionBuilder {
@@ -54,7 +83,12 @@ pub fn new(parameters: ZcashConsensusParameters, target_height: Arc<ZcashBlockHe
             sapling_spends: RwLock::new(Vec::new()),
             sapling_outputs: RwLock::new(Vec::new()),
             transparent_input: RwLock::new(Vec::new()),
+            external_transparent_input: RwLock::new(Vec::new()),
+            multisig_transparent_input: RwLock::new(Vec::new()),
             transparent_output: RwLock::new(Vec::new()),
+            external_sapling_spends: RwLock::new(Vec::new()),
+            external_orchard_spends: RwLock::new(Vec::new()),
+            orchard_outputs: RwLock::new(Vec::new()),
         }
     }
 
@@ -109,6 +143,23 @@ pub fn build(
         prover: Arc<ZcashLocalTxProver>,
         fee_rule: ZcashFeeRules,
     ) -> ZcashResult<ZcashTransactionAndSaplingMetadata> {
+        if !self.external_transparent_input.read().unwrap().is_empty()
+            || !self.external_sapling_spends.read().unwrap().is_empty()
+            || !self.external_orchard_spends.read().unwrap().is_empty()
+        {
+            return Err(
+                "Spends for an external signer must be built with build_with_signer()".into(),
+            );
+        }
+
+        if !self.orchard_outputs.read().unwrap().is_empty() {
+            return Err("Orchard outputs must be built with build_with_signer()".into());
+        }
+
+        if !self.multisig_transparent_input.read().unwrap().is_empty() {
+            return Err("Multisig inputs must be built with build_multisig()".into());
+        }
+
         // CHANGED
         let mut builder = Builder::new(self.parameters, (*self.target_height).into(), None);
 
@@ -205,6 +256,20 @@ pub fn build(
 
 type TransparentInput = RwLock<Vec<(Arc<SecpSecretKey>, Arc<ZcashOutPoint>, Arc<ZcashTxOut>)>>;
 
+type ExternalTransparentInput =
+    RwLock<Vec<(secp256k1::PublicKey, Arc<ZcashOutPoint>, Arc<ZcashTxOut>)>>;
+
+type ExternalSaplingSpends = RwLock<
+    Vec<(
+        Arc<ZcashProofGenerationKey>,
+        Arc<ZcashSaplingNote>,
+        Arc<ZcashSaplingMerklePath>,
+    )>,
+>;
+
+type MultisigTransparentInput =
+    RwLock<Vec<(Arc<ZcashScript>, Arc<ZcashOutPoint>, Arc<ZcashTxOut>)>>;
+
 type TransparentOutput = RwLock<Vec<(Arc<ZcashTransparentAddress>, Arc<ZcashAmount>)>>;
 
 type SaplingSpends = RwLock<
@@ -226,6 +291,7 @@ pub fn build(
 >;
 
 /// A selector for the desired fee rules for applying to a transaction.
+#[derive(Clone)]
 pub enum ZcashFeeRules {
     FixedStandard,
     FixedNonStandard {
@@ -287,15 +353,6 @@ pub fn expiry_height(&self) -> Arc<ZcashBlockHeight> {
         Arc::new(self.0.expiry_height().into())
     }
 
-    /// Returns the total fees paid by the transaction, given a function that can be used to
-    /// retrieve the value of previous transactions' transparent outputs that are being spent in
-    /// this transaction.
-    // pub fn fee_paid(&self) -> ZcashResult<Arc<ZcashAmount>> {
-    //     let amount = self.0.fee_paid::<BalanceError, _>(|_| Ok(Amount::zero()))?;
-    //     Ok(Arc::new(amount.into()))
-    // }
-    // TODO: investigate alternative ways of exposing this to FFI, as it accepts a closure as parameter.
-
     pub fn transparent_bundle(&self) -> Option<Arc<ZcashTransparentBundle>> {
         self.0.transparent_bundle().map(From::from).map(Arc::new)
     }
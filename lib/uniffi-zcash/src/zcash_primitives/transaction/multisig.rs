@@ -0,0 +1,379 @@
+use std::io::Write;
+use std::sync::{Arc, RwLock};
+
+use secp256k1::{ecdsa::Signature, Message, PublicKey, Secp256k1, SecretKey};
+use zcash_primitives::{
+    legacy::Script,
+    transaction::{components::TxOut, sighash::SIGHASH_ALL},
+};
+
+use super::partial::{read_array, read_u32, read_u8, write_u32, write_u8};
+use super::transparent_signing::{self, UnsignedTransparentTransaction};
+use crate::{
+    parse_multisig_script, SecpSecretKey, ZcashError, ZcashFeeRules, ZcashOutPoint, ZcashResult,
+    ZcashScript, ZcashTransaction, ZcashTransactionBuilder, ZcashTransparentAddress, ZcashTxOut,
+};
+
+/// Leading bytes of a serialized [`ZcashMultisigTransaction`].
+const MULTISIG_TX_MAGIC: [u8; 4] = *b"ZMSX";
+
+/// Version of the [`ZcashMultisigTransaction`] serialization format.
+const MULTISIG_TX_VERSION: u32 = 1;
+
+/// Maximum size of a DER-encoded ECDSA signature.
+const MAX_DER_SIGNATURE_SIZE: usize = 72;
+
+impl ZcashTransactionBuilder {
+    /// Adds an input spending a P2SH coin locked by the multisig `redeem_script`,
+    /// created with [`ZcashScript::multisig`].
+    pub fn add_transparent_multisig_input(
+        &self,
+        redeem_script: Arc<ZcashScript>,
+        utxo: Arc<ZcashOutPoint>,
+        coin: Arc<ZcashTxOut>,
+    ) -> ZcashResult<()> {
+        if parse_multisig_script(&redeem_script.as_ref().into()).is_none() {
+            return Err("The redeem script is not a multisig script".into());
+        }
+
+        let address = ZcashTransparentAddress::from_redeem_script(redeem_script.clone());
+        if TxOut::from(coin.as_ref()).recipient_address() != Some(address.into()) {
+            return Err("The coin is not sent to the P2SH address of the redeem script".into());
+        }
+
+        self.multisig_transparent_input
+            .write()
+            .unwrap()
+            .push((redeem_script, utxo, coin));
+        Ok(())
+    }
+
+    /// Creates a transaction spending the inputs added through
+    /// [`Self::add_transparent_multisig_input`], to be signed by the cosigners.
+    ///
+    /// The inputs must exactly cover the outputs plus the fee, as no change output is added.
+    pub fn build_multisig(
+        &self,
+        fee_rule: ZcashFeeRules,
+    ) -> ZcashResult<Arc<ZcashMultisigTransaction>> {
+        if !self.sapling_spends.read().unwrap().is_empty()
+            || !self.sapling_outputs.read().unwrap().is_empty()
+            || !self.orchard_outputs.read().unwrap().is_empty()
+        {
+            return Err(
+                "Shielded spends and outputs cannot be part of a multisig transaction".into(),
+            );
+        }
+
+        if !self.transparent_input.read().unwrap().is_empty()
+            || !self.external_transparent_input.read().unwrap().is_empty()
+            || !self.external_sapling_spends.read().unwrap().is_empty()
+            || !self.external_orchard_spends.read().unwrap().is_empty()
+        {
+            return Err("Only multisig inputs can be built with build_multisig()".into());
+        }
+
+        let (redeem_scripts, inputs): (Vec<_>, Vec<_>) = self
+            .multisig_transparent_input
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(redeem_script, utxo, coin)| {
+                let script = Script::from(redeem_script.as_ref());
+                // The redeem script was checked when the input was added.
+                let (threshold, pubkeys) = parse_multisig_script(&script).unwrap();
+
+                (
+                    MultisigInput {
+                        redeem_script: script,
+                        threshold,
+                        pubkeys,
+                    },
+                    ((*utxo.as_ref()).clone().into(), coin.as_ref().into()),
+                )
+            })
+            .unzip();
+
+        let unsigned = UnsignedTransparentTransaction::new(
+            &self.parameters,
+            (*self.target_height).into(),
+            inputs,
+            self.transparent_outputs(),
+        );
+
+        let fee = transparent_signing::transparent_fee(
+            &fee_rule,
+            redeem_scripts
+                .iter()
+                .map(|input| transparent_signing::input_size(input.script_sig_size()))
+                .sum(),
+            unsigned
+                .outputs()
+                .iter()
+                .map(transparent_signing::output_size)
+                .sum(),
+        )?;
+        unsigned.check_balance(fee)?;
+
+        Ok(Arc::new(ZcashMultisigTransaction::new(
+            unsigned,
+            redeem_scripts,
+        )))
+    }
+}
+
+/// A transparent transaction spending P2SH multisig coins, collecting the signatures
+/// of the cosigners until it can be finalized.
+///
+/// Each cosigner can sign its own copy, obtained with [`Self::to_bytes`] and
+/// [`Self::from_bytes`], the copies being merged afterwards with [`Self::combine`].
+pub struct ZcashMultisigTransaction {
+    unsigned: UnsignedTransparentTransaction,
+    inputs: Vec<MultisigInput>,
+    /// Signatures of each input, in the order of the public keys of its redeem script.
+    signatures: RwLock<Vec<Vec<Option<Signature>>>>,
+}
+
+impl ZcashMultisigTransaction {
+    fn new(unsigned: UnsignedTransparentTransaction, inputs: Vec<MultisigInput>) -> Self {
+        let signatures = inputs
+            .iter()
+            .map(|input| vec![None; input.pubkeys.len()])
+            .collect();
+
+        Self {
+            unsigned,
+            inputs,
+            signatures: RwLock::new(signatures),
+        }
+    }
+
+    /// Returns the signature hash the cosigners sign for the input at `input_index`.
+    pub fn sighash(&self, input_index: u32) -> ZcashResult<Vec<u8>> {
+        let (index, input) = self.input(input_index)?;
+        Ok(self.unsigned.sighash(index, &input.redeem_script)?.to_vec())
+    }
+
+    /// Signs the input at `input_index` with `sk`, whose public key must be part of
+    /// the input's redeem script.
+    pub fn sign(&self, input_index: u32, sk: Arc<SecpSecretKey>) -> ZcashResult<()> {
+        let secp = Secp256k1::signing_only();
+        let sk = SecretKey::from(sk.as_ref());
+        let pubkey = PublicKey::from_secret_key(&secp, &sk);
+
+        let message = Message::from_slice(&self.sighash(input_index)?)?;
+        let signature = secp.sign_ecdsa(&message, &sk);
+
+        self.insert_signature(input_index, &pubkey, signature)
+    }
+
+    /// Adds the DER-encoded signature of the input at `input_index` made by the holder
+    /// of `pubkey`, for instance on a hardware wallet.
+    pub fn add_signature(
+        &self,
+        input_index: u32,
+        pubkey: Vec<u8>,
+        signature: Vec<u8>,
+    ) -> ZcashResult<()> {
+        let pubkey = PublicKey::from_slice(pubkey.as_slice())?;
+        let mut signature = Signature::from_der(signature.as_slice())?;
+        signature.normalize_s();
+
+        let message = Message::from_slice(&self.sighash(input_index)?)?;
+        Secp256k1::verification_only().verify_ecdsa(&message, &signature, &pubkey)?;
+
+        self.insert_signature(input_index, &pubkey, signature)
+    }
+
+    /// Adds the signatures collected by `other`, a copy of the same transaction.
+    pub fn combine(&self, other: Arc<ZcashMultisigTransaction>) -> ZcashResult<()> {
+        if self.unsigned != other.unsigned || self.inputs != other.inputs {
+            return Err("The multisig transactions spend different inputs or outputs".into());
+        }
+
+        let theirs = other.signatures.read().unwrap().clone();
+        let mut ours = self.signatures.write().unwrap();
+
+        ours.iter_mut()
+            .flatten()
+            .zip(theirs.into_iter().flatten())
+            .for_each(|(ours, theirs)| {
+                if ours.is_none() {
+                    *ours = theirs;
+                }
+            });
+
+        Ok(())
+    }
+
+    /// Returns the number of signatures collected for the input at `input_index`.
+    pub fn signature_count(&self, input_index: u32) -> ZcashResult<u32> {
+        let (index, _) = self.input(input_index)?;
+        let count = self.signatures.read().unwrap()[index]
+            .iter()
+            .filter(|signature| signature.is_some())
+            .count();
+        Ok(count.try_into()?)
+    }
+
+    /// Returns `true` if every input has at least as many signatures as its threshold.
+    pub fn is_complete(&self) -> bool {
+        self.inputs
+            .iter()
+            .zip(self.signatures.read().unwrap().iter())
+            .all(|(input, signatures)| {
+                signatures.iter().filter(|s| s.is_some()).count() >= input.threshold
+            })
+    }
+
+    /// Creates the final transaction, once [`Self::is_complete`].
+    pub fn finalize(&self) -> ZcashResult<Arc<ZcashTransaction>> {
+        if !self.is_complete() {
+            return Err("Not enough signatures to finalize the multisig transaction".into());
+        }
+
+        let script_sigs = self
+            .inputs
+            .iter()
+            .zip(self.signatures.read().unwrap().iter())
+            .map(|(input, signatures)| {
+                // OP_0 works around the extra element popped by OP_CHECKMULTISIG.
+                let script = Script(vec![0x00]);
+                let script = signatures.iter().flatten().take(input.threshold).fold(
+                    script,
+                    |script, signature| {
+                        let mut sig_bytes = signature.serialize_der().to_vec();
+                        sig_bytes.push(SIGHASH_ALL);
+                        script << &sig_bytes[..]
+                    },
+                );
+                script << &input.redeem_script.0[..]
+            })
+            .collect();
+
+        Ok(Arc::new(self.unsigned.finalize(script_sigs)?.into()))
+    }
+
+    pub fn to_bytes(&self) -> ZcashResult<Vec<u8>> {
+        let mut w = Vec::new();
+
+        w.write_all(&MULTISIG_TX_MAGIC)?;
+        write_u32(&mut w, MULTISIG_TX_VERSION)?;
+        self.unsigned.write(&mut w)?;
+
+        let signatures = self.signatures.read().unwrap();
+        for (input, signatures) in self.inputs.iter().zip(signatures.iter()) {
+            input.redeem_script.write(&mut w)?;
+
+            for signature in signatures {
+                match signature {
+                    Some(signature) => {
+                        write_u8(&mut w, 1)?;
+                        w.write_all(&signature.serialize_compact())?;
+                    }
+                    None => write_u8(&mut w, 0)?,
+                }
+            }
+        }
+
+        Ok(w)
+    }
+
+    pub fn from_bytes(data: &[u8]) -> ZcashResult<Self> {
+        let mut r = data;
+
+        if read_array::<4>(&mut r)? != MULTISIG_TX_MAGIC {
+            return Err("Not a multisig transaction".into());
+        }
+
+        let version = read_u32(&mut r)?;
+        if version != MULTISIG_TX_VERSION {
+            return Err(format!("Unsupported multisig transaction version {}", version).into());
+        }
+
+        let unsigned = UnsignedTransparentTransaction::read(&mut r)?;
+
+        let (inputs, signatures) = unsigned
+            .inputs()
+            .iter()
+            .map(
+                |_| -> ZcashResult<(MultisigInput, Vec<Option<Signature>>)> {
+                    let redeem_script = Script::read(&mut r)?;
+                    let (threshold, pubkeys) = parse_multisig_script(&redeem_script)
+                        .ok_or("The redeem script is not a multisig script")?;
+
+                    let signatures = pubkeys
+                        .iter()
+                        .map(|_| match read_u8(&mut r)? {
+                            0 => Ok(None),
+                            1 => Ok(Some(Signature::from_compact(&read_array::<64>(&mut r)?)?)),
+                            _ => Err(ZcashError::from("Invalid signature flag")),
+                        })
+                        .collect::<ZcashResult<Vec<_>>>()?;
+
+                    Ok((
+                        MultisigInput {
+                            redeem_script,
+                            threshold,
+                            pubkeys,
+                        },
+                        signatures,
+                    ))
+                },
+            )
+            .collect::<ZcashResult<Vec<_>>>()?
+            .into_iter()
+            .unzip();
+
+        if !r.is_empty() {
+            return Err("Unexpected trailing data".into());
+        }
+
+        Ok(Self {
+            unsigned,
+            inputs,
+            signatures: RwLock::new(signatures),
+        })
+    }
+
+    fn input(&self, input_index: u32) -> ZcashResult<(usize, &MultisigInput)> {
+        let index = input_index as usize;
+        self.inputs
+            .get(index)
+            .map(|input| (index, input))
+            .ok_or_else(|| "Input index out of range".into())
+    }
+
+    fn insert_signature(
+        &self,
+        input_index: u32,
+        pubkey: &PublicKey,
+        signature: Signature,
+    ) -> ZcashResult<()> {
+        let (index, input) = self.input(input_index)?;
+        let key_index = input
+            .pubkeys
+            .iter()
+            .position(|key| key == pubkey)
+            .ok_or("The public key is not part of the redeem script")?;
+
+        self.signatures.write().unwrap()[index][key_index] = Some(signature);
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct MultisigInput {
+    redeem_script: Script,
+    threshold: usize,
+    pubkeys: Vec<PublicKey>,
+}
+
+impl MultisigInput {
+    /// Returns the size of the `scriptSig` of the input, with signatures of the
+    /// maximum DER size.
+    fn script_sig_size(&self) -> usize {
+        let redeem_script = Script::default() << &self.redeem_script.0[..];
+        1 + self.threshold * (2 + MAX_DER_SIGNATURE_SIZE) + redeem_script.0.len()
+    }
+}
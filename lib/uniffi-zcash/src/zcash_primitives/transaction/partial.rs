@@ -0,0 +1,966 @@
+use std::io::{Read, Write};
+use std::sync::{Arc, RwLock};
+
+use group::ff::PrimeField;
+use incrementalmerkletree::{MerklePath, Position};
+use orchard::{
+    keys::{FullViewingKey, SpendAuthorizingKey, SpendingKey},
+    note::{Nullifier, RandomSeed},
+    tree::MerkleHashOrchard,
+    Address,
+};
+use rand::rngs::OsRng;
+use secp256k1::{Message, PublicKey, Secp256k1, SecretKey};
+use zcash_primitives::consensus::{BlockHeight, BranchId};
+use zcash_primitives::legacy::{Script, TransparentAddress};
+use zcash_primitives::memo::MemoBytes;
+use zcash_primitives::merkle_tree::HashSer;
+use zcash_primitives::sapling::{
+    keys::OutgoingViewingKey, prover::TxProver, redjubjub::PrivateKey, spend_sig, value::NoteValue,
+    Node, Note, PaymentAddress, ProofGenerationKey, Rseed,
+};
+use zcash_primitives::transaction::components::{Amount, OutPoint, TxOut};
+use zcash_primitives::transaction::Transaction;
+use zcash_primitives::zip32::{sapling::DiversifiableFullViewingKey, ExtendedSpendingKey, Scope};
+
+use super::transparent_signing;
+use super::unauthorized::{
+    OrchardOutput, OrchardSpend, SaplingOutput, SaplingSpend, UnauthorizedTransaction,
+    UnprovenTransaction,
+};
+use crate::{
+    SecpPublicKey, ZcashAmount, ZcashBlockHeight, ZcashConsensusParameters, ZcashError,
+    ZcashFeeRules, ZcashLocalTxProver, ZcashMemoBytes, ZcashOrchardAddress, ZcashOrchardMerklePath,
+    ZcashOrchardNote, ZcashOrchardNoteValue, ZcashOrchardOutgoingViewingKey, ZcashOutPoint,
+    ZcashOutgoingViewingKey, ZcashPaymentAddress, ZcashProofGenerationKey, ZcashResult,
+    ZcashSaplingMerklePath, ZcashSaplingNote, ZcashScope, ZcashTransaction,
+    ZcashTransparentAddress, ZcashTxOut, ZcashUnifiedFullViewingKey, ZcashUnifiedSpendingKey,
+};
+
+/// Leading bytes of a serialized [`ZcashPartialTransaction`].
+const PARTIAL_TX_MAGIC: [u8; 4] = *b"ZPTX";
+
+/// Version of the [`ZcashPartialTransaction`] serialization format.
+const PARTIAL_TX_VERSION: u32 = 3;
+
+const SAPLING_MERKLE_DEPTH: u8 = 32;
+
+const ORCHARD_MERKLE_DEPTH: u8 = 32;
+
+/// A transaction under construction, which can be moved between devices as bytes.
+///
+/// This allows building a transaction on a device that does not hold the spending key:
+///
+/// - The creator sets the inputs and outputs of the transaction with
+///   [`ZcashPartialTransaction::new`] and the `add_*` methods.
+/// - The prover, an online device holding the [`ZcashUnifiedFullViewingKey`] of the
+///   account, creates the proofs with [`ZcashPartialTransaction::prove`].
+/// - The signer, an offline device holding the [`ZcashUnifiedSpendingKey`], checks what
+///   the transaction pays and adds the signatures with [`ZcashPartialTransaction::sign`].
+///   It needs neither the Sapling parameters nor the Orchard proving key.
+/// - The extractor returns the finished transaction with
+///   [`ZcashPartialTransaction::extract`].
+///
+/// Between each role, the partial transaction is exported with
+/// [`ZcashPartialTransaction::to_bytes`] and imported with
+/// [`ZcashPartialTransaction::from_bytes`].
+///
+/// Inputs can be received by the external or the internal (change) addresses of the
+/// account. The inputs must exactly cover the outputs plus the fee, as no change output
+/// is added. Transparent inputs must be P2PKH coins.
+pub struct ZcashPartialTransaction {
+    parameters: ZcashConsensusParameters,
+    target_height: BlockHeight,
+    fee_rule: ZcashFeeRules,
+    transparent_inputs: RwLock<Vec<PartialTransparentInput>>,
+    transparent_outputs: RwLock<Vec<PartialTransparentOutput>>,
+    sapling_spends: RwLock<Vec<PartialSaplingSpend>>,
+    sapling_outputs: RwLock<Vec<PartialSaplingOutput>>,
+    orchard_spends: RwLock<Vec<PartialOrchardSpend>>,
+    orchard_outputs: RwLock<Vec<PartialOrchardOutput>>,
+    proven: RwLock<Option<UnauthorizedTransaction>>,
+    transaction: RwLock<Option<Vec<u8>>>,
+}
+
+struct PartialTransparentInput {
+    utxo: OutPoint,
+    coin: TxOut,
+    scope: Scope,
+    address_index: u32,
+}
+
+struct PartialTransparentOutput {
+    to: TransparentAddress,
+    value: Amount,
+}
+
+struct PartialSaplingSpend {
+    note: Note,
+    merkle_path: MerklePath<Node, SAPLING_MERKLE_DEPTH>,
+    proof_generation_key: ProofGenerationKey,
+}
+
+struct PartialSaplingOutput {
+    ovk: Option<OutgoingViewingKey>,
+    to: PaymentAddress,
+    value: Amount,
+    memo: MemoBytes,
+}
+
+struct PartialOrchardSpend {
+    note: orchard::Note,
+    position: u32,
+    auth_path: [MerkleHashOrchard; 32],
+}
+
+struct PartialOrchardOutput {
+    ovk: Option<orchard::keys::OutgoingViewingKey>,
+    to: Address,
+    value: orchard::value::NoteValue,
+    memo: MemoBytes,
+}
+
+impl ZcashPartialTransaction {
+    /// Creates an empty partial transaction, to be mined at `target_height` and paying
+    /// a fee computed with `fee_rule`.
+    pub fn new(
+        parameters: ZcashConsensusParameters,
+        target_height: Arc<ZcashBlockHeight>,
+        fee_rule: ZcashFeeRules,
+    ) -> Self {
+        Self {
+            parameters,
+            target_height: target_height.as_ref().into(),
+            fee_rule,
+            transparent_inputs: RwLock::new(Vec::new()),
+            transparent_outputs: RwLock::new(Vec::new()),
+            sapling_spends: RwLock::new(Vec::new()),
+            sapling_outputs: RwLock::new(Vec::new()),
+            orchard_spends: RwLock::new(Vec::new()),
+            orchard_outputs: RwLock::new(Vec::new()),
+            proven: RwLock::new(None),
+            transaction: RwLock::new(None),
+        }
+    }
+
+    /// Adds a P2PKH coin to be spent, received by the transparent address at
+    /// `address_index` in `scope` of the signer's account.
+    pub fn add_transparent_input(
+        &self,
+        utxo: Arc<ZcashOutPoint>,
+        coin: Arc<ZcashTxOut>,
+        scope: ZcashScope,
+        address_index: u32,
+    ) -> ZcashResult<()> {
+        self.ensure_unproven()?;
+
+        let coin = TxOut::from(coin.as_ref());
+        if !matches!(
+            coin.recipient_address(),
+            Some(TransparentAddress::PublicKey(_))
+        ) {
+            return Err("Only P2PKH coins can be spent in a partial transaction".into());
+        }
+
+        self.transparent_inputs
+            .write()
+            .unwrap()
+            .push(PartialTransparentInput {
+                utxo: (*utxo.as_ref()).clone().into(),
+                coin,
+                scope: scope.into(),
+                address_index,
+            });
+        Ok(())
+    }
+
+    /// Adds a transparent address to send funds to.
+    pub fn add_transparent_output(
+        &self,
+        to: Arc<ZcashTransparentAddress>,
+        value: Arc<ZcashAmount>,
+    ) -> ZcashResult<()> {
+        self.ensure_unproven()?;
+        self.transparent_outputs
+            .write()
+            .unwrap()
+            .push(PartialTransparentOutput {
+                to: to.as_ref().into(),
+                value: value.as_ref().into(),
+            });
+        Ok(())
+    }
+
+    /// Adds a Sapling note of the signer's account to be spent.
+    ///
+    /// `proof_generation_key` is the key of the scope that received the note, returned by
+    /// [`crate::ZcashExtendedSpendingKey::proof_generation_key`] for the external scope, or
+    /// by the same method on the key returned by `derive_internal` for the internal scope.
+    /// The Merkle path must be consistent with the anchor of the other Sapling spends.
+    pub fn add_sapling_spend(
+        &self,
+        note: Arc<ZcashSaplingNote>,
+        merkle_path: Arc<ZcashSaplingMerklePath>,
+        proof_generation_key: Arc<ZcashProofGenerationKey>,
+    ) -> ZcashResult<()> {
+        self.ensure_unproven()?;
+        self.sapling_spends
+            .write()
+            .unwrap()
+            .push(PartialSaplingSpend {
+                note: note.as_ref().into(),
+                merkle_path: merkle_path.as_ref().into(),
+                proof_generation_key: proof_generation_key.as_ref().into(),
+            });
+        Ok(())
+    }
+
+    /// Adds a Sapling address to send funds to.
+    pub fn add_sapling_output(
+        &self,
+        ovk: Option<Arc<ZcashOutgoingViewingKey>>,
+        to: Arc<ZcashPaymentAddress>,
+        value: Arc<ZcashAmount>,
+        memo: Arc<ZcashMemoBytes>,
+    ) -> ZcashResult<()> {
+        self.ensure_unproven()?;
+        self.sapling_outputs
+            .write()
+            .unwrap()
+            .push(PartialSaplingOutput {
+                ovk: ovk.map(|ovk| ovk.as_ref().into()),
+                to: to.as_ref().into(),
+                value: value.as_ref().into(),
+                memo: memo.as_ref().into(),
+            });
+        Ok(())
+    }
+
+    /// Adds an Orchard note of the signer's account to be spent.
+    ///
+    /// The Merkle path must be consistent with the anchor of the other Orchard spends.
+    pub fn add_orchard_spend(
+        &self,
+        note: Arc<ZcashOrchardNote>,
+        merkle_path: Arc<ZcashOrchardMerklePath>,
+    ) -> ZcashResult<()> {
+        self.ensure_unproven()?;
+        self.orchard_spends
+            .write()
+            .unwrap()
+            .push(PartialOrchardSpend {
+                note: note.as_ref().into(),
+                position: merkle_path.position(),
+                auth_path: merkle_path.auth_path(),
+            });
+        Ok(())
+    }
+
+    /// Adds an Orchard address to send funds to.
+    pub fn add_orchard_output(
+        &self,
+        ovk: Option<Arc<ZcashOrchardOutgoingViewingKey>>,
+        to: Arc<ZcashOrchardAddress>,
+        value: Arc<ZcashOrchardNoteValue>,
+        memo: Arc<ZcashMemoBytes>,
+    ) -> ZcashResult<()> {
+        self.ensure_unproven()?;
+        self.orchard_outputs
+            .write()
+            .unwrap()
+            .push(PartialOrchardOutput {
+                ovk: ovk.map(|ovk| ovk.as_ref().into()),
+                to: to.0,
+                value: (*value).into(),
+                memo: memo.as_ref().into(),
+            });
+        Ok(())
+    }
+
+    /// Returns whether the proofs of the transaction have been created.
+    pub fn is_proven(&self) -> bool {
+        self.proven.read().unwrap().is_some() || self.is_signed()
+    }
+
+    /// Returns whether the transaction has been signed.
+    pub fn is_signed(&self) -> bool {
+        self.transaction.read().unwrap().is_some()
+    }
+
+    /// Creates the proofs of the transaction, with the full viewing key of the account
+    /// owning the shielded inputs.
+    ///
+    /// `prover` must be able to create Sapling proofs if the transaction has Sapling
+    /// spends or outputs. Orchard proofs are created with a proving key built on the fly.
+    pub fn prove(
+        &self,
+        ufvk: Arc<ZcashUnifiedFullViewingKey>,
+        prover: Arc<ZcashLocalTxProver>,
+    ) -> ZcashResult<()> {
+        self.prove_with(&ufvk, &prover.internal)
+    }
+
+    fn prove_with<Pr: TxProver>(
+        &self,
+        ufvk: &ZcashUnifiedFullViewingKey,
+        prover: &Pr,
+    ) -> ZcashResult<()> {
+        self.ensure_unproven()?;
+
+        let sapling_spends = self.sapling_spends.read().unwrap();
+        let sapling_spends = if sapling_spends.is_empty() {
+            Vec::new()
+        } else {
+            let dfvk = ufvk
+                .sapling()
+                .ok_or("The unified full viewing key has no Sapling component")?;
+            let dfvk = DiversifiableFullViewingKey::from(dfvk.as_ref());
+
+            sapling_spends
+                .iter()
+                .map(|spend| {
+                    let scope = dfvk
+                        .decrypt_diversifier(&spend.note.recipient())
+                        .map(|(_, scope)| scope)
+                        .ok_or("A Sapling note was not received by the account")?;
+                    if spend.proof_generation_key.ak != dfvk.fvk().vk.ak
+                        || spend.proof_generation_key.to_viewing_key().nk != dfvk.to_nk(scope)
+                    {
+                        return Err(
+                            "A proof generation key does not match the scope of its note".into(),
+                        );
+                    }
+
+                    Ok(SaplingSpend {
+                        proof_generation_key: spend.proof_generation_key.clone(),
+                        note: spend.note.clone(),
+                        merkle_path: spend.merkle_path.clone(),
+                    })
+                })
+                .collect::<ZcashResult<Vec<_>>>()?
+        };
+
+        let orchard_spends = self.orchard_spends.read().unwrap();
+        let orchard_spends = if orchard_spends.is_empty() {
+            Vec::new()
+        } else {
+            let fvk = ufvk
+                .orchard()
+                .ok_or("The unified full viewing key has no Orchard component")?;
+            let fvk = FullViewingKey::from(fvk.as_ref());
+
+            orchard_spends
+                .iter()
+                .map(|spend| {
+                    fvk.scope_for_address(&spend.note.recipient())
+                        .ok_or("An Orchard note was not received by the account")?;
+
+                    Ok(OrchardSpend {
+                        fvk: fvk.clone(),
+                        note: spend.note,
+                        merkle_path: orchard::tree::MerklePath::from_parts(
+                            spend.position,
+                            spend.auth_path,
+                        ),
+                    })
+                })
+                .collect::<ZcashResult<Vec<_>>>()?
+        };
+
+        let unproven = UnprovenTransaction {
+            transparent_inputs: self.transparent_input_coins(),
+            transparent_outputs: self.transparent_output_coins(),
+            sapling_spends,
+            sapling_outputs: self
+                .sapling_outputs
+                .read()
+                .unwrap()
+                .iter()
+                .map(|output| {
+                    Ok(SaplingOutput {
+                        ovk: output.ovk,
+                        to: output.to,
+                        value: note_value(output.value)?,
+                        memo: output.memo.clone(),
+                    })
+                })
+                .collect::<ZcashResult<Vec<_>>>()?,
+            orchard_spends,
+            orchard_outputs: self
+                .orchard_outputs
+                .read()
+                .unwrap()
+                .iter()
+                .map(|output| OrchardOutput {
+                    ovk: output.ovk.clone(),
+                    to: output.to,
+                    value: output.value,
+                    memo: *output.memo.as_array(),
+                })
+                .collect(),
+            ..Default::default()
+        };
+
+        unproven.check_balance(unproven.fee(&self.fee_rule)?)?;
+        let proven = unproven.prove(&self.parameters, self.target_height, Some(prover))?;
+        *self.proven.write().unwrap() = Some(proven);
+
+        Ok(())
+    }
+
+    /// Signs the proven transaction with the spending key of the account owning all
+    /// the inputs.
+    ///
+    /// Before signing, this checks that the proven transaction spends the inputs and
+    /// pays the outputs set by the creator, with the expected fee.
+    pub fn sign(&self, usk: Arc<ZcashUnifiedSpendingKey>) -> ZcashResult<()> {
+        if self.is_signed() {
+            return Err("The partial transaction has already been signed".into());
+        }
+        let proven = self.proven.read().unwrap();
+        let proven = proven
+            .as_ref()
+            .ok_or("The partial transaction has not been proven yet")?;
+
+        let transparent_inputs = self.transparent_input_coins();
+        let transparent_outputs = self.transparent_output_coins();
+        let sapling_outputs = self
+            .sapling_outputs
+            .read()
+            .unwrap()
+            .iter()
+            .map(|output| Ok((output.to, note_value(output.value)?)))
+            .collect::<ZcashResult<Vec<_>>>()?;
+        let orchard_outputs: Vec<_> = self
+            .orchard_outputs
+            .read()
+            .unwrap()
+            .iter()
+            .map(|output| (output.to, output.value))
+            .collect();
+        let fee = super::unauthorized::fee(
+            &self.fee_rule,
+            transparent_inputs.len(),
+            &transparent_outputs,
+            self.sapling_spends.read().unwrap().len(),
+            sapling_outputs.len(),
+            self.orchard_spends.read().unwrap().len(),
+            orchard_outputs.len(),
+        )?;
+        proven.check_payments(
+            &transparent_inputs,
+            &transparent_outputs,
+            &sapling_outputs,
+            &orchard_outputs,
+            fee,
+        )?;
+
+        let mut rng = OsRng;
+        let secp = Secp256k1::signing_only();
+
+        let account_sk = usk.transparent();
+        let script_sigs = self
+            .transparent_inputs
+            .read()
+            .unwrap()
+            .iter()
+            .enumerate()
+            .map(|(index, input)| -> ZcashResult<Script> {
+                let sk = match input.scope {
+                    Scope::External => account_sk.derive_external_secret_key(input.address_index),
+                    Scope::Internal => account_sk.derive_internal_secret_key(input.address_index),
+                }?;
+                let sk = SecretKey::from(sk.as_ref());
+                let pubkey = PublicKey::from_secret_key(&secp, &sk);
+                if SecpPublicKey::from(pubkey).p2pkh_address(true).script()
+                    != input.coin.script_pubkey
+                {
+                    return Err(format!(
+                        "Transparent input {} is not received by the address of its key",
+                        index
+                    )
+                    .into());
+                }
+
+                let sighash = proven.transparent_sighash(index)?;
+                let signature = secp.sign_ecdsa(&Message::from_slice(&sighash)?, &sk);
+                Ok(transparent_signing::p2pkh_script_sig(&signature, &pubkey))
+            })
+            .collect::<ZcashResult<Vec<_>>>()?;
+
+        let sighash = proven.shielded_sighash()?;
+
+        // The spend authorizing key is shared by the external and internal scopes.
+        let ask = ExtendedSpendingKey::from((*usk.sapling()).clone())
+            .expsk
+            .ask;
+        let sapling_sigs = proven
+            .sapling_alphas()
+            .iter()
+            .map(|(_, alpha)| spend_sig(PrivateKey(ask), *alpha, &sighash, &mut rng))
+            .collect();
+
+        let ask = SpendAuthorizingKey::from(&SpendingKey::from(usk.orchard().as_ref()));
+        let orchard_sigs = proven
+            .orchard_alphas()
+            .iter()
+            .flatten()
+            .map(|(_, alpha)| ask.randomize(alpha).sign(rng, &sighash))
+            .collect();
+
+        let tx = proven.authorize(script_sigs, sapling_sigs, orchard_sigs)?;
+        let mut data = Vec::new();
+        tx.write(&mut data)?;
+        *self.transaction.write().unwrap() = Some(data);
+
+        Ok(())
+    }
+
+    /// Returns the finished transaction, ready to be broadcast.
+    ///
+    /// Returns an error if the transaction has not been signed yet.
+    pub fn extract(&self) -> ZcashResult<Arc<ZcashTransaction>> {
+        match self.transaction.read().unwrap().as_ref() {
+            Some(data) => {
+                let branch_id = BranchId::for_height(&self.parameters, self.target_height);
+                let tx = Transaction::read(&data[..], branch_id)?;
+                Ok(Arc::new(tx.into()))
+            }
+            None => Err("The partial transaction has not been signed yet".into()),
+        }
+    }
+
+    /// Serializes the partial transaction.
+    pub fn to_bytes(&self) -> ZcashResult<Vec<u8>> {
+        let mut w = Vec::new();
+
+        w.write_all(&PARTIAL_TX_MAGIC)?;
+        write_u32(&mut w, PARTIAL_TX_VERSION)?;
+        write_u8(
+            &mut w,
+            match self.parameters {
+                ZcashConsensusParameters::MainNetwork => 0,
+                ZcashConsensusParameters::TestNetwork => 1,
+            },
+        )?;
+        write_u32(&mut w, self.target_height.into())?;
+        write_fee_rule(&mut w, &self.fee_rule)?;
+
+        let transparent_inputs = self.transparent_inputs.read().unwrap();
+        write_u32(&mut w, transparent_inputs.len().try_into()?)?;
+        for input in transparent_inputs.iter() {
+            input.utxo.write(&mut w)?;
+            input.coin.write(&mut w)?;
+            write_u8(
+                &mut w,
+                match input.scope {
+                    Scope::External => 0,
+                    Scope::Internal => 1,
+                },
+            )?;
+            write_u32(&mut w, input.address_index)?;
+        }
+
+        let transparent_outputs = self.transparent_outputs.read().unwrap();
+        write_u32(&mut w, transparent_outputs.len().try_into()?)?;
+        for output in transparent_outputs.iter() {
+            output.to.script().write(&mut w)?;
+            write_amount(&mut w, output.value)?;
+        }
+
+        let sapling_spends = self.sapling_spends.read().unwrap();
+        write_u32(&mut w, sapling_spends.len().try_into()?)?;
+        for spend in sapling_spends.iter() {
+            write_note(&mut w, &spend.note)?;
+            write_u64(&mut w, spend.merkle_path.position().into())?;
+            for node in spend.merkle_path.path_elems() {
+                node.write(&mut w)?;
+            }
+            w.write_all(&ZcashProofGenerationKey::write(&spend.proof_generation_key))?;
+        }
+
+        let sapling_outputs = self.sapling_outputs.read().unwrap();
+        write_u32(&mut w, sapling_outputs.len().try_into()?)?;
+        for output in sapling_outputs.iter() {
+            write_ovk(&mut w, output.ovk.map(|ovk| ovk.0))?;
+            w.write_all(&output.to.to_bytes())?;
+            write_amount(&mut w, output.value)?;
+            w.write_all(output.memo.as_array())?;
+        }
+
+        let orchard_spends = self.orchard_spends.read().unwrap();
+        write_u32(&mut w, orchard_spends.len().try_into()?)?;
+        for spend in orchard_spends.iter() {
+            write_orchard_note(&mut w, &spend.note)?;
+            write_u32(&mut w, spend.position)?;
+            for node in spend.auth_path.iter() {
+                w.write_all(&node.to_bytes())?;
+            }
+        }
+
+        let orchard_outputs = self.orchard_outputs.read().unwrap();
+        write_u32(&mut w, orchard_outputs.len().try_into()?)?;
+        for output in orchard_outputs.iter() {
+            write_ovk(&mut w, output.ovk.as_ref().map(|ovk| *ovk.as_ref()))?;
+            w.write_all(&output.to.to_raw_address_bytes())?;
+            write_u64(&mut w, output.value.inner())?;
+            w.write_all(output.memo.as_array())?;
+        }
+
+        match (
+            self.proven.read().unwrap().as_ref(),
+            self.transaction.read().unwrap().as_ref(),
+        ) {
+            (_, Some(data)) => {
+                write_u8(&mut w, 2)?;
+                write_u32(&mut w, data.len().try_into()?)?;
+                w.write_all(data)?;
+            }
+            (Some(proven), None) => {
+                write_u8(&mut w, 1)?;
+                proven.write(&mut w)?;
+            }
+            (None, None) => write_u8(&mut w, 0)?,
+        }
+
+        Ok(w)
+    }
+
+    /// Parses a partial transaction serialized with [`ZcashPartialTransaction::to_bytes`].
+    pub fn from_bytes(data: &[u8]) -> ZcashResult<Self> {
+        let mut r = data;
+
+        let mut magic = [0u8; 4];
+        r.read_exact(&mut magic)?;
+        if magic != PARTIAL_TX_MAGIC {
+            return Err("Not a partial transaction".into());
+        }
+
+        let version = read_u32(&mut r)?;
+        if version != PARTIAL_TX_VERSION {
+            return Err(format!("Unsupported partial transaction version: {}", version).into());
+        }
+
+        let parameters = match read_u8(&mut r)? {
+            0 => ZcashConsensusParameters::MainNetwork,
+            1 => ZcashConsensusParameters::TestNetwork,
+            other => return Err(format!("Unknown network: {}", other).into()),
+        };
+        let target_height = BlockHeight::from_u32(read_u32(&mut r)?);
+        let fee_rule = read_fee_rule(&mut r)?;
+
+        let transparent_inputs = (0..read_u32(&mut r)?)
+            .map(|_| -> ZcashResult<PartialTransparentInput> {
+                Ok(PartialTransparentInput {
+                    utxo: OutPoint::read(&mut r)?,
+                    coin: TxOut::read(&mut r)?,
+                    scope: match read_u8(&mut r)? {
+                        0 => Scope::External,
+                        1 => Scope::Internal,
+                        other => return Err(format!("Invalid scope: {}", other).into()),
+                    },
+                    address_index: read_u32(&mut r)?,
+                })
+            })
+            .collect::<ZcashResult<Vec<_>>>()?;
+
+        let transparent_outputs = (0..read_u32(&mut r)?)
+            .map(|_| -> ZcashResult<PartialTransparentOutput> {
+                let output = TxOut {
+                    script_pubkey: Script::read(&mut r)?,
+                    value: read_amount(&mut r)?,
+                };
+                Ok(PartialTransparentOutput {
+                    to: output
+                        .recipient_address()
+                        .ok_or("Invalid transparent output address")?,
+                    value: output.value,
+                })
+            })
+            .collect::<ZcashResult<Vec<_>>>()?;
+
+        let sapling_spends = (0..read_u32(&mut r)?)
+            .map(|_| -> ZcashResult<PartialSaplingSpend> {
+                let note = read_note(&mut r)?;
+                let position = Position::from(read_u64(&mut r)?);
+                let path_elems = (0..SAPLING_MERKLE_DEPTH)
+                    .map(|_| Node::read(&mut r))
+                    .collect::<std::io::Result<Vec<_>>>()?;
+                let merkle_path = MerklePath::from_parts(path_elems, position)
+                    .or(Err("Invalid Sapling Merkle path"))?;
+                let proof_generation_key = ZcashProofGenerationKey::read(&read_array(&mut r)?)
+                    .ok_or("Invalid proof generation key")?;
+                Ok(PartialSaplingSpend {
+                    note,
+                    merkle_path,
+                    proof_generation_key: (&proof_generation_key).into(),
+                })
+            })
+            .collect::<ZcashResult<Vec<_>>>()?;
+
+        let sapling_outputs = (0..read_u32(&mut r)?)
+            .map(|_| -> ZcashResult<PartialSaplingOutput> {
+                let ovk = read_ovk(&mut r)?.map(OutgoingViewingKey);
+                let to = PaymentAddress::from_bytes(&read_array(&mut r)?)
+                    .ok_or("Invalid Sapling output address")?;
+                let value = read_amount(&mut r)?;
+                let memo = MemoBytes::from_bytes(&read_array::<512>(&mut r)?)
+                    .or(Err("Invalid Sapling output memo"))?;
+                Ok(PartialSaplingOutput {
+                    ovk,
+                    to,
+                    value,
+                    memo,
+                })
+            })
+            .collect::<ZcashResult<Vec<_>>>()?;
+
+        let orchard_spends = (0..read_u32(&mut r)?)
+            .map(|_| -> ZcashResult<PartialOrchardSpend> {
+                let note = read_orchard_note(&mut r)?;
+                let position = read_u32(&mut r)?;
+                let auth_path = (0..ORCHARD_MERKLE_DEPTH)
+                    .map(|_| -> ZcashResult<MerkleHashOrchard> {
+                        Option::from(MerkleHashOrchard::from_bytes(&read_array(&mut r)?))
+                            .ok_or_else(|| "Invalid Orchard Merkle path".into())
+                    })
+                    .collect::<ZcashResult<Vec<_>>>()?;
+                Ok(PartialOrchardSpend {
+                    note,
+                    position,
+                    auth_path: auth_path
+                        .try_into()
+                        .expect("The Merkle path has the depth of the tree"),
+                })
+            })
+            .collect::<ZcashResult<Vec<_>>>()?;
+
+        let orchard_outputs = (0..read_u32(&mut r)?)
+            .map(|_| -> ZcashResult<PartialOrchardOutput> {
+                let ovk = read_ovk(&mut r)?.map(orchard::keys::OutgoingViewingKey::from);
+                let to = Option::from(Address::from_raw_address_bytes(&read_array(&mut r)?))
+                    .ok_or("Invalid Orchard output address")?;
+                let value = orchard::value::NoteValue::from_raw(read_u64(&mut r)?);
+                let memo = MemoBytes::from_bytes(&read_array::<512>(&mut r)?)
+                    .or(Err("Invalid Orchard output memo"))?;
+                Ok(PartialOrchardOutput {
+                    ovk,
+                    to,
+                    value,
+                    memo,
+                })
+            })
+            .collect::<ZcashResult<Vec<_>>>()?;
+
+        let (proven, transaction) = match read_u8(&mut r)? {
+            0 => (None, None),
+            1 => (Some(UnauthorizedTransaction::read(&mut r)?), None),
+            2 => {
+                let mut data = vec![0u8; read_u32(&mut r)?.try_into()?];
+                r.read_exact(&mut data)?;
+                (None, Some(data))
+            }
+            other => return Err(format!("Invalid transaction state: {}", other).into()),
+        };
+
+        if !r.is_empty() {
+            return Err("Unexpected trailing bytes in partial transaction".into());
+        }
+
+        Ok(Self {
+            parameters,
+            target_height,
+            fee_rule,
+            transparent_inputs: RwLock::new(transparent_inputs),
+            transparent_outputs: RwLock::new(transparent_outputs),
+            sapling_spends: RwLock::new(sapling_spends),
+            sapling_outputs: RwLock::new(sapling_outputs),
+            orchard_spends: RwLock::new(orchard_spends),
+            orchard_outputs: RwLock::new(orchard_outputs),
+            proven: RwLock::new(proven),
+            transaction: RwLock::new(transaction),
+        })
+    }
+
+    fn ensure_unproven(&self) -> ZcashResult<()> {
+        if self.is_proven() {
+            Err("The partial transaction has already been proven".into())
+        } else {
+            Ok(())
+        }
+    }
+
+    fn transparent_input_coins(&self) -> Vec<(OutPoint, TxOut)> {
+        self.transparent_inputs
+            .read()
+            .unwrap()
+            .iter()
+            .map(|input| (input.utxo.clone(), input.coin.clone()))
+            .collect()
+    }
+
+    fn transparent_output_coins(&self) -> Vec<TxOut> {
+        self.transparent_outputs
+            .read()
+            .unwrap()
+            .iter()
+            .map(|output| TxOut {
+                value: output.value,
+                script_pubkey: output.to.script(),
+            })
+            .collect()
+    }
+}
+
+pub(super) fn note_value(value: Amount) -> ZcashResult<NoteValue> {
+    u64::try_from(i64::from(value))
+        .map(NoteValue::from_raw)
+        .or(Err("Shielded outputs cannot have a negative value".into()))
+}
+
+pub(super) fn write_u8<W: Write>(w: &mut W, v: u8) -> ZcashResult<()> {
+    Ok(w.write_all(&[v])?)
+}
+
+pub(super) fn write_u32<W: Write>(w: &mut W, v: u32) -> ZcashResult<()> {
+    Ok(w.write_all(&v.to_le_bytes())?)
+}
+
+fn write_u64<W: Write>(w: &mut W, v: u64) -> ZcashResult<()> {
+    Ok(w.write_all(&v.to_le_bytes())?)
+}
+
+fn write_amount<W: Write>(w: &mut W, v: Amount) -> ZcashResult<()> {
+    Ok(w.write_all(&i64::from(v).to_le_bytes())?)
+}
+
+pub(super) fn read_array<const N: usize>(r: &mut &[u8]) -> ZcashResult<[u8; N]> {
+    let mut data = [0u8; N];
+    r.read_exact(&mut data)?;
+    Ok(data)
+}
+
+pub(super) fn read_u8(r: &mut &[u8]) -> ZcashResult<u8> {
+    Ok(u8::from_le_bytes(read_array(r)?))
+}
+
+pub(super) fn read_u32(r: &mut &[u8]) -> ZcashResult<u32> {
+    Ok(u32::from_le_bytes(read_array(r)?))
+}
+
+fn read_u64(r: &mut &[u8]) -> ZcashResult<u64> {
+    Ok(u64::from_le_bytes(read_array(r)?))
+}
+
+fn read_amount(r: &mut &[u8]) -> ZcashResult<Amount> {
+    Amount::from_i64(i64::from_le_bytes(read_array(r)?)).or(Err("Invalid amount".into()))
+}
+
+fn write_fee_rule<W: Write>(w: &mut W, fee_rule: &ZcashFeeRules) -> ZcashResult<()> {
+    match fee_rule {
+        ZcashFeeRules::FixedStandard => write_u8(w, 0),
+        ZcashFeeRules::FixedNonStandard { amount } => {
+            write_u8(w, 1)?;
+            write_u64(w, *amount)
+        }
+        ZcashFeeRules::Zip317Standard => write_u8(w, 2),
+        ZcashFeeRules::Zip317NonStandard {
+            marginal_fee,
+            grace_actions,
+            p2pkh_standard_input_size,
+            p2pkh_standard_output_size,
+        } => {
+            write_u8(w, 3)?;
+            write_u64(w, *marginal_fee)?;
+            write_u64(w, *grace_actions)?;
+            write_u64(w, *p2pkh_standard_input_size)?;
+            write_u64(w, *p2pkh_standard_output_size)
+        }
+    }
+}
+
+fn read_fee_rule(r: &mut &[u8]) -> ZcashResult<ZcashFeeRules> {
+    match read_u8(r)? {
+        0 => Ok(ZcashFeeRules::FixedStandard),
+        1 => Ok(ZcashFeeRules::FixedNonStandard {
+            amount: read_u64(r)?,
+        }),
+        2 => Ok(ZcashFeeRules::Zip317Standard),
+        3 => Ok(ZcashFeeRules::Zip317NonStandard {
+            marginal_fee: read_u64(r)?,
+            grace_actions: read_u64(r)?,
+            p2pkh_standard_input_size: read_u64(r)?,
+            p2pkh_standard_output_size: read_u64(r)?,
+        }),
+        other => Err(format!("Unknown fee rule: {}", other).into()),
+    }
+}
+
+pub(super) fn write_note<W: Write>(w: &mut W, note: &Note) -> ZcashResult<()> {
+    w.write_all(&note.recipient().to_bytes())?;
+    write_u64(w, note.value().inner())?;
+    match note.rseed() {
+        Rseed::BeforeZip212(rcm) => {
+            write_u8(w, 0)?;
+            w.write_all(&rcm.to_repr())?;
+        }
+        Rseed::AfterZip212(rseed) => {
+            write_u8(w, 1)?;
+            w.write_all(rseed)?;
+        }
+    }
+    Ok(())
+}
+
+pub(super) fn read_note(r: &mut &[u8]) -> ZcashResult<Note> {
+    let recipient =
+        PaymentAddress::from_bytes(&read_array(r)?).ok_or("Invalid Sapling note recipient")?;
+    let value = NoteValue::from_raw(read_u64(r)?);
+    let rseed = match read_u8(r)? {
+        0 => {
+            let rcm: Option<jubjub::Fr> = jubjub::Fr::from_repr(read_array(r)?).into();
+            Rseed::BeforeZip212(rcm.ok_or("Invalid Sapling note rcm")?)
+        }
+        1 => Rseed::AfterZip212(read_array(r)?),
+        other => return Err(ZcashError::from(format!("Invalid rseed type: {}", other))),
+    };
+    Ok(Note::from_parts(recipient, value, rseed))
+}
+
+pub(super) fn write_orchard_note<W: Write>(w: &mut W, note: &orchard::Note) -> ZcashResult<()> {
+    w.write_all(&note.recipient().to_raw_address_bytes())?;
+    write_u64(w, note.value().inner())?;
+    w.write_all(&note.rho().to_bytes())?;
+    w.write_all(note.rseed().as_bytes())?;
+    Ok(())
+}
+
+pub(super) fn read_orchard_note(r: &mut &[u8]) -> ZcashResult<orchard::Note> {
+    let recipient = Option::from(Address::from_raw_address_bytes(&read_array(r)?))
+        .ok_or("Invalid Orchard note recipient")?;
+    let value = orchard::value::NoteValue::from_raw(read_u64(r)?);
+    let rho: Nullifier =
+        Option::from(Nullifier::from_bytes(&read_array(r)?)).ok_or("Invalid Orchard note rho")?;
+    let rseed = Option::from(RandomSeed::from_bytes(read_array(r)?, &rho))
+        .ok_or("Invalid Orchard note rseed")?;
+    Option::from(orchard::Note::from_parts(recipient, value, rho, rseed))
+        .ok_or_else(|| "Invalid Orchard note".into())
+}
+
+fn write_ovk<W: Write>(w: &mut W, ovk: Option<[u8; 32]>) -> ZcashResult<()> {
+    match ovk {
+        Some(ovk) => {
+            write_u8(w, 1)?;
+            Ok(w.write_all(&ovk)?)
+        }
+        None => write_u8(w, 0),
+    }
+}
+
+fn read_ovk(r: &mut &[u8]) -> ZcashResult<Option<[u8; 32]>> {
+    match read_u8(r)? {
+        0 => Ok(None),
+        1 => Ok(Some(read_array(r)?)),
+        other => Err(format!("Invalid OVK flag: {}", other).into()),
+    }
+}
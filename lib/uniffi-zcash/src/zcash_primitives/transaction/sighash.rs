@@ -0,0 +1,178 @@
+use std::sync::Arc;
+
+use zcash_primitives::{
+    legacy::Script,
+    transaction::{
+        components::{sapling, transparent, Amount, TxOut},
+        sighash::{
+            signature_hash, SignableInput, TransparentAuthorizingContext, SIGHASH_ALL,
+            SIGHASH_ANYONECANPAY, SIGHASH_MASK, SIGHASH_SINGLE,
+        },
+        txid::TxIdDigester,
+        Authorization,
+    },
+};
+
+use crate::{ZcashError, ZcashResult, ZcashScript, ZcashTransaction, ZcashTxOut};
+
+/// The digests committed to by the ZIP 244 transaction identifier.
+pub struct ZcashTxDigests {
+    pub header_digest: Vec<u8>,
+    pub transparent_prevouts_digest: Option<Vec<u8>>,
+    pub transparent_sequence_digest: Option<Vec<u8>>,
+    pub transparent_outputs_digest: Option<Vec<u8>>,
+    pub sapling_digest: Option<Vec<u8>>,
+    pub orchard_digest: Option<Vec<u8>>,
+}
+
+impl ZcashTransaction {
+    /// Returns the signature hash signed by the Sapling and Orchard spend authorization
+    /// signatures and by the binding signatures.
+    ///
+    /// `prevouts` are the outputs spent by the transparent inputs, in the order of the inputs.
+    pub fn shielded_sighash(&self, prevouts: Vec<Arc<ZcashTxOut>>) -> ZcashResult<Vec<u8>> {
+        self.sighash(self.prevouts(prevouts)?, &SignableInput::Shielded)
+    }
+
+    /// Returns the signature hash of the transparent input at `input_index`, for the given
+    /// `hash_type` (`SIGHASH_ALL`, `SIGHASH_NONE` or `SIGHASH_SINGLE`, optionally combined
+    /// with `SIGHASH_ANYONECANPAY`).
+    ///
+    /// `prevouts` are the outputs spent by the transparent inputs, in the order of the inputs.
+    /// `script_code` defaults to the script of the spent output, and must be set to the
+    /// redeem script when spending a P2SH output.
+    pub fn transparent_sighash(
+        &self,
+        input_index: u32,
+        hash_type: u8,
+        prevouts: Vec<Arc<ZcashTxOut>>,
+        script_code: Option<Arc<ZcashScript>>,
+    ) -> ZcashResult<Vec<u8>> {
+        if !(SIGHASH_ALL..=SIGHASH_SINGLE).contains(&(hash_type & SIGHASH_MASK))
+            || hash_type & !(SIGHASH_MASK | SIGHASH_ANYONECANPAY) != 0
+        {
+            return Err(format!("Invalid hash type {:#04x}", hash_type).into());
+        }
+
+        let index = input_index as usize;
+        let prevouts = self.prevouts(prevouts)?;
+        let prevout = prevouts
+            .get(index)
+            .ok_or("Input index out of range")?
+            .clone();
+        let script_code = script_code
+            .map(|script| script.as_ref().into())
+            .unwrap_or_else(|| prevout.script_pubkey.clone());
+
+        self.sighash(
+            prevouts,
+            &SignableInput::Transparent {
+                hash_type,
+                index,
+                script_code: &script_code,
+                script_pubkey: &prevout.script_pubkey,
+                value: prevout.value,
+            },
+        )
+    }
+
+    /// Returns the digests from which the transaction identifier is computed.
+    pub fn txid_digests(&self) -> ZcashTxDigests {
+        let digests = self.0.digest(TxIdDigester);
+        let transparent = digests.transparent_digests.as_ref();
+
+        ZcashTxDigests {
+            header_digest: digests.header_digest.as_bytes().to_vec(),
+            transparent_prevouts_digest: transparent.map(|d| d.prevouts_digest.as_bytes().to_vec()),
+            transparent_sequence_digest: transparent.map(|d| d.sequence_digest.as_bytes().to_vec()),
+            transparent_outputs_digest: transparent.map(|d| d.outputs_digest.as_bytes().to_vec()),
+            sapling_digest: digests.sapling_digest.map(|d| d.as_bytes().to_vec()),
+            orchard_digest: digests.orchard_digest.map(|d| d.as_bytes().to_vec()),
+        }
+    }
+
+    /// Returns the ZIP 244 authorizing data commitment of the transaction, committed to by
+    /// the block header `hashAuthDataRoot`.
+    pub fn auth_commitment(&self) -> Vec<u8> {
+        self.0.auth_commitment().as_bytes().to_vec()
+    }
+
+    fn prevouts(&self, prevouts: Vec<Arc<ZcashTxOut>>) -> ZcashResult<Vec<TxOut>> {
+        let input_count = self
+            .0
+            .transparent_bundle()
+            .map_or(0, |bundle| bundle.vin.len());
+
+        if prevouts.len() != input_count {
+            return Err(ZcashError::ArrayLengthMismatch {
+                expected: input_count as u64,
+                got: prevouts.len() as u64,
+            });
+        }
+
+        Ok(prevouts
+            .iter()
+            .map(|prevout| prevout.as_ref().into())
+            .collect())
+    }
+
+    fn sighash(
+        &self,
+        prevouts: Vec<TxOut>,
+        signable_input: &SignableInput,
+    ) -> ZcashResult<Vec<u8>> {
+        let tx_data = self
+            .clone()
+            .0
+            .into_data()
+            .map_authorization::<WithPrevouts>(AddPrevouts(prevouts), (), ());
+        let txid_parts = tx_data.digest(TxIdDigester);
+
+        Ok(signature_hash(&tx_data, signable_input, &txid_parts)
+            .as_ref()
+            .to_vec())
+    }
+}
+
+/// Authorization of a transaction whose transparent inputs are annotated with the
+/// outputs they spend, as required to compute signature hashes.
+#[derive(Debug)]
+struct WithPrevouts;
+
+impl Authorization for WithPrevouts {
+    type TransparentAuth = TransparentPrevouts;
+    type SaplingAuth = sapling::Authorized;
+    type OrchardAuth = orchard::bundle::Authorized;
+}
+
+#[derive(Debug)]
+struct TransparentPrevouts(Vec<TxOut>);
+
+impl transparent::Authorization for TransparentPrevouts {
+    type ScriptSig = Script;
+}
+
+impl TransparentAuthorizingContext for TransparentPrevouts {
+    fn input_amounts(&self) -> Vec<Amount> {
+        self.0.iter().map(|prevout| prevout.value).collect()
+    }
+
+    fn input_scriptpubkeys(&self) -> Vec<Script> {
+        self.0
+            .iter()
+            .map(|prevout| prevout.script_pubkey.clone())
+            .collect()
+    }
+}
+
+struct AddPrevouts(Vec<TxOut>);
+
+impl transparent::MapAuth<transparent::Authorized, TransparentPrevouts> for AddPrevouts {
+    fn map_script_sig(&self, script_sig: Script) -> Script {
+        script_sig
+    }
+
+    fn map_authorization(&self, _: transparent::Authorized) -> TransparentPrevouts {
+        TransparentPrevouts(self.0.clone())
+    }
+}
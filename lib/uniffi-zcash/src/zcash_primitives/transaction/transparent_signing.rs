@@ -0,0 +1,389 @@
+use std::cmp::max;
+use std::io::{self, Write};
+
+use secp256k1::{ecdsa::Signature, PublicKey};
+use zcash_primitives::{
+    consensus::{BlockHeight, BranchId},
+    legacy::{Script, TransparentAddress},
+    transaction::{
+        components::{sapling, transparent, Amount, OutPoint, TxIn, TxOut},
+        fees::zip317,
+        sighash::{signature_hash, SignableInput, TransparentAuthorizingContext, SIGHASH_ALL},
+        txid::TxIdDigester,
+        Authorization, Authorized, Transaction, TransactionData, TxVersion,
+    },
+};
+
+use super::partial::{read_u32, write_u32};
+use crate::{
+    ZcashConsensusParameters, ZcashError, ZcashFeeRules, ZcashResult, ZcashTransactionBuilder,
+};
+
+/// Expiry delta used by the librustzcash builder, which does not export it.
+pub(super) const DEFAULT_TX_EXPIRY_DELTA: u32 = 40;
+
+/// Size of the `scriptSig` of a P2PKH input, with a signature of the maximum DER size.
+pub(super) const P2PKH_SCRIPT_SIG_SIZE: usize = 1 + 73 + 1 + 33;
+
+/// Serialized size of an outpoint and a sequence number.
+const TX_IN_FIXED_SIZE: usize = 40;
+
+/// Serialized size of the value of a transparent output.
+const TX_OUT_VALUE_SIZE: usize = 8;
+
+/// A transparent-only transaction whose inputs are signed with `SIGHASH_ALL`
+/// outside of the librustzcash builder.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct UnsignedTransparentTransaction {
+    consensus_branch_id: BranchId,
+    expiry_height: BlockHeight,
+    inputs: Vec<(OutPoint, TxOut)>,
+    outputs: Vec<TxOut>,
+}
+
+impl UnsignedTransparentTransaction {
+    pub(crate) fn new(
+        params: &ZcashConsensusParameters,
+        target_height: BlockHeight,
+        inputs: Vec<(OutPoint, TxOut)>,
+        outputs: Vec<TxOut>,
+    ) -> Self {
+        Self {
+            consensus_branch_id: BranchId::for_height(params, target_height),
+            expiry_height: target_height + DEFAULT_TX_EXPIRY_DELTA,
+            inputs,
+            outputs,
+        }
+    }
+
+    pub(crate) fn inputs(&self) -> &[(OutPoint, TxOut)] {
+        &self.inputs
+    }
+
+    pub(crate) fn outputs(&self) -> &[TxOut] {
+        &self.outputs
+    }
+
+    /// Checks that the inputs exactly cover the outputs plus `fee`, as no change output is added.
+    pub(crate) fn check_balance(&self, fee: Amount) -> ZcashResult<()> {
+        let total_in = self
+            .inputs
+            .iter()
+            .map(|(_, coin)| i64::from(coin.value))
+            .sum::<i64>();
+        let total_out = self
+            .outputs
+            .iter()
+            .map(|output| i64::from(output.value))
+            .sum::<i64>();
+        let change = total_in - total_out - i64::from(fee);
+
+        if change < 0 {
+            Err(ZcashError::InsufficientFundsError {
+                amount: change.unsigned_abs(),
+            })
+        } else if change > 0 {
+            Err(ZcashError::ChangeRequiredError {
+                amount: change as u64,
+            })
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Returns the signature hash of the input at `index`, spent with `script_code`.
+    pub(crate) fn sighash(&self, index: usize, script_code: &Script) -> ZcashResult<[u8; 32]> {
+        let (_, coin) = self.inputs.get(index).ok_or("Input index out of range")?;
+
+        let tx_data = self.transaction_data::<Unsigned>(
+            self.inputs.iter().map(|_| ()).collect(),
+            Signing {
+                input_amounts: self.inputs.iter().map(|(_, coin)| coin.value).collect(),
+                input_scriptpubkeys: self
+                    .inputs
+                    .iter()
+                    .map(|(_, coin)| coin.script_pubkey.clone())
+                    .collect(),
+            },
+        );
+        let txid_parts = tx_data.digest(TxIdDigester);
+
+        let sighash = signature_hash(
+            &tx_data,
+            &SignableInput::Transparent {
+                hash_type: SIGHASH_ALL,
+                index,
+                script_code,
+                script_pubkey: &coin.script_pubkey,
+                value: coin.value,
+            },
+            &txid_parts,
+        );
+
+        Ok(*sighash.as_ref())
+    }
+
+    /// Assembles the transaction with the given `scriptSig` of each input.
+    pub(crate) fn finalize(&self, script_sigs: Vec<Script>) -> ZcashResult<Transaction> {
+        if script_sigs.len() != self.inputs.len() {
+            return Err(ZcashError::ArrayLengthMismatch {
+                expected: self.inputs.len() as u64,
+                got: script_sigs.len() as u64,
+            });
+        }
+
+        Ok(self
+            .transaction_data::<Authorized>(script_sigs, transparent::Authorized)
+            .freeze()?)
+    }
+
+    fn transaction_data<A>(
+        &self,
+        script_sigs: Vec<<A::TransparentAuth as transparent::Authorization>::ScriptSig>,
+        authorization: A::TransparentAuth,
+    ) -> TransactionData<A>
+    where
+        A: Authorization<
+            SaplingAuth = sapling::Authorized,
+            OrchardAuth = orchard::bundle::Authorized,
+        >,
+    {
+        TransactionData::from_parts(
+            TxVersion::suggested_for_branch(self.consensus_branch_id),
+            self.consensus_branch_id,
+            0,
+            self.expiry_height,
+            Some(transparent::Bundle {
+                vin: self
+                    .inputs
+                    .iter()
+                    .zip(script_sigs)
+                    .map(|((prevout, _), script_sig)| TxIn {
+                        prevout: prevout.clone(),
+                        script_sig,
+                        sequence: u32::MAX,
+                    })
+                    .collect(),
+                vout: self.outputs.clone(),
+                authorization,
+            }),
+            None,
+            None,
+            None,
+        )
+    }
+
+    pub(crate) fn write<W: Write>(&self, mut w: W) -> ZcashResult<()> {
+        write_u32(&mut w, self.consensus_branch_id.into())?;
+        write_u32(&mut w, self.expiry_height.into())?;
+
+        write_u32(&mut w, self.inputs.len().try_into()?)?;
+        for (prevout, coin) in self.inputs.iter() {
+            prevout.write(&mut w)?;
+            coin.write(&mut w)?;
+        }
+
+        write_u32(&mut w, self.outputs.len().try_into()?)?;
+        for output in self.outputs.iter() {
+            output.write(&mut w)?;
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn read(r: &mut &[u8]) -> ZcashResult<Self> {
+        let consensus_branch_id = BranchId::try_from(read_u32(r)?)?;
+        let expiry_height = BlockHeight::from_u32(read_u32(r)?);
+
+        let inputs = (0..read_u32(r)?)
+            .map(|_| -> io::Result<(OutPoint, TxOut)> {
+                Ok((OutPoint::read(&mut *r)?, TxOut::read(&mut *r)?))
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+
+        let outputs = (0..read_u32(r)?)
+            .map(|_| TxOut::read(&mut *r))
+            .collect::<io::Result<Vec<_>>>()?;
+
+        Ok(Self {
+            consensus_branch_id,
+            expiry_height,
+            inputs,
+            outputs,
+        })
+    }
+}
+
+impl ZcashTransactionBuilder {
+    /// Returns the transparent outputs added to the builder.
+    pub(super) fn transparent_outputs(&self) -> Vec<TxOut> {
+        self.transparent_output
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(to, value)| TxOut {
+                value: value.as_ref().into(),
+                script_pubkey: TransparentAddress::from(to.as_ref()).script(),
+            })
+            .collect()
+    }
+}
+
+/// Returns the `scriptSig` spending a P2PKH coin with a `SIGHASH_ALL` signature.
+pub(super) fn p2pkh_script_sig(signature: &Signature, pubkey: &PublicKey) -> Script {
+    let mut sig_bytes = signature.serialize_der().to_vec();
+    sig_bytes.push(SIGHASH_ALL);
+
+    Script::default() << &sig_bytes[..] << &pubkey.serialize()[..]
+}
+
+/// Returns the serialized size of a transparent input with a `scriptSig` of `script_sig_size` bytes.
+pub(crate) fn input_size(script_sig_size: usize) -> usize {
+    TX_IN_FIXED_SIZE + compact_size_len(script_sig_size) + script_sig_size
+}
+
+/// Returns the serialized size of a transparent output.
+pub(crate) fn output_size(output: &TxOut) -> usize {
+    let script_size = output.script_pubkey.0.len();
+    TX_OUT_VALUE_SIZE + compact_size_len(script_size) + script_size
+}
+
+/// Returns the fee of a transparent-only transaction. ZIP 317 fee rules are computed from
+/// the total serialized size of its inputs and outputs.
+pub(crate) fn transparent_fee(
+    fee_rule: &ZcashFeeRules,
+    t_in_total_size: usize,
+    t_out_total_size: usize,
+) -> ZcashResult<Amount> {
+    fee(fee_rule, t_in_total_size, t_out_total_size, 0)
+}
+
+/// Returns the fee of a transaction with `shielded_logical_actions` ZIP 317 logical actions
+/// on top of its transparent inputs and outputs.
+pub(crate) fn fee(
+    fee_rule: &ZcashFeeRules,
+    t_in_total_size: usize,
+    t_out_total_size: usize,
+    shielded_logical_actions: usize,
+) -> ZcashResult<Amount> {
+    let fee_rule = match fee_rule {
+        ZcashFeeRules::FixedNonStandard { amount } => {
+            return Ok(Amount::from_u64(*amount).or(Err("Error parsing amount"))?);
+        }
+        ZcashFeeRules::FixedStandard | ZcashFeeRules::Zip317Standard => zip317::FeeRule::standard(),
+        ZcashFeeRules::Zip317NonStandard {
+            marginal_fee,
+            grace_actions,
+            p2pkh_standard_input_size,
+            p2pkh_standard_output_size,
+        } => zip317::FeeRule::non_standard(
+            Amount::from_u64(*marginal_fee).or(Err("Error parsing amount"))?,
+            (*grace_actions).try_into()?,
+            (*p2pkh_standard_input_size).try_into()?,
+            (*p2pkh_standard_output_size).try_into()?,
+        )
+        .ok_or("p2pkh_standard_input_size and p2pkh_standard_output_size should not be zero")?,
+    };
+
+    conventional_fee(
+        &fee_rule,
+        transparent_logical_actions(&fee_rule, t_in_total_size, t_out_total_size)
+            + shielded_logical_actions,
+    )
+}
+
+/// Returns the ZIP 317 logical actions of the transparent inputs and outputs.
+pub(crate) fn transparent_logical_actions(
+    fee_rule: &zip317::FeeRule,
+    t_in_total_size: usize,
+    t_out_total_size: usize,
+) -> usize {
+    max(
+        t_in_total_size.div_ceil(fee_rule.p2pkh_standard_input_size()),
+        t_out_total_size.div_ceil(fee_rule.p2pkh_standard_output_size()),
+    )
+}
+
+/// Returns the ZIP 317 conventional fee for the given number of logical actions.
+pub(crate) fn conventional_fee(
+    fee_rule: &zip317::FeeRule,
+    logical_actions: usize,
+) -> ZcashResult<Amount> {
+    (fee_rule.marginal_fee() * max(fee_rule.grace_actions(), logical_actions))
+        .ok_or_else(|| "Fee overflow".into())
+}
+
+fn compact_size_len(size: usize) -> usize {
+    match size {
+        0..=0xfc => 1,
+        0xfd..=0xffff => 3,
+        0x10000..=0xffff_ffff => 5,
+        _ => 9,
+    }
+}
+
+/// Returns a copy of `tx` from which signature hashes can be computed, given the coins
+/// spent by its transparent inputs.
+pub(super) fn signing_data(
+    tx: &TransactionData<Authorized>,
+    coins: &[TxOut],
+) -> TransactionData<Unsigned> {
+    TransactionData::from_parts(
+        tx.version(),
+        tx.consensus_branch_id(),
+        tx.lock_time(),
+        tx.expiry_height(),
+        tx.transparent_bundle().map(|bundle| transparent::Bundle {
+            vin: bundle
+                .vin
+                .iter()
+                .map(|txin| TxIn {
+                    prevout: txin.prevout.clone(),
+                    script_sig: (),
+                    sequence: txin.sequence,
+                })
+                .collect(),
+            vout: bundle.vout.clone(),
+            authorization: Signing {
+                input_amounts: coins.iter().map(|coin| coin.value).collect(),
+                input_scriptpubkeys: coins
+                    .iter()
+                    .map(|coin| coin.script_pubkey.clone())
+                    .collect(),
+            },
+        }),
+        None,
+        tx.sapling_bundle().cloned(),
+        tx.orchard_bundle().cloned(),
+    )
+}
+
+/// Authorization of a transaction whose transparent inputs are waiting for their signatures.
+#[derive(Debug)]
+pub(super) struct Unsigned;
+
+impl Authorization for Unsigned {
+    type TransparentAuth = Signing;
+    type SaplingAuth = sapling::Authorized;
+    type OrchardAuth = orchard::bundle::Authorized;
+}
+
+#[derive(Debug)]
+pub(super) struct Signing {
+    input_amounts: Vec<Amount>,
+    input_scriptpubkeys: Vec<Script>,
+}
+
+impl transparent::Authorization for Signing {
+    type ScriptSig = ();
+}
+
+impl TransparentAuthorizingContext for Signing {
+    fn input_amounts(&self) -> Vec<Amount> {
+        self.input_amounts.clone()
+    }
+
+    fn input_scriptpubkeys(&self) -> Vec<Script> {
+        self.input_scriptpubkeys.clone()
+    }
+}
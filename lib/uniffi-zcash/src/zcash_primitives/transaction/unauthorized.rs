@@ -0,0 +1,1233 @@
+use std::cmp::max;
+use std::io::{self, Write};
+use std::iter;
+
+use group::ff::{Field, PrimeField};
+use group::GroupEncoding;
+use incrementalmerkletree::{Hashable, Level, MerklePath};
+use nonempty::NonEmpty;
+use orchard::{
+    builder::SpendInfo,
+    bundle::{Authorized as OrchardAuthorized, Flags},
+    circuit::{Circuit, Instance, Proof, ProvingKey},
+    keys::{FullViewingKey, Scope, SpendAuthorizingKey, SpendValidatingKey, SpendingKey},
+    note::{ExtractedNoteCommitment, Nullifier, RandomSeed, TransmittedNoteCiphertext},
+    note_encryption::{OrchardDomain, OrchardNoteEncryption},
+    primitives::redpallas::{self, Binding, SpendAuth},
+    tree::{Anchor, MerkleHashOrchard},
+    value::{ValueCommitTrapdoor, ValueCommitment},
+    Action, Address,
+};
+use pasta_curves::pallas;
+use rand::{rngs::OsRng, seq::SliceRandom, RngCore};
+use zcash_note_encryption::Domain;
+use zcash_primitives::{
+    consensus::{BlockHeight, BranchId, NetworkUpgrade, Parameters},
+    constants::SPENDING_KEY_GENERATOR,
+    keys::prf_expand,
+    legacy::{Script, TransparentAddress},
+    memo::MemoBytes,
+    sapling::{
+        keys::OutgoingViewingKey,
+        note_encryption::{sapling_note_encryption, SaplingDomain},
+        prover::TxProver,
+        redjubjub,
+        util::generate_random_rseed,
+        value::NoteValue,
+        Diversifier, Node, Note, PaymentAddress, ProofGenerationKey, Rseed, SaplingIvk,
+    },
+    transaction::{
+        components::{
+            sapling::{self, GrothProofBytes, OutputDescription, SpendDescription},
+            transparent, Amount, OutPoint, TxIn, TxOut,
+        },
+        sighash::{signature_hash, SignableInput, SIGHASH_ALL},
+        txid::TxIdDigester,
+        Authorized, Transaction, TransactionData, TxVersion,
+    },
+};
+
+use super::partial::{
+    read_array, read_orchard_note, read_u32, read_u8, write_note, write_orchard_note, write_u32,
+    write_u8,
+};
+use super::{partial::read_note, transparent_signing};
+use crate::{ZcashError, ZcashFeeRules, ZcashResult};
+
+/// Minimum number of outputs of a Sapling bundle with spends, as padded by the
+/// librustzcash builder.
+const MIN_SHIELDED_OUTPUTS: usize = 2;
+
+/// Minimum number of actions of an Orchard bundle, as padded by the orchard builder.
+const MIN_ACTIONS: usize = 2;
+
+const SAPLING_MERKLE_DEPTH: u8 = 32;
+
+const ORCHARD_MERKLE_DEPTH: u8 = 32;
+
+/// A Sapling note to be spent, with the key needed to prove the spend.
+pub(crate) struct SaplingSpend {
+    pub(crate) proof_generation_key: ProofGenerationKey,
+    pub(crate) note: Note,
+    pub(crate) merkle_path: MerklePath<Node, SAPLING_MERKLE_DEPTH>,
+}
+
+pub(crate) struct SaplingOutput {
+    pub(crate) ovk: Option<OutgoingViewingKey>,
+    pub(crate) to: PaymentAddress,
+    pub(crate) value: NoteValue,
+    pub(crate) memo: MemoBytes,
+}
+
+/// An Orchard note to be spent, with the full viewing key needed to prove the spend.
+pub(crate) struct OrchardSpend {
+    pub(crate) fvk: FullViewingKey,
+    pub(crate) note: orchard::Note,
+    pub(crate) merkle_path: orchard::tree::MerklePath,
+}
+
+pub(crate) struct OrchardOutput {
+    pub(crate) ovk: Option<orchard::keys::OutgoingViewingKey>,
+    pub(crate) to: Address,
+    pub(crate) value: orchard::value::NoteValue,
+    pub(crate) memo: [u8; 512],
+}
+
+/// The address receiving the change of a transaction.
+pub(crate) enum ChangeAddress {
+    Transparent(TransparentAddress),
+    Sapling(Option<OutgoingViewingKey>, PaymentAddress),
+    Orchard(Option<orchard::keys::OutgoingViewingKey>, Address),
+}
+
+/// The inputs and outputs of a transaction, before it is proven.
+///
+/// Unlike the librustzcash builders, proving only needs the proof generation keys of the
+/// Sapling spends and the full viewing keys of the Orchard spends. The spend authorization
+/// signatures are added afterwards to the resulting [`UnauthorizedTransaction`].
+/// Transparent inputs must be P2PKH coins.
+#[derive(Default)]
+pub(crate) struct UnprovenTransaction {
+    pub(crate) transparent_inputs: Vec<(OutPoint, TxOut)>,
+    pub(crate) transparent_outputs: Vec<TxOut>,
+    pub(crate) sapling_spends: Vec<SaplingSpend>,
+    pub(crate) sapling_outputs: Vec<SaplingOutput>,
+    pub(crate) orchard_spends: Vec<OrchardSpend>,
+    pub(crate) orchard_outputs: Vec<OrchardOutput>,
+    /// The anchor of the Orchard bundle, defaulting to the root of the Orchard spends, or
+    /// to the root of the empty tree without spends.
+    pub(crate) orchard_anchor: Option<Anchor>,
+    /// The expiry height, defaulting to the one set by the librustzcash builder.
+    pub(crate) expiry_height: Option<BlockHeight>,
+}
+
+impl UnprovenTransaction {
+    /// Returns the fee of the transaction, including the padding of its shielded bundles.
+    pub(crate) fn fee(&self, fee_rule: &ZcashFeeRules) -> ZcashResult<Amount> {
+        fee(
+            fee_rule,
+            self.transparent_inputs.len(),
+            &self.transparent_outputs,
+            self.sapling_spends.len(),
+            self.sapling_outputs.len(),
+            self.orchard_spends.len(),
+            self.orchard_outputs.len(),
+        )
+    }
+
+    /// Returns the value of the inputs minus the value of the outputs.
+    fn balance(&self) -> i64 {
+        let total_in = self
+            .transparent_inputs
+            .iter()
+            .map(|(_, coin)| i64::from(coin.value))
+            .chain(
+                self.sapling_spends
+                    .iter()
+                    .map(|spend| spend.note.value().inner() as i64),
+            )
+            .chain(
+                self.orchard_spends
+                    .iter()
+                    .map(|spend| spend.note.value().inner() as i64),
+            )
+            .sum::<i64>();
+        let total_out = self
+            .transparent_outputs
+            .iter()
+            .map(|output| i64::from(output.value))
+            .chain(
+                self.sapling_outputs
+                    .iter()
+                    .map(|output| output.value.inner() as i64),
+            )
+            .chain(
+                self.orchard_outputs
+                    .iter()
+                    .map(|output| output.value.inner() as i64),
+            )
+            .sum::<i64>();
+
+        total_in - total_out
+    }
+
+    /// Adds an output sending to `change` the value of the inputs left after the outputs
+    /// and the fee, which accounts for the change output itself.
+    ///
+    /// No output is added if there is no value left, or if it does not cover the fee of
+    /// the change output.
+    pub(crate) fn add_change(
+        &mut self,
+        fee_rule: &ZcashFeeRules,
+        change: &ChangeAddress,
+    ) -> ZcashResult<()> {
+        if self.balance() <= i64::from(self.fee(fee_rule)?) {
+            return Ok(());
+        }
+
+        self.push_change(change, 0)?;
+        let value = self.balance() - i64::from(self.fee(fee_rule)?);
+        self.pop_change(change);
+
+        if value > 0 {
+            self.push_change(change, value as u64)?;
+        }
+        Ok(())
+    }
+
+    fn push_change(&mut self, change: &ChangeAddress, value: u64) -> ZcashResult<()> {
+        match change {
+            ChangeAddress::Transparent(to) => self.transparent_outputs.push(TxOut {
+                value: Amount::from_u64(value).or(Err("Invalid change value"))?,
+                script_pubkey: to.script(),
+            }),
+            ChangeAddress::Sapling(ovk, to) => self.sapling_outputs.push(SaplingOutput {
+                ovk: *ovk,
+                to: *to,
+                value: NoteValue::from_raw(value),
+                memo: MemoBytes::empty(),
+            }),
+            ChangeAddress::Orchard(ovk, to) => self.orchard_outputs.push(OrchardOutput {
+                ovk: ovk.clone(),
+                to: *to,
+                value: orchard::value::NoteValue::from_raw(value),
+                memo: *MemoBytes::empty().as_array(),
+            }),
+        }
+        Ok(())
+    }
+
+    fn pop_change(&mut self, change: &ChangeAddress) {
+        match change {
+            ChangeAddress::Transparent(_) => {
+                self.transparent_outputs.pop();
+            }
+            ChangeAddress::Sapling(..) => {
+                self.sapling_outputs.pop();
+            }
+            ChangeAddress::Orchard(..) => {
+                self.orchard_outputs.pop();
+            }
+        }
+    }
+
+    /// Checks that the inputs exactly cover the outputs plus `fee`.
+    pub(crate) fn check_balance(&self, fee: Amount) -> ZcashResult<()> {
+        let change = self.balance() - i64::from(fee);
+
+        if change < 0 {
+            Err(ZcashError::InsufficientFundsError {
+                amount: change.unsigned_abs(),
+            })
+        } else if change > 0 {
+            Err(ZcashError::ChangeRequiredError {
+                amount: change as u64,
+            })
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Creates the proofs and the binding signatures of a v5 transaction to be mined at
+    /// `target_height`, leaving its spend authorization signatures to be added.
+    ///
+    /// `prover` is only needed when the transaction has Sapling spends or outputs.
+    /// The balance is not checked, so that the value left after the outputs is the fee.
+    pub(crate) fn prove<P: Parameters, Pr: TxProver>(
+        self,
+        params: &P,
+        target_height: BlockHeight,
+        prover: Option<&Pr>,
+    ) -> ZcashResult<UnauthorizedTransaction> {
+        if !params.is_nu_active(NetworkUpgrade::Nu5, target_height) {
+            return Err("Only v5 transactions can be proven, NU5 must be active".into());
+        }
+
+        let mut rng = OsRng;
+        let consensus_branch_id = BranchId::for_height(params, target_height);
+
+        let (prevouts, coins): (Vec<_>, Vec<_>) = self.transparent_inputs.into_iter().unzip();
+        let transparent_bundle = (!prevouts.is_empty() || !self.transparent_outputs.is_empty())
+            .then(|| transparent::Bundle {
+                vin: prevouts
+                    .into_iter()
+                    .map(|prevout| TxIn {
+                        prevout,
+                        script_sig: Script::default(),
+                        sequence: u32::MAX,
+                    })
+                    .collect(),
+                vout: self.transparent_outputs,
+                authorization: transparent::Authorized,
+            });
+
+        let mut sapling_prover =
+            if self.sapling_spends.is_empty() && self.sapling_outputs.is_empty() {
+                None
+            } else {
+                let prover = prover.ok_or("A prover is required for Sapling spends and outputs")?;
+                Some((prover, prover.new_sapling_proving_context()))
+            };
+        let sapling = match sapling_prover.as_mut() {
+            Some((prover, ctx)) => ProvenSapling::prove(
+                self.sapling_spends,
+                self.sapling_outputs,
+                params,
+                target_height,
+                *prover,
+                ctx,
+                &mut rng,
+            )?,
+            None => ProvenSapling::empty(),
+        };
+        let mut orchard = ProvenOrchard::prove(
+            self.orchard_spends,
+            self.orchard_outputs,
+            self.orchard_anchor,
+            &mut rng,
+        )?;
+        let expiry_height = self
+            .expiry_height
+            .unwrap_or(target_height + transparent_signing::DEFAULT_TX_EXPIRY_DELTA);
+
+        let transaction_data =
+            |orchard: &Option<ProvenOrchard>, sapling_binding_sig, orchard_binding_sig| {
+                TransactionData::<Authorized>::from_parts(
+                    TxVersion::Zip225,
+                    consensus_branch_id,
+                    0,
+                    expiry_height,
+                    transparent_bundle.clone(),
+                    None,
+                    sapling.bundle(sapling_binding_sig),
+                    orchard
+                        .as_ref()
+                        .and_then(|orchard| orchard.bundle(orchard_binding_sig)),
+                )
+            };
+
+        // The binding signatures are not covered by the signature hash.
+        let sighash = shielded_sighash(
+            &transaction_data(&orchard, placeholder_sapling_signature()?, [0u8; 64].into()),
+            &coins,
+        );
+
+        let sapling_binding_sig = match sapling_prover.as_mut() {
+            Some((prover, ctx)) => prover
+                .binding_sig(ctx, sapling.value_balance, &sighash)
+                .or(Err("Failed to create the Sapling binding signature"))?,
+            None => placeholder_sapling_signature()?,
+        };
+
+        let orchard_binding_sig = match orchard.as_mut() {
+            Some(orchard) => orchard.sign_dummy_spends(&sighash, &mut rng),
+            None => [0u8; 64].into(),
+        };
+
+        let transaction =
+            transaction_data(&orchard, sapling_binding_sig, orchard_binding_sig).freeze()?;
+        let mut data = Vec::new();
+        transaction.write(&mut data)?;
+
+        Ok(UnauthorizedTransaction {
+            consensus_branch_id,
+            transaction: data,
+            coins,
+            sapling_alphas: sapling.alphas,
+            sapling_notes: sapling.notes,
+            orchard_alphas: orchard
+                .as_ref()
+                .map_or_else(Vec::new, |orchard| orchard.alphas.clone()),
+            orchard_notes: orchard.map_or_else(Vec::new, |orchard| orchard.notes),
+        })
+    }
+}
+
+/// Returns the fee of a transaction with the given inputs and outputs, including the
+/// padding of its shielded bundles. Transparent inputs are assumed to be P2PKH.
+pub(crate) fn fee(
+    fee_rule: &ZcashFeeRules,
+    transparent_inputs: usize,
+    transparent_outputs: &[TxOut],
+    sapling_spends: usize,
+    sapling_outputs: usize,
+    orchard_spends: usize,
+    orchard_outputs: usize,
+) -> ZcashResult<Amount> {
+    let sapling_outputs = match sapling_spends {
+        0 => sapling_outputs,
+        _ => max(MIN_SHIELDED_OUTPUTS, sapling_outputs),
+    };
+    let orchard_actions = match max(orchard_spends, orchard_outputs) {
+        0 => 0,
+        actions => max(MIN_ACTIONS, actions),
+    };
+
+    transparent_signing::fee(
+        fee_rule,
+        transparent_inputs
+            * transparent_signing::input_size(transparent_signing::P2PKH_SCRIPT_SIG_SIZE),
+        transparent_outputs
+            .iter()
+            .map(transparent_signing::output_size)
+            .sum(),
+        max(sapling_spends, sapling_outputs) + orchard_actions,
+    )
+}
+
+fn placeholder_sapling_signature() -> ZcashResult<redjubjub::Signature> {
+    Ok(redjubjub::Signature::read(&[0u8; 64][..])?)
+}
+
+fn shielded_sighash(tx: &TransactionData<Authorized>, coins: &[TxOut]) -> [u8; 32] {
+    let tx_data = transparent_signing::signing_data(tx, coins);
+    let txid_parts = tx_data.digest(TxIdDigester);
+    *signature_hash(&tx_data, &SignableInput::Shielded, &txid_parts).as_ref()
+}
+
+/// The Sapling spends and outputs of a transaction, proven but not yet signed.
+struct ProvenSapling {
+    spends: Vec<SpendDescription<sapling::Authorized>>,
+    outputs: Vec<OutputDescription<GrothProofBytes>>,
+    value_balance: Amount,
+    /// The index of each spend in the unproven transaction, with its randomizer.
+    alphas: Vec<(usize, jubjub::Fr)>,
+    notes: Vec<Note>,
+}
+
+impl ProvenSapling {
+    fn empty() -> Self {
+        Self {
+            spends: Vec::new(),
+            outputs: Vec::new(),
+            value_balance: Amount::zero(),
+            alphas: Vec::new(),
+            notes: Vec::new(),
+        }
+    }
+
+    fn prove<P: Parameters, Pr: TxProver>(
+        spends: Vec<SaplingSpend>,
+        mut outputs: Vec<SaplingOutput>,
+        params: &P,
+        target_height: BlockHeight,
+        prover: &Pr,
+        ctx: &mut Pr::SaplingProvingContext,
+        rng: &mut OsRng,
+    ) -> ZcashResult<Self> {
+        let value_balance = spends
+            .iter()
+            .map(|spend| spend.note.value().inner() as i64)
+            .sum::<i64>()
+            - outputs
+                .iter()
+                .map(|output| output.value.inner() as i64)
+                .sum::<i64>();
+        let value_balance =
+            Amount::from_i64(value_balance).or(Err("Invalid Sapling value balance"))?;
+
+        let mut anchor = None;
+        for spend in spends.iter() {
+            let root: bls12_381::Scalar = spend
+                .merkle_path
+                .root(Node::from_cmu(&spend.note.cmu()))
+                .into();
+            if *anchor.get_or_insert(root) != root {
+                return Err("All Sapling spends must have the same anchor".into());
+            }
+        }
+
+        if !spends.is_empty() {
+            while outputs.len() < MIN_SHIELDED_OUTPUTS {
+                outputs.push(dummy_sapling_output(rng));
+            }
+        }
+
+        let mut spends: Vec<_> = spends.into_iter().enumerate().collect();
+        spends.shuffle(rng);
+        outputs.shuffle(rng);
+
+        let mut alphas = Vec::with_capacity(spends.len());
+        let spends = spends
+            .into_iter()
+            .map(|(index, spend)| {
+                let anchor = anchor.expect("The anchor is set when there are spends");
+                let alpha = jubjub::Fr::random(&mut *rng);
+                let nullifier = spend.note.nf(
+                    &spend.proof_generation_key.to_viewing_key().nk,
+                    u64::from(spend.merkle_path.position()),
+                );
+
+                let (zkproof, cv, rk) = prover
+                    .spend_proof(
+                        ctx,
+                        spend.proof_generation_key,
+                        *spend.note.recipient().diversifier(),
+                        *spend.note.rseed(),
+                        alpha,
+                        spend.note.value().inner(),
+                        anchor,
+                        spend.merkle_path,
+                    )
+                    .or(Err("Failed to create a Sapling spend proof"))?;
+
+                alphas.push((index, alpha));
+                Ok(SpendDescription::temporary_zcashd_from_parts(
+                    cv,
+                    anchor,
+                    nullifier,
+                    rk,
+                    zkproof,
+                    placeholder_sapling_signature()?,
+                ))
+            })
+            .collect::<ZcashResult<Vec<_>>>()?;
+
+        let mut notes = Vec::with_capacity(outputs.len());
+        let outputs = outputs
+            .into_iter()
+            .map(|output| {
+                let rseed = generate_random_rseed(params, target_height, rng);
+                let note = Note::from_parts(output.to, output.value, rseed);
+
+                // Since ZIP 212, `esk` is derived from `rseed`, as done by the encryptor.
+                let esk = match rseed {
+                    Rseed::AfterZip212(rseed) => {
+                        jubjub::Fr::from_bytes_wide(prf_expand(&rseed, &[0x05]).as_array())
+                    }
+                    Rseed::BeforeZip212(_) => unreachable!("NU5 is active"),
+                };
+
+                let encryptor =
+                    sapling_note_encryption::<_, P>(output.ovk, note.clone(), output.memo, rng);
+                let (zkproof, cv) =
+                    prover.output_proof(ctx, esk, output.to, note.rcm(), output.value.inner());
+                let cmu = note.cmu();
+
+                notes.push(note);
+                OutputDescription::temporary_zcashd_from_parts(
+                    cv.clone(),
+                    cmu,
+                    SaplingDomain::<P>::epk_bytes(encryptor.epk()),
+                    encryptor.encrypt_note_plaintext(),
+                    encryptor.encrypt_outgoing_plaintext(&cv, &cmu, rng),
+                    zkproof,
+                )
+            })
+            .collect();
+
+        Ok(Self {
+            spends,
+            outputs,
+            value_balance,
+            alphas,
+            notes,
+        })
+    }
+
+    fn is_empty(&self) -> bool {
+        self.spends.is_empty() && self.outputs.is_empty()
+    }
+
+    fn bundle(
+        &self,
+        binding_sig: redjubjub::Signature,
+    ) -> Option<sapling::Bundle<sapling::Authorized>> {
+        (!self.is_empty()).then(|| {
+            sapling::Bundle::temporary_zcashd_from_parts(
+                self.spends.clone(),
+                self.outputs.clone(),
+                self.value_balance,
+                sapling::Authorized { binding_sig },
+            )
+        })
+    }
+}
+
+fn dummy_sapling_output(rng: &mut OsRng) -> SaplingOutput {
+    let to = loop {
+        let mut diversifier = Diversifier([0; 11]);
+        rng.fill_bytes(&mut diversifier.0);
+        if let Some(to) = SaplingIvk(jubjub::Fr::random(&mut *rng)).to_payment_address(diversifier)
+        {
+            break to;
+        }
+    };
+
+    SaplingOutput {
+        ovk: None,
+        to,
+        value: NoteValue::from_raw(0),
+        memo: MemoBytes::empty(),
+    }
+}
+
+/// The Orchard actions of a transaction, proven but not yet signed.
+struct ProvenOrchard {
+    actions: Vec<Action<redpallas::Signature<SpendAuth>>>,
+    anchor: Anchor,
+    value_balance: Amount,
+    proof: Proof,
+    bsk: redpallas::SigningKey<Binding>,
+    /// The index of each spend to be signed in the unproven transaction, with its
+    /// randomizer, or `None` for dummy spends.
+    alphas: Vec<Option<(usize, pallas::Scalar)>>,
+    /// The randomized spend authorizing keys of the dummy spends.
+    dummy_rsks: Vec<Option<redpallas::SigningKey<SpendAuth>>>,
+    notes: Vec<orchard::Note>,
+}
+
+impl ProvenOrchard {
+    fn prove(
+        spends: Vec<OrchardSpend>,
+        mut outputs: Vec<OrchardOutput>,
+        anchor: Option<Anchor>,
+        rng: &mut OsRng,
+    ) -> ZcashResult<Option<Self>> {
+        let num_actions = match max(spends.len(), outputs.len()) {
+            0 => return Ok(None),
+            actions => max(MIN_ACTIONS, actions),
+        };
+
+        let mut anchor = anchor;
+        for spend in spends.iter() {
+            let root = spend.merkle_path.root(spend.note.commitment().into());
+            if *anchor.get_or_insert(root) != root {
+                return Err("All Orchard spends must have the same anchor".into());
+            }
+        }
+        // Without spends, the anchor is the root of the empty tree, which is valid at
+        // every height since NU5.
+        let anchor = anchor.unwrap_or_else(|| {
+            MerkleHashOrchard::empty_root(Level::from(ORCHARD_MERKLE_DEPTH)).into()
+        });
+
+        let mut spends: Vec<_> = spends
+            .into_iter()
+            .enumerate()
+            .map(|(index, spend)| (spend, OrchardSpendAuthorization::Signer(index)))
+            .collect();
+        spends.extend(
+            iter::repeat_with(|| dummy_orchard_spend(rng)).take(num_actions - spends.len()),
+        );
+        outputs.extend(
+            iter::repeat_with(|| dummy_orchard_output(rng)).take(num_actions - outputs.len()),
+        );
+
+        spends.shuffle(rng);
+        outputs.shuffle(rng);
+
+        let mut value_balance = 0i64;
+        let mut bsk = pallas::Scalar::zero();
+        let mut actions = Vec::with_capacity(num_actions);
+        let mut circuits = Vec::with_capacity(num_actions);
+        let mut instances = Vec::with_capacity(num_actions);
+        let mut alphas = Vec::with_capacity(num_actions);
+        let mut dummy_rsks = Vec::with_capacity(num_actions);
+        let mut notes = Vec::with_capacity(num_actions);
+
+        for ((spend, authorization), output) in spends.into_iter().zip(outputs) {
+            let alpha = pallas::Scalar::random(&mut *rng);
+            let rcv_scalar = pallas::Scalar::random(&mut *rng);
+            // A canonical encoding is always a valid trapdoor.
+            let rcv = ValueCommitTrapdoor::from_bytes(rcv_scalar.to_repr()).unwrap();
+            bsk += rcv_scalar;
+
+            let nf_old = spend.note.nullifier(&spend.fvk);
+            let rk = SpendValidatingKey::from(spend.fvk.clone()).randomize(&alpha);
+            let note = random_orchard_note(output.to, output.value, nf_old, rng);
+            let cmx = ExtractedNoteCommitment::from(note.commitment());
+
+            value_balance += spend.note.value().inner() as i64 - output.value.inner() as i64;
+            let cv_net = ValueCommitment::derive(spend.note.value() - output.value, rcv.clone());
+
+            let encryptor = OrchardNoteEncryption::new(output.ovk, note, output.memo);
+            let encrypted_note = TransmittedNoteCiphertext {
+                epk_bytes: OrchardDomain::epk_bytes(encryptor.epk()).0,
+                enc_ciphertext: encryptor.encrypt_note_plaintext(),
+                out_ciphertext: encryptor.encrypt_outgoing_plaintext(&cv_net, &cmx, &mut *rng),
+            };
+
+            let spend_info = SpendInfo::new(spend.fvk, spend.note, spend.merkle_path)
+                .ok_or("An Orchard note was not received by the full viewing key")?;
+            circuits.push(
+                Circuit::from_action_context(spend_info, note, alpha, rcv)
+                    .ok_or("Invalid Orchard action")?,
+            );
+            instances.push(Instance::from_parts(
+                anchor,
+                cv_net.clone(),
+                nf_old,
+                rk.clone(),
+                cmx,
+                true,
+                true,
+            ));
+            actions.push(Action::from_parts(
+                nf_old,
+                rk,
+                cmx,
+                encrypted_note,
+                cv_net,
+                [0u8; 64].into(),
+            ));
+
+            match authorization {
+                OrchardSpendAuthorization::Signer(index) => {
+                    alphas.push(Some((index, alpha)));
+                    dummy_rsks.push(None);
+                }
+                OrchardSpendAuthorization::Dummy(ask) => {
+                    alphas.push(None);
+                    dummy_rsks.push(Some(ask.randomize(&alpha)));
+                }
+            }
+            notes.push(note);
+        }
+
+        let proof = Proof::create(&ProvingKey::build(), &circuits, &instances, &mut *rng)
+            .or(Err("Failed to create the Orchard proof"))?;
+
+        Ok(Some(Self {
+            actions,
+            anchor,
+            value_balance: Amount::from_i64(value_balance)
+                .or(Err("Invalid Orchard value balance"))?,
+            proof,
+            bsk: redpallas::SigningKey::try_from(bsk.to_repr())
+                .or(Err("Invalid Orchard binding signing key"))?,
+            alphas,
+            dummy_rsks,
+            notes,
+        }))
+    }
+
+    /// Signs the dummy spends, returning the binding signature.
+    fn sign_dummy_spends(
+        &mut self,
+        sighash: &[u8; 32],
+        rng: &mut OsRng,
+    ) -> redpallas::Signature<Binding> {
+        self.actions = self
+            .actions
+            .drain(..)
+            .zip(self.dummy_rsks.iter())
+            .map(|(action, rsk)| match rsk {
+                Some(rsk) => action.map(|_| rsk.sign(*rng, sighash)),
+                None => action,
+            })
+            .collect();
+
+        self.bsk.sign(rng, sighash)
+    }
+
+    fn bundle(
+        &self,
+        binding_sig: redpallas::Signature<Binding>,
+    ) -> Option<orchard::Bundle<OrchardAuthorized, Amount>> {
+        NonEmpty::from_vec(self.actions.clone()).map(|actions| {
+            orchard::Bundle::from_parts(
+                actions,
+                Flags::from_parts(true, true),
+                self.value_balance,
+                self.anchor,
+                OrchardAuthorized::from_parts(self.proof.clone(), binding_sig),
+            )
+        })
+    }
+}
+
+fn random_orchard_spending_key(rng: &mut OsRng) -> SpendingKey {
+    loop {
+        if let Some(sk) = SpendingKey::from_bytes(read_random(rng)).into() {
+            break sk;
+        }
+    }
+}
+
+fn random_orchard_note(
+    recipient: Address,
+    value: orchard::value::NoteValue,
+    rho: Nullifier,
+    rng: &mut OsRng,
+) -> orchard::Note {
+    loop {
+        let rseed: Option<RandomSeed> = RandomSeed::from_bytes(read_random(rng), &rho).into();
+        if let Some(note) = rseed
+            .and_then(|rseed| Option::from(orchard::Note::from_parts(recipient, value, rho, rseed)))
+        {
+            break note;
+        }
+    }
+}
+
+fn read_random(rng: &mut OsRng) -> [u8; 32] {
+    let mut bytes = [0u8; 32];
+    rng.fill_bytes(&mut bytes);
+    bytes
+}
+
+/// How the spend of an Orchard action is authorized.
+enum OrchardSpendAuthorization {
+    /// By the signer of the spend at this index in the unproven transaction.
+    Signer(usize),
+    /// By the spend authorizing key of a dummy spend, when proving.
+    Dummy(SpendAuthorizingKey),
+}
+
+fn dummy_orchard_spend(rng: &mut OsRng) -> (OrchardSpend, OrchardSpendAuthorization) {
+    let sk = random_orchard_spending_key(rng);
+    let fvk = FullViewingKey::from(&sk);
+    let recipient = fvk.address_at(0u32, Scope::External);
+
+    // A canonical encoding is always a valid nullifier and Merkle hash.
+    let rho = Nullifier::from_bytes(&pallas::Base::random(&mut *rng).to_repr()).unwrap();
+    let note = random_orchard_note(recipient, orchard::value::NoteValue::from_raw(0), rho, rng);
+    // The Merkle path of a note of zero value is not checked by the circuit.
+    let auth_path = [(); ORCHARD_MERKLE_DEPTH as usize].map(|_| {
+        MerkleHashOrchard::from_bytes(&pallas::Base::random(&mut *rng).to_repr()).unwrap()
+    });
+
+    (
+        OrchardSpend {
+            fvk,
+            note,
+            merkle_path: orchard::tree::MerklePath::from_parts(rng.next_u32(), auth_path),
+        },
+        OrchardSpendAuthorization::Dummy(SpendAuthorizingKey::from(&sk)),
+    )
+}
+
+fn dummy_orchard_output(rng: &mut OsRng) -> OrchardOutput {
+    let sk = random_orchard_spending_key(rng);
+
+    OrchardOutput {
+        ovk: None,
+        to: FullViewingKey::from(&sk).address_at(0u32, Scope::External),
+        value: orchard::value::NoteValue::from_raw(0),
+        memo: *MemoBytes::empty().as_array(),
+    }
+}
+
+/// A proven v5 transaction, whose spend authorization signatures and transparent
+/// `scriptSig`s are still missing.
+///
+/// The transaction is stored with placeholder signatures, and its binding signatures are
+/// final. The randomizers of the spends are kept to create their signatures, and the
+/// output notes are kept so that a signer can check what the transaction pays.
+pub(crate) struct UnauthorizedTransaction {
+    consensus_branch_id: BranchId,
+    transaction: Vec<u8>,
+    coins: Vec<TxOut>,
+    /// The index of each Sapling spend in the unproven transaction, with its randomizer.
+    sapling_alphas: Vec<(usize, jubjub::Fr)>,
+    sapling_notes: Vec<Note>,
+    /// One entry per action, `None` for the dummy spends, which are already signed.
+    orchard_alphas: Vec<Option<(usize, pallas::Scalar)>>,
+    orchard_notes: Vec<orchard::Note>,
+}
+
+impl UnauthorizedTransaction {
+    fn transaction(&self) -> ZcashResult<Transaction> {
+        Ok(Transaction::read(
+            &self.transaction[..],
+            self.consensus_branch_id,
+        )?)
+    }
+
+    /// Returns the signature hash of the Sapling and Orchard spends.
+    pub(crate) fn shielded_sighash(&self) -> ZcashResult<[u8; 32]> {
+        let tx = self.transaction()?;
+        Ok(shielded_sighash(&tx, &self.coins))
+    }
+
+    /// Returns the `SIGHASH_ALL` signature hash of the P2PKH input at `index`.
+    pub(crate) fn transparent_sighash(&self, index: usize) -> ZcashResult<[u8; 32]> {
+        let coin = self.coins.get(index).ok_or("Input index out of range")?;
+
+        let tx = self.transaction()?;
+        let tx_data = transparent_signing::signing_data(&tx, &self.coins);
+        let txid_parts = tx_data.digest(TxIdDigester);
+
+        let sighash = signature_hash(
+            &tx_data,
+            &SignableInput::Transparent {
+                hash_type: SIGHASH_ALL,
+                index,
+                script_code: &coin.script_pubkey,
+                script_pubkey: &coin.script_pubkey,
+                value: coin.value,
+            },
+            &txid_parts,
+        );
+
+        Ok(*sighash.as_ref())
+    }
+
+    /// Returns the index in the unproven transaction and the spend authorization
+    /// randomizer of each Sapling spend, in the order of the bundle.
+    pub(crate) fn sapling_alphas(&self) -> &[(usize, jubjub::Fr)] {
+        &self.sapling_alphas
+    }
+
+    /// Returns the index in the unproven transaction and the spend authorization
+    /// randomizer of each Orchard action, or `None` for the actions whose spend is a dummy.
+    pub(crate) fn orchard_alphas(&self) -> &[Option<(usize, pallas::Scalar)>] {
+        &self.orchard_alphas
+    }
+
+    /// Checks that the transaction spends `inputs`, pays exactly `transparent_outputs`,
+    /// `sapling_outputs` and `orchard_outputs`, and pays `fee`.
+    ///
+    /// The shielded outputs that were not requested must be dummies of zero value.
+    pub(crate) fn check_payments(
+        &self,
+        inputs: &[(OutPoint, TxOut)],
+        transparent_outputs: &[TxOut],
+        sapling_outputs: &[(PaymentAddress, NoteValue)],
+        orchard_outputs: &[(Address, orchard::value::NoteValue)],
+        fee: Amount,
+    ) -> ZcashResult<()> {
+        let tx = self.transaction()?;
+
+        let (vin, vout) = tx
+            .transparent_bundle()
+            .map_or((&[][..], &[][..]), |bundle| {
+                (&bundle.vin[..], &bundle.vout[..])
+            });
+        if vin.len() != inputs.len()
+            || vin
+                .iter()
+                .zip(inputs)
+                .any(|(txin, (prevout, _))| txin.prevout != *prevout)
+            || self.coins.iter().ne(inputs.iter().map(|(_, coin)| coin))
+            || vout != transparent_outputs
+        {
+            return Err(
+                "The transparent inputs or outputs of the transaction were modified".into(),
+            );
+        }
+
+        let sapling_bundle_outputs = tx
+            .sapling_bundle()
+            .map_or(&[][..], |bundle| bundle.shielded_outputs());
+        if sapling_bundle_outputs.len() != self.sapling_notes.len()
+            || sapling_bundle_outputs
+                .iter()
+                .zip(self.sapling_notes.iter())
+                .any(|(output, note)| *output.cmu() != note.cmu())
+            || !pays_exactly(
+                self.sapling_notes
+                    .iter()
+                    .map(|note| (note.recipient(), note.value().inner())),
+                sapling_outputs
+                    .iter()
+                    .map(|(to, value)| (*to, value.inner())),
+            )
+        {
+            return Err("The Sapling outputs of the transaction were modified".into());
+        }
+
+        let orchard_actions: Vec<_> = tx
+            .orchard_bundle()
+            .map_or_else(Vec::new, |bundle| bundle.actions().iter().collect());
+        if orchard_actions.len() != self.orchard_notes.len()
+            || orchard_actions
+                .iter()
+                .zip(self.orchard_notes.iter())
+                .any(|(action, note)| {
+                    *action.nullifier() != note.rho()
+                        || *action.cmx() != ExtractedNoteCommitment::from(note.commitment())
+                })
+            || !pays_exactly(
+                self.orchard_notes
+                    .iter()
+                    .map(|note| (note.recipient(), note.value().inner())),
+                orchard_outputs
+                    .iter()
+                    .map(|(to, value)| (*to, value.inner())),
+            )
+        {
+            return Err("The Orchard outputs of the transaction were modified".into());
+        }
+
+        let tx_fee = self
+            .coins
+            .iter()
+            .map(|coin| i64::from(coin.value))
+            .sum::<i64>()
+            - vout.iter().map(|txout| i64::from(txout.value)).sum::<i64>()
+            + i64::from(tx.sapling_value_balance())
+            + tx.orchard_bundle()
+                .map_or(0, |bundle| i64::from(*bundle.value_balance()));
+        if tx_fee != i64::from(fee) {
+            return Err(format!(
+                "The transaction pays a fee of {} zatoshis instead of {}",
+                tx_fee,
+                i64::from(fee)
+            )
+            .into());
+        }
+
+        Ok(())
+    }
+
+    /// Adds the `scriptSig` of each transparent input and the spend authorization
+    /// signature of each Sapling spend and each non-dummy Orchard spend.
+    ///
+    /// The spend authorization signatures are checked against the randomized keys of
+    /// the spends.
+    pub(crate) fn authorize(
+        &self,
+        script_sigs: Vec<Script>,
+        sapling_sigs: Vec<redjubjub::Signature>,
+        orchard_sigs: Vec<redpallas::Signature<SpendAuth>>,
+    ) -> ZcashResult<Transaction> {
+        let expected_orchard_sigs = self.orchard_alphas.iter().flatten().count();
+        for (expected, got) in [
+            (self.coins.len(), script_sigs.len()),
+            (self.sapling_alphas.len(), sapling_sigs.len()),
+            (expected_orchard_sigs, orchard_sigs.len()),
+        ] {
+            if expected != got {
+                return Err(ZcashError::ArrayLengthMismatch {
+                    expected: expected as u64,
+                    got: got as u64,
+                });
+            }
+        }
+
+        let tx = self.transaction()?;
+        let sighash = shielded_sighash(&tx, &self.coins);
+
+        let transparent_bundle = tx.transparent_bundle().map(|bundle| transparent::Bundle {
+            vin: bundle
+                .vin
+                .iter()
+                .zip(script_sigs)
+                .map(|(txin, script_sig)| TxIn {
+                    prevout: txin.prevout.clone(),
+                    script_sig,
+                    sequence: txin.sequence,
+                })
+                .collect(),
+            vout: bundle.vout.clone(),
+            authorization: transparent::Authorized,
+        });
+
+        let sapling_bundle = tx
+            .sapling_bundle()
+            .map(|bundle| -> ZcashResult<_> {
+                let spends = bundle
+                    .shielded_spends()
+                    .iter()
+                    .zip(sapling_sigs)
+                    .map(|(spend, sig)| {
+                        let mut data_to_be_signed = [0u8; 64];
+                        data_to_be_signed[..32].copy_from_slice(&spend.rk().0.to_bytes());
+                        data_to_be_signed[32..].copy_from_slice(&sighash);
+                        if !spend
+                            .rk()
+                            .verify(&data_to_be_signed, &sig, SPENDING_KEY_GENERATOR)
+                        {
+                            return Err("Invalid Sapling spend authorization signature".into());
+                        }
+
+                        Ok(SpendDescription::temporary_zcashd_from_parts(
+                            spend.cv().clone(),
+                            *spend.anchor(),
+                            *spend.nullifier(),
+                            spend.rk().clone(),
+                            *spend.zkproof(),
+                            sig,
+                        ))
+                    })
+                    .collect::<ZcashResult<Vec<_>>>()?;
+
+                Ok(sapling::Bundle::temporary_zcashd_from_parts(
+                    spends,
+                    bundle.shielded_outputs().to_vec(),
+                    *bundle.value_balance(),
+                    *bundle.authorization(),
+                ))
+            })
+            .transpose()?;
+
+        let orchard_bundle = tx
+            .orchard_bundle()
+            .map(|bundle| -> ZcashResult<_> {
+                let mut orchard_sigs = orchard_sigs.into_iter();
+                let actions = bundle
+                    .actions()
+                    .iter()
+                    .zip(self.orchard_alphas.iter())
+                    .map(|(action, alpha)| {
+                        let sig = match alpha {
+                            Some(_) => orchard_sigs.next().expect("The signatures were counted"),
+                            None => action.authorization().clone(),
+                        };
+                        if action.rk().verify(&sighash, &sig).is_err() {
+                            return Err("Invalid Orchard spend authorization signature".into());
+                        }
+
+                        Ok(action.clone().map(|_| sig))
+                    })
+                    .collect::<ZcashResult<Vec<_>>>()?;
+
+                Ok(orchard::Bundle::from_parts(
+                    NonEmpty::from_vec(actions).expect("The bundle has actions"),
+                    *bundle.flags(),
+                    *bundle.value_balance(),
+                    *bundle.anchor(),
+                    bundle.authorization().clone(),
+                ))
+            })
+            .transpose()?;
+
+        Ok(TransactionData::<Authorized>::from_parts(
+            tx.version(),
+            tx.consensus_branch_id(),
+            tx.lock_time(),
+            tx.expiry_height(),
+            transparent_bundle,
+            None,
+            sapling_bundle,
+            orchard_bundle,
+        )
+        .freeze()?)
+    }
+
+    pub(crate) fn write<W: Write>(&self, mut w: W) -> ZcashResult<()> {
+        write_u32(&mut w, self.consensus_branch_id.into())?;
+        write_u32(&mut w, self.transaction.len().try_into()?)?;
+        w.write_all(&self.transaction)?;
+
+        write_u32(&mut w, self.coins.len().try_into()?)?;
+        for coin in self.coins.iter() {
+            coin.write(&mut w)?;
+        }
+
+        write_u32(&mut w, self.sapling_alphas.len().try_into()?)?;
+        for (index, alpha) in self.sapling_alphas.iter() {
+            write_u32(&mut w, (*index).try_into()?)?;
+            w.write_all(&alpha.to_bytes())?;
+        }
+
+        write_u32(&mut w, self.sapling_notes.len().try_into()?)?;
+        for note in self.sapling_notes.iter() {
+            write_note(&mut w, note)?;
+        }
+
+        write_u32(&mut w, self.orchard_alphas.len().try_into()?)?;
+        for alpha in self.orchard_alphas.iter() {
+            match alpha {
+                Some((index, alpha)) => {
+                    write_u8(&mut w, 1)?;
+                    write_u32(&mut w, (*index).try_into()?)?;
+                    w.write_all(&alpha.to_repr())?;
+                }
+                None => write_u8(&mut w, 0)?,
+            }
+        }
+
+        write_u32(&mut w, self.orchard_notes.len().try_into()?)?;
+        for note in self.orchard_notes.iter() {
+            write_orchard_note(&mut w, note)?;
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn read(r: &mut &[u8]) -> ZcashResult<Self> {
+        let consensus_branch_id = BranchId::try_from(read_u32(r)?)?;
+
+        let mut transaction = vec![0u8; read_u32(r)?.try_into()?];
+        io::Read::read_exact(r, &mut transaction)?;
+
+        let coins = (0..read_u32(r)?)
+            .map(|_| TxOut::read(&mut *r))
+            .collect::<io::Result<Vec<_>>>()?;
+
+        let sapling_alphas = (0..read_u32(r)?)
+            .map(|_| -> ZcashResult<(usize, jubjub::Fr)> {
+                let index = read_u32(r)? as usize;
+                Option::from(jubjub::Fr::from_bytes(&read_array(r)?))
+                    .map(|alpha| (index, alpha))
+                    .ok_or_else(|| "Invalid Sapling randomizer".into())
+            })
+            .collect::<ZcashResult<Vec<_>>>()?;
+
+        let sapling_notes = (0..read_u32(r)?)
+            .map(|_| read_note(r))
+            .collect::<ZcashResult<Vec<_>>>()?;
+
+        let orchard_alphas = (0..read_u32(r)?)
+            .map(|_| -> ZcashResult<Option<(usize, pallas::Scalar)>> {
+                match read_u8(r)? {
+                    0 => Ok(None),
+                    1 => {
+                        let index = read_u32(r)? as usize;
+                        Option::from(pallas::Scalar::from_repr(read_array(r)?))
+                            .map(|alpha| Some((index, alpha)))
+                            .ok_or_else(|| "Invalid Orchard randomizer".into())
+                    }
+                    other => Err(format!("Invalid randomizer flag: {}", other).into()),
+                }
+            })
+            .collect::<ZcashResult<Vec<_>>>()?;
+
+        let orchard_notes = (0..read_u32(r)?)
+            .map(|_| read_orchard_note(r))
+            .collect::<ZcashResult<Vec<_>>>()?;
+
+        Ok(Self {
+            consensus_branch_id,
+            transaction,
+            coins,
+            sapling_alphas,
+            sapling_notes,
+            orchard_alphas,
+            orchard_notes,
+        })
+    }
+}
+
+/// Returns whether `notes` pay each of `expected` once, the other notes having zero value.
+fn pays_exactly<A: PartialEq>(
+    notes: impl Iterator<Item = (A, u64)>,
+    expected: impl Iterator<Item = (A, u64)>,
+) -> bool {
+    let mut remaining: Vec<(A, u64)> = expected.collect();
+    for (to, value) in notes {
+        match remaining.iter().position(|(expected_to, expected_value)| {
+            *expected_to == to && *expected_value == value
+        }) {
+            Some(index) => {
+                remaining.swap_remove(index);
+            }
+            None if value == 0 => {}
+            None => return false,
+        }
+    }
+    remaining.is_empty()
+}
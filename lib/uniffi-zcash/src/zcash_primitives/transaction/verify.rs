@@ -0,0 +1,152 @@
+use std::sync::Arc;
+
+use bellman::groth16::Proof;
+use group::GroupEncoding;
+use zcash_primitives::{
+    consensus::{BlockHeight, BranchId, NetworkUpgrade, Parameters},
+    transaction::components::{amount::BalanceError, TxOut},
+};
+use zcash_proofs::sapling::SaplingVerificationContext;
+
+use crate::{
+    ZcashBlockHeight, ZcashConsensusParameters, ZcashResult, ZcashSaplingVerifyingKeys,
+    ZcashTransaction, ZcashTxOut, ZcashVerifyingKey,
+};
+
+/// The outcome of each check performed by [`ZcashTransaction::verify`].
+///
+/// Checks on a bundle that is absent from the transaction pass trivially.
+pub struct ZcashVerificationReport {
+    /// The consensus branch ID matches the one active at the verification height.
+    pub consensus_branch_id: bool,
+    /// The transaction has not expired at the verification height.
+    pub expiry: bool,
+    /// The inputs cover the outputs, so the transaction pays a non-negative fee.
+    pub value_balance: bool,
+    /// The Groth16 proofs and spend authorization signatures of the Sapling spends are valid.
+    pub sapling_spends: bool,
+    /// The Groth16 proofs of the Sapling outputs are valid.
+    pub sapling_outputs: bool,
+    /// The Sapling binding signature is valid.
+    pub sapling_binding_signature: bool,
+    /// The Orchard Halo 2 proof is valid.
+    pub orchard_proof: bool,
+    /// The Orchard spend authorization signatures and binding signature are valid.
+    pub orchard_signatures: bool,
+}
+
+impl ZcashTransaction {
+    /// Verifies the transaction as if it were to be mined at `height`.
+    ///
+    /// `prevouts` are the outputs spent by the transparent inputs, in the order of the inputs.
+    /// Transparent signatures are not checked, as they require a script interpreter.
+    pub fn verify(
+        &self,
+        params: ZcashConsensusParameters,
+        height: Arc<ZcashBlockHeight>,
+        prevouts: Vec<Arc<ZcashTxOut>>,
+        sapling_keys: Arc<ZcashSaplingVerifyingKeys>,
+        orchard_key: Arc<ZcashVerifyingKey>,
+    ) -> ZcashResult<ZcashVerificationReport> {
+        let height: BlockHeight = height.as_ref().into();
+        let sighash: [u8; 32] = self
+            .shielded_sighash(prevouts.clone())?
+            .try_into()
+            .expect("signature hashes are 32 bytes");
+
+        let consensus_branch_id =
+            self.0.consensus_branch_id() == BranchId::for_height(&params, height);
+
+        let expiry_height = self.0.expiry_height();
+        let expiry = expiry_height == BlockHeight::from_u32(0) || height <= expiry_height;
+
+        let mut prevout_values = prevouts
+            .iter()
+            .map(|prevout| TxOut::from(prevout.as_ref()).value);
+        let value_balance = self
+            .0
+            .fee_paid(|_| prevout_values.next().ok_or(BalanceError::Underflow))
+            .is_ok_and(|fee| !fee.is_negative());
+
+        let (sapling_spends, sapling_outputs, sapling_binding_signature) =
+            match self.0.sapling_bundle() {
+                None => (true, true, true),
+                Some(bundle) => {
+                    let mut ctx = SaplingVerificationContext::new(
+                        params.is_nu_active(NetworkUpgrade::Nu5, height),
+                    );
+
+                    let spends = bundle.shielded_spends().iter().fold(true, |valid, spend| {
+                        let spend_valid = Proof::read(&spend.zkproof()[..]).is_ok_and(|proof| {
+                            ctx.check_spend(
+                                spend.cv(),
+                                *spend.anchor(),
+                                &spend.nullifier().0,
+                                spend.rk().clone(),
+                                &sighash,
+                                *spend.spend_auth_sig(),
+                                proof,
+                                &sapling_keys.spend_pvk,
+                            )
+                        });
+                        spend_valid && valid
+                    });
+
+                    let outputs = bundle
+                        .shielded_outputs()
+                        .iter()
+                        .fold(true, |valid, output| {
+                            let epk = jubjub::ExtendedPoint::from_bytes(&output.ephemeral_key().0);
+                            let output_valid = Option::from(epk)
+                                .zip(Proof::read(&output.zkproof()[..]).ok())
+                                .is_some_and(|(epk, proof)| {
+                                    ctx.check_output(
+                                        output.cv(),
+                                        *output.cmu(),
+                                        epk,
+                                        proof,
+                                        &sapling_keys.output_pvk,
+                                    )
+                                });
+                            output_valid && valid
+                        });
+
+                    let binding_signature = ctx.final_check(
+                        *bundle.value_balance(),
+                        &sighash,
+                        bundle.authorization().binding_sig,
+                    );
+
+                    (spends, outputs, binding_signature)
+                }
+            };
+
+        let (orchard_proof, orchard_signatures) = match self.0.orchard_bundle() {
+            None => (true, true),
+            Some(bundle) => {
+                let proof = bundle.verify_proof(&orchard_key.0).is_ok();
+                let signatures = bundle
+                    .actions()
+                    .iter()
+                    .all(|action| action.rk().verify(&sighash, action.authorization()).is_ok())
+                    && bundle
+                        .binding_validating_key()
+                        .verify(&sighash, bundle.authorization().binding_signature())
+                        .is_ok();
+
+                (proof, signatures)
+            }
+        };
+
+        Ok(ZcashVerificationReport {
+            consensus_branch_id,
+            expiry,
+            value_balance,
+            sapling_spends,
+            sapling_outputs,
+            sapling_binding_signature,
+            orchard_proof,
+            orchard_signatures,
+        })
+    }
+}
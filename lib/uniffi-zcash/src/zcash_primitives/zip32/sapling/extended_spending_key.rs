@@ -1,14 +1,16 @@
 use std::sync::Arc;
 
+use rand::rngs::OsRng;
 use zcash_client_backend::encoding;
 use zcash_primitives::{
     consensus::Parameters,
+    sapling::{redjubjub::PrivateKey, spend_sig},
     zip32::{ChildIndex, ExtendedSpendingKey},
 };
 
 use crate::{
-    ZcashChildIndex, ZcashConsensusParameters, ZcashDiversifiableFullViewingKey,
-    ZcashDiversifierIndexAndPaymentAddress, ZcashError, ZcashResult,
+    utils::cast_slice, ZcashChildIndex, ZcashConsensusParameters, ZcashDiversifiableFullViewingKey,
+    ZcashDiversifierIndexAndPaymentAddress, ZcashError, ZcashProofGenerationKey, ZcashResult,
 };
 
 use derive_more::{From, Into};
@@ -73,4 +75,29 @@ pub fn derive_internal(&self) -> Arc<Self> {
     pub fn to_diversifiable_full_viewing_key(&self) -> Arc<ZcashDiversifiableFullViewingKey> {
         Arc::new(self.0.to_diversifiable_full_viewing_key().into())
     }
+
+    /// Returns the key needed to create proofs for the notes received with this key,
+    /// without being able to authorize spending them.
+    ///
+    /// The proof generation key of the internal (change) scope is obtained from
+    /// [`Self::derive_internal`].
+    pub fn proof_generation_key(&self) -> Arc<ZcashProofGenerationKey> {
+        Arc::new(self.0.expsk.proof_generation_key().into())
+    }
+
+    /// Creates the spend authorization signature of a Sapling spend, given the shielded
+    /// signature hash of the transaction and the randomizer `alpha` of the spend.
+    ///
+    /// This is what a [`crate::ZcashExternalSigner`] holding the key returns from
+    /// `sign_sapling`.
+    pub fn sign_spend_auth(&self, sighash: Vec<u8>, alpha: Vec<u8>) -> ZcashResult<Vec<u8>> {
+        let sighash: [u8; 32] = cast_slice(&sighash)?;
+        let alpha = Option::from(jubjub::Fr::from_bytes(&cast_slice(&alpha)?))
+            .ok_or("Invalid Sapling randomizer")?;
+
+        let signature = spend_sig(PrivateKey(self.0.expsk.ask), alpha, &sighash, &mut OsRng);
+        let mut data = Vec::with_capacity(64);
+        signature.write(&mut data)?;
+        Ok(data)
+    }
 }
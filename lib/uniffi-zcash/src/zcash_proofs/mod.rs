@@ -1,2 +1,5 @@
 mod prover;
 pub use self::prover::*;
+
+mod verifier;
+pub use self::verifier::*;
@@ -0,0 +1,56 @@
+use std::path::Path;
+
+use bellman::groth16::{PreparedVerifyingKey, VerifyingKey};
+use bls12_381::Bls12;
+use zcash_proofs::{default_params_folder, load_parameters, parse_parameters, ZcashParameters};
+
+use crate::ZcashResult;
+
+/// The Groth16 verifying keys of the Sapling spend and output circuits.
+pub struct ZcashSaplingVerifyingKeys {
+    pub(crate) spend_vk: VerifyingKey<Bls12>,
+    pub(crate) spend_pvk: PreparedVerifyingKey<Bls12>,
+    pub(crate) output_vk: VerifyingKey<Bls12>,
+    pub(crate) output_pvk: PreparedVerifyingKey<Bls12>,
+}
+
+impl ZcashSaplingVerifyingKeys {
+    /// Loads the verifying keys from the Sapling parameters at the given local paths.
+    ///
+    /// This function will panic if the paths do not point to valid parameter files with
+    /// the expected hashes.
+    pub fn new(spend_path: &str, output_path: &str) -> Self {
+        load_parameters(Path::new(spend_path), Path::new(output_path), None).into()
+    }
+
+    /// Loads the verifying keys from Sapling parameters specified as byte arrays.
+    pub fn from_bytes(spend_param_bytes: &[u8], output_param_bytes: &[u8]) -> Self {
+        parse_parameters(spend_param_bytes, output_param_bytes, None).into()
+    }
+
+    /// Attempts to load the verifying keys from the Sapling parameters in the default
+    /// local location.
+    pub fn with_default_location() -> ZcashResult<Self> {
+        let params_dir =
+            default_params_folder().ok_or("Parameters cannot be found in default location")?;
+        let spend_path = params_dir.join("sapling-spend.params");
+        let output_path = params_dir.join("sapling-output.params");
+
+        if !(spend_path.exists() && output_path.exists()) {
+            return Err("Parameters cannot be found in default location".into());
+        }
+
+        Ok(load_parameters(&spend_path, &output_path, None).into())
+    }
+}
+
+impl From<ZcashParameters> for ZcashSaplingVerifyingKeys {
+    fn from(params: ZcashParameters) -> Self {
+        ZcashSaplingVerifyingKeys {
+            spend_vk: params.spend_params.vk,
+            spend_pvk: params.spend_vk,
+            output_vk: params.output_params.vk,
+            output_pvk: params.output_vk,
+        }
+    }
+}
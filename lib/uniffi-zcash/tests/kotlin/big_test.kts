@@ -572,6 +572,31 @@ fun testUnifiedAddressCreationWithOrchard() {
 }
 testUnifiedAddressCreationWithOrchard()
 
+fun testUnifiedAddressReceivers() {
+    val seed = supp.getAsU8Array("seed")
+    val params = ZcashConsensusParameters.MAIN_NETWORK
+
+    val unifiedSpendingKey = ZcashUnifiedSpendingKey.fromSeed(params, seed, ZcashAccountId(0u))
+    val unifiedAddress = unifiedSpendingKey.toUnifiedFullViewingKey().defaultAddress().address
+
+    val receivers = unifiedAddress.receivers(params)
+    assert(receivers.map { it.receiverType } == unifiedAddress.receiverTypes())
+    assert(receivers.first().receiverType == ZcashReceiverType.ORCHARD)
+
+    val orchardOnly = ZcashUnifiedAddress.decode(params, receivers.first().address)
+    assert(orchardOnly.receiverTypes() == listOf(ZcashReceiverType.ORCHARD))
+    assert(orchardOnly.encode(params) == unifiedAddress.subset(listOf(ZcashReceiverType.ORCHARD)).encode(params))
+
+    var thrown = false
+    try {
+        unifiedAddress.subset(listOf(ZcashReceiverType.TRANSPARENT))
+    } catch (e: ZcashException.Message) {
+        thrown = true
+    }
+    assert(thrown)
+}
+testUnifiedAddressReceivers()
+
 
 
 fun testDecodeExtendedFullViewingKey() {
@@ -922,6 +947,27 @@ fun testAccountPrivKeyDeriveExternalSecretKey() {
 }
 testAccountPrivKeyDeriveExternalSecretKey()
 
+fun testTransparentMessageSigning() {
+    val seed = supp.getAsU8Array("seed")
+
+    val key = ZcashAccountPrivKey.fromSeed(
+        ZcashConsensusParameters.MAIN_NETWORK,
+        seed,
+        ZcashAccountId(0u),
+    )
+
+    val secretKey = key.deriveExternalSecretKey(0u)
+    val publicKey = SecpPublicKey.fromSecretKey(secretKey)
+    val address = publicKey.toTransparentAddress()
+
+    val signature = secretKey.signMessage("withdrawal 42")
+
+    assert(address.verifyMessage("withdrawal 42", signature))
+    assert(!address.verifyMessage("withdrawal 43", signature))
+    assert(SecpPublicKey.recoverFromMessage("withdrawal 42", signature).serialize() == publicKey.serialize())
+}
+testTransparentMessageSigning()
+
 fun testAccountPrivKeyDeriveInternalSecretKey() {
     val seed = supp.getAsU8Array("seed")
 
@@ -1177,6 +1223,106 @@ fun testMemoBytesEmpty() {
 }
 testMemoBytesEmpty()
 
+fun testMemoContent() {
+    val address = "zs1mrhc9y7jdh5r9ece8u5khgvj9kg0zgkxzdduyv0whkg7lkcrkx5xqem3e48avjq9wn2rukydkwn"
+    val memo = ZcashMemoBytes.fromContent(ZcashMemoContent.Text("Thanks!", address))
+
+    assert(memo.format() == ZcashMemoFormat.TEXT)
+    assert(memo.data() == "Thanks!\nReply-To:\n$address".toByteArray().map { it.toUByte() })
+    assert(memo.content() == ZcashMemoContent.Text("Thanks!", address))
+    assert(ZcashMemoBytes.empty().format() == ZcashMemoFormat.EMPTY)
+
+    val arbitrary = ZcashMemoBytes.fromContent(ZcashMemoContent.Arbitrary(listOf(1u, 2u)))
+    assert(arbitrary.format() == ZcashMemoFormat.ARBITRARY)
+    assert(arbitrary.content() == ZcashMemoContent.Arbitrary(listOf(1u, 2u)))
+}
+testMemoContent()
+
+fun testMemoTextTooLong() {
+    var thrown = false;
+    try {
+        ZcashMemoBytes.fromMemo(ZcashMemo.Text("x".repeat(513)))
+    } catch (e: ZcashException.MemoException) {
+        thrown = true;
+    }
+    assert(thrown)
+}
+testMemoTextTooLong()
+
+fun testQrCodeFromAddress() {
+    val address = "zs1mrhc9y7jdh5r9ece8u5khgvj9kg0zgkxzdduyv0whkg7lkcrkx5xqem3e48avjq9wn2rukydkwn"
+    val qr = ZcashQrCode.fromAddress(address, ZcashQrErrorCorrection.MEDIUM)
+    val options = ZcashQrRenderOptions(4u, true)
+
+    assert(qr.modules().size == qr.width().toInt())
+    assert(qr.modules().all { it.size == qr.width().toInt() })
+    assert(qr.toSvg(options).contains("width=\"${(qr.width() + 8u) * 4u}\""))
+    assert(qr.toPng(options).take(4) == listOf(0x89u, 0x50u, 0x4Eu, 0x47u).map { it.toUByte() })
+
+    for (size in listOf(0u, 1000u, UInt.MAX_VALUE)) {
+        try {
+            qr.toPng(ZcashQrRenderOptions(size, true))
+            assert(false)
+        } catch (e: ZcashException.Message) {}
+        try {
+            qr.toSvg(ZcashQrRenderOptions(size, false))
+            assert(false)
+        } catch (e: ZcashException.Message) {}
+    }
+}
+testQrCodeFromAddress()
+
+fun testParseAddress() {
+    val parsed = parseAddress("zs1mrhc9y7jdh5r9ece8u5khgvj9kg0zgkxzdduyv0whkg7lkcrkx5xqem3e48avjq9wn2rukydkwn")
+
+    assert(parsed.network() == ZcashAddressNetwork.MAIN)
+    assert(parsed.kind() == ZcashAddressKind.SAPLING)
+    assert(parsed.typecodes() == listOf(2u))
+    assert(parsed.sapling() != null)
+    assert(parsed.orchard() == null)
+    assert(parsed.transparent() == null)
+
+    val tex = parseAddress("tex1s2rt77ggv6q989lr49rkgzmh5slsksa9khdgte")
+    assert(tex.kind() == ZcashAddressKind.TEX)
+    assert(tex.transparent()!!.encode(ZcashConsensusParameters.MAIN_NETWORK) == "t1VmmGiyjVNeCjxDZzg7vZmd99WyzVby9yC")
+}
+testParseAddress()
+
+fun testParseAddressErrors() {
+    try {
+        parseAddress("zs1mrhc9y7jdh5r9ece8u5khgvj9kg0zgkxzdduyv0whkg7lkcrkx5xqem3e48avjq9wn2rukydkwm")
+        assert(false)
+    } catch (e: ZcashAddressParseException.InvalidChecksum) {}
+
+    try {
+        parseAddress("bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq")
+        assert(false)
+    } catch (e: ZcashAddressParseException.UnknownHrp) {
+        assert(e.hrp == "bc")
+    }
+
+    try {
+        parseAddress("1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa")
+        assert(false)
+    } catch (e: ZcashAddressParseException.UnknownPrefix) {}
+}
+testParseAddressErrors()
+
+fun testTexAddress() {
+    val params = ZcashConsensusParameters.MAIN_NETWORK
+    val transparent = ZcashTransparentAddress.decode(params, "t1VmmGiyjVNeCjxDZzg7vZmd99WyzVby9yC")
+    val tex = ZcashTexAddress.fromTransparentAddress(transparent)
+
+    assert(tex.encode(params) == "tex1s2rt77ggv6q989lr49rkgzmh5slsksa9khdgte")
+    assert(ZcashTexAddress.decode(params, tex.encode(params)).toTransparentAddress().encode(params) == transparent.encode(params))
+    assert(ZcashRecipientAddress.decode(params, tex.encode(params)).isTransparentSourceOnly())
+
+    val request = ZcashTransactionRequest.fromUri(params, "zcash:${tex.encode(params)}?amount=1")
+    assert(request.payments().single().recipientAddress.isTransparentSourceOnly())
+    assert(request.toUri(params) == "zcash:${tex.encode(params)}?amount=1")
+}
+testTexAddress()
+
 
 
 fun testDiversifierNew() {
@@ -1842,8 +1988,142 @@ fun testExtendedSpendingKeyToDiversifiableFullViewingKey() {
 }
 testExtendedSpendingKeyToDiversifiableFullViewingKey()
 
+fun testCommitmentTreeAndWitnessSerialization() {
+    val key = ZcashUnifiedSpendingKey.fromBytes(ZcashKeysEra.ORCHARD, supp.getAsU8Array("unified_spending_key"))
+    val paymentAddress = key.sapling().defaultAddress().address
+    val note = paymentAddress.createNote(200u, ZcashRseed.AfterZip212(List(32) { 0u }))
+
+    val tree = ZcashCommitmentTree.empty()
+    tree.append(ZcashSaplingNode.fromCmu(note.cmu()))
+    val witness = ZcashIncrementalWitness.fromTree(tree)
+
+    val parsedTree = ZcashCommitmentTree.fromBytes(tree.toBytes())
+    assert(parsedTree.toBytes() == tree.toBytes())
+    assert(parsedTree.size() == 1.toULong())
+
+    val parsedWitness = ZcashIncrementalWitness.fromBytes(witness.toBytes())
+    assert(parsedWitness.toBytes() == witness.toBytes())
+    assert(parsedWitness.witnessedPosition() == 0.toULong())
+    assert(parsedWitness.root().toBytes() == tree.root().toBytes())
+}
+testCommitmentTreeAndWitnessSerialization()
+
+fun testOrchardCommitmentTreeAndWitness() {
+    val key = ZcashUnifiedSpendingKey.fromBytes(ZcashKeysEra.ORCHARD, supp.getAsU8Array("unified_spending_key"))
+    val fvk = key.toUnifiedFullViewingKey().orchard()!!
+    val address = fvk.toIvk(ZcashOrchardScope.INTERNAL).address(ZcashOrchardDiversifier.fromBytes(List(11) { 0u }))
+    val rho = ZcashOrchardNullifier.fromBytes(List(32) { 0u })
+    val note = ZcashOrchardNote.fromParts(address, ZcashOrchardNoteValue.fromRaw(15u), rho, ZcashOrchardRandomSeed.fromBytes(List(32) { 0u }, rho))
+    val cmx = note.commitment().toExtractedNoteCommitment()
+
+    val tree = ZcashOrchardCommitmentTree.empty()
+    assert(tree.size() == 0.toULong())
+    assert(ZcashOrchardIncrementalWitness.fromTree(tree).path() == null)
+    val emptyRoot = tree.root().toBytes()
+
+    tree.append(ZcashOrchardMerkleHash.fromCmx(cmx))
+    assert(tree.size() == 1.toULong())
+    assert(tree.root().toBytes() != emptyRoot)
+
+    val witness = ZcashOrchardIncrementalWitness.fromTree(tree)
+    assert(witness.witnessedPosition() == 0.toULong())
+    assert(witness.root().toBytes() == tree.root().toBytes())
+    assert(witness.path()!!.root(cmx).toBytes() == tree.root().toBytes())
+
+    // The witness follows the tree as leaves are appended after the witnessed note
+    val leaf = ZcashOrchardMerkleHash.fromBytes(List(32) { 0u })
+    tree.append(leaf)
+    witness.append(leaf)
+    assert(tree.size() == 2.toULong())
+    assert(witness.root().toBytes() == tree.root().toBytes())
+    assert(witness.path()!!.root(cmx).toBytes() == tree.root().toBytes())
+
+    val parsedTree = ZcashOrchardCommitmentTree.fromBytes(tree.toBytes())
+    assert(parsedTree.toBytes() == tree.toBytes())
+    assert(parsedTree.root().toBytes() == tree.root().toBytes())
+
+    val parsedWitness = ZcashOrchardIncrementalWitness.fromBytes(witness.toBytes())
+    assert(parsedWitness.toBytes() == witness.toBytes())
+    assert(parsedWitness.witnessedPosition() == 0.toULong())
+
+    val treeHex = tree.toBytes().joinToString("") { "%02x".format(it.toInt()) }
+    val treeState = ZcashTreeState("main", 2000000u, "00".repeat(32), 0u, "000000", treeHex)
+    assert(treeState.orchardTree().root().toBytes() == tree.root().toBytes())
+    assert(treeState.saplingTree().size() == 0.toULong())
+
+    try {
+        ZcashOrchardCommitmentTree.fromBytes(listOf(1u, 2u))
+        assert(false)
+    } catch (e: ZcashException) {}
+
+    try {
+        ZcashOrchardMerklePath.fromParts(0u, listOf(leaf))
+        assert(false)
+    } catch (e: ZcashException.ArrayLengthMismatch) {}
+}
+testOrchardCommitmentTreeAndWitness()
+
+
 
 
+fun testAmountArithmetic() {
+    val amount = ZcashAmount.parseZec("1.2345")
+    assert(amount.value() == 123450000L)
+    assert(amount.negate().value() == -123450000L)
+    assert(ZcashAmount.sum(listOf(amount, amount)).value() == 246900000L)
+    assert(amount.checkedSub(ZcashAmount(200000000)).value() == -76550000L)
+
+    var thrown = false;
+    try {
+        ZcashAmount(2100000000000000).checkedAdd(amount)
+    } catch (e: ZcashException.BalanceException) {
+        thrown = true;
+    }
+    assert(thrown)
+
+    thrown = false;
+    try {
+        ZcashNonNegativeAmount.zero().checkedSub(ZcashNonNegativeAmount.fromU64(1u))
+    } catch (e: ZcashException.BalanceException) {
+        thrown = true;
+    }
+    assert(thrown)
+
+    thrown = false;
+    try {
+        ZcashAmount.parseZec("1.123456789")
+    } catch (e: ZcashException.Message) {
+        thrown = true;
+    }
+    assert(thrown)
+}
+testAmountArithmetic()
+
+fun testAmountFormatting() {
+    val amount = ZcashAmount.parseZec("1.2345")
+    assert(amount.format(ZcashAmountUnit.ZEC, 2u, ZcashRounding.HALF_UP) == "1.23")
+    assert(amount.format(ZcashAmountUnit.MILLI_ZEC, 0u, ZcashRounding.DOWN) == "1234")
+    assert(amount.format(ZcashAmountUnit.MICRO_ZEC, 3u, ZcashRounding.DOWN) == "1234500.000")
+
+    val dust = ZcashAmount.parseZec("-0.00000005")
+    assert(dust.format(ZcashAmountUnit.ZEC, 7u, ZcashRounding.HALF_EVEN) == "0.0000000")
+    assert(dust.format(ZcashAmountUnit.ZEC, 7u, ZcashRounding.HALF_UP) == "-0.0000001")
+
+    assert(amount.format(ZcashAmountUnit.ZATOSHI, 2u, ZcashRounding.DOWN) == "123450000.00")
+    assert(amount.format(ZcashAmountUnit.ZEC, 38u, ZcashRounding.DOWN) == "1.2345" + "0".repeat(34))
+
+    try {
+        amount.format(ZcashAmountUnit.ZEC, 39u, ZcashRounding.DOWN)
+        assert(false)
+    } catch (e: ZcashException.Message) {}
+
+    // Whole parts too long for MAX_MONEY are rejected before they can overflow
+    try {
+        ZcashAmount.parseZec("99999999999")
+        assert(false)
+    } catch (e: ZcashException.ValueOutOfRange) {}
+}
+testAmountFormatting()
 
 class TransactionBuilderTest(supp: TestSupport) {
 
@@ -1981,6 +2261,174 @@ class TransactionBuilderTest(supp: TestSupport) {
         // The output of each Sapling transaction differs each time.
         // This asserts the size, as its deterministic.
         assert(result.transaction.toBytes().size == 2377)
+
+        val saplingKeys = ZcashSaplingVerifyingKeys.withDefaultLocation()
+        val orchardKey = ZcashVerifyingKey()
+        val report = result.transaction.verify(
+            ZcashConsensusParameters.MAIN_NETWORK, ZcashBlockHeight(2030820u), listOf(), saplingKeys, orchardKey)
+        assert(report.consensusBranchId && report.expiry && report.valueBalance)
+        assert(report.saplingSpends && report.saplingOutputs && report.saplingBindingSignature)
+        assert(report.orchardProof && report.orchardSignatures)
+
+        val expired = result.transaction.verify(
+            ZcashConsensusParameters.MAIN_NETWORK, ZcashBlockHeight(2030861u), listOf(), saplingKeys, orchardKey)
+        assert(!expired.expiry)
+        assert(expired.saplingSpends && expired.saplingBindingSignature)
+
+        // Between Canopy and NU5, a v4 transaction is verified without the ZIP 216 rules
+        val canopyBuilder = ZcashTransactionBuilder(ZcashConsensusParameters.MAIN_NETWORK, ZcashBlockHeight(1600000u))
+        canopyBuilder.addSaplingSpend(extsk, paymentAddress.diversifier(), note, witness.path()!!)
+        canopyBuilder.addSaplingOutput(ovk, paymentAddress, ZcashAmount(200), ZcashMemoBytes.empty())
+        val canopyTransaction = canopyBuilder.build(prover, feeRule).transaction
+        assert(canopyTransaction.version().selection() is ZcashTxVersionSelection.Sapling)
+
+        val canopy = canopyTransaction.verify(
+            ZcashConsensusParameters.MAIN_NETWORK, ZcashBlockHeight(1600000u), listOf(), saplingKeys, orchardKey)
+        assert(canopy.consensusBranchId && canopy.expiry && canopy.valueBalance)
+        assert(canopy.saplingSpends && canopy.saplingOutputs && canopy.saplingBindingSignature)
+    }
+
+    class ReplaySigner(signature: List<UByte>?) : ZcashExternalSigner {
+        val signature = signature
+        val requests = mutableListOf<UInt>()
+
+        override fun signTransparent(sighash: List<UByte>, inputIndex: UInt): List<UByte>? {
+            assert(sighash.size == 32)
+            requests.add(inputIndex)
+            return signature
+        }
+
+        override fun signSapling(sighash: List<UByte>, alpha: List<UByte>, spendIndex: UInt): List<UByte>? = null
+
+        override fun signOrchard(sighash: List<UByte>, alpha: List<UByte>, spendIndex: UInt): List<UByte>? = null
+    }
+
+    // Holds the shielded spending keys of an account, as a hardware wallet would
+    class ShieldedSigner(key: ZcashUnifiedSpendingKey?) : ZcashExternalSigner {
+        val key = key
+        val orchardRequests = mutableListOf<UInt>()
+
+        override fun signTransparent(sighash: List<UByte>, inputIndex: UInt): List<UByte>? = null
+
+        override fun signSapling(sighash: List<UByte>, alpha: List<UByte>, spendIndex: UInt): List<UByte>? =
+            key?.sapling()?.signSpendAuth(sighash, alpha)
+
+        override fun signOrchard(sighash: List<UByte>, alpha: List<UByte>, spendIndex: UInt): List<UByte>? {
+            orchardRequests.add(spendIndex)
+            return key?.orchard()?.signSpendAuth(sighash, alpha)
+        }
+    }
+
+    fun testTransparentWithExternalSigner(){
+        val expectedTransactionBytes = supp.getAsU8Array(
+            "transaction_non_standard_fee")
+
+        val key = ZcashUnifiedSpendingKey.fromBytes(ZcashKeysEra.ORCHARD, supp.getAsU8Array("unified_spending_key"))
+        val address = key.transparent().toAccountPubkey().deriveExternalIvk().deriveAddress(0u)
+        val pubkey = SecpPublicKey.fromSecretKey(key.transparent().deriveExternalSecretKey(0u)).serialize()
+
+        val prevCoin = ZcashTxOut(ZcashAmount(200), address.script())
+
+        fun builder(): ZcashTransactionBuilder {
+            val builder = ZcashTransactionBuilder(ZcashConsensusParameters.MAIN_NETWORK, ZcashBlockHeight(2030820u))
+            builder.addTransparentInputForSigner(pubkey, ZcashOutPoint(List(32) { 0u }, 1u), prevCoin)
+            builder.addTransparentOutput(address, ZcashAmount(200))
+            return builder
+        }
+
+        // Signing is deterministic, so the signer can replay the signature of the same
+        // transaction built with the spending key
+        val expected = ZcashTransaction.fromBytes(expectedTransactionBytes, ZcashBranchId.NU5)
+        val scriptSig = expected.transparentBundle()!!.vin()[0].scriptSig().instructions()
+        assert(scriptSig[1].data == pubkey)
+        val signature = scriptSig[0].data!!.dropLast(1)
+
+        val signer = ReplaySigner(signature)
+        val transaction = builder().buildWithSigner(signer, ZcashFeeRules.FixedNonStandard(0u), null, null)
+        assert(transaction.toBytes() == expectedTransactionBytes)
+        assert(signer.requests == listOf(0u))
+
+        try {
+            builder().buildWithSigner(ReplaySigner(null), ZcashFeeRules.FixedNonStandard(0u), null, null)
+            assert(false)
+        } catch (e: ZcashException.Message) {}
+
+        try {
+            val tampered = signature.dropLast(1) + (signature.last().toInt() xor 1).toUByte()
+            builder().buildWithSigner(ReplaySigner(tampered), ZcashFeeRules.FixedNonStandard(0u), null, null)
+            assert(false)
+        } catch (e: ZcashException.Message) {}
+
+        try {
+            builder().buildWithSigner(ReplaySigner(signature), ZcashFeeRules.FixedNonStandard(1u), null, null)
+            assert(false)
+        } catch (e: ZcashException.Message) {}
+
+        try {
+            val p2shCoin = ZcashTxOut(ZcashAmount(200), ZcashScript.p2sh(List(20) { 9u }))
+            builder().addTransparentInputForSigner(pubkey, ZcashOutPoint(List(32) { 0u }, 2u), p2shCoin)
+            assert(false)
+        } catch (e: ZcashException.Message) {}
+    }
+
+    fun testOrchardWithExternalSigner(){
+        val key = ZcashUnifiedSpendingKey.fromBytes(ZcashKeysEra.ORCHARD, supp.getAsU8Array("unified_spending_key"))
+        val fvk = key.toUnifiedFullViewingKey().orchard()!!
+
+        val changeAddress = fvk.addressAt(ZcashOrchardDiversifierIndex.fromU32(0u), ZcashOrchardScope.INTERNAL)
+        val nullifier = ZcashOrchardNullifier.fromBytes(List(32) { 0u })
+        val rseed = ZcashOrchardRandomSeed.fromBytes(List(32) { 0u }, nullifier)
+        val note = ZcashOrchardNote.fromParts(changeAddress, ZcashOrchardNoteValue.fromRaw(15u), nullifier, rseed)
+        val merklePath = ZcashOrchardMerklePath.fromParts(0u, List(32) { ZcashOrchardMerkleHash.fromBytes(List(32) { 0u }) })
+
+        // The transparent input is signed by the builder, the Orchard spend by the signer
+        val secretKey = key.transparent().deriveExternalSecretKey(0u)
+        val address = SecpPublicKey.fromSecretKey(secretKey).toTransparentAddress()
+        val prevCoin = ZcashTxOut(ZcashAmount(200), address.script())
+
+        fun builder(): ZcashTransactionBuilder {
+            val builder = ZcashTransactionBuilder(ZcashConsensusParameters.MAIN_NETWORK, ZcashBlockHeight(2030820u))
+            builder.addTransparentInput(secretKey, ZcashOutPoint(List(32) { 0u }, 1u), prevCoin)
+            builder.addOrchardSpendForSigner(fvk, note, merklePath)
+            builder.addTransparentOutput(address, ZcashAmount(100))
+            return builder
+        }
+
+        // Without change, the inputs must exactly cover the outputs and the fee
+        try {
+            builder().buildWithSigner(ShieldedSigner(key), ZcashFeeRules.FixedNonStandard(5u), null, null)
+            assert(false)
+        } catch (e: ZcashException.ChangeRequiredError) {}
+
+        // The signer declines to sign
+        try {
+            builder().buildWithSigner(ShieldedSigner(null), ZcashFeeRules.FixedNonStandard(5u), null, null)
+            assert(false)
+        } catch (e: ZcashException.Message) {}
+
+        val signer = ShieldedSigner(key)
+        val transaction = builder().buildWithSigner(
+            signer, ZcashFeeRules.FixedNonStandard(5u), ZcashChangeAddress.orchard(null, changeAddress), null)
+        assert(signer.orchardRequests == listOf(0u))
+
+        val bundle = transaction.orchardBundle()!!
+        assert(bundle.valueBalance().value() == -110L + 15L)
+
+        val validator = ZcashOrchardBatchValidator()
+        validator.addBundle(bundle, transaction.shieldedSighash(listOf(prevCoin)))
+        assert(validator.validate(ZcashVerifyingKey()) == listOf(1u))
+
+        // The Orchard builder can also be signed externally
+        val orchardBuilder = ZcashOrchardTransactionBuilder(
+            ZcashConsensusParameters.MAIN_NETWORK, ZcashBlockHeight(2030820u), ZcashBlockHeight(2030860u),
+            merklePath.root(note.commitment().toExtractedNoteCommitment()), ZcashOrchardFlags.fromParts(true, true))
+        orchardBuilder.addSpend(fvk, note, merklePath)
+        orchardBuilder.addRecipient(null, changeAddress, ZcashOrchardNoteValue.fromRaw(15u), null)
+        val orchardTransaction = orchardBuilder.buildWithSigner(ShieldedSigner(key))
+
+        val orchardValidator = ZcashOrchardBatchValidator()
+        orchardValidator.addBundle(orchardTransaction.orchardBundle()!!, orchardTransaction.shieldedSighash(listOf()))
+        assert(orchardValidator.validate(ZcashVerifyingKey()) == listOf(1u))
     }
 
     fun execute(){
@@ -1989,16 +2437,232 @@ class TransactionBuilderTest(supp: TestSupport) {
         testTransparentWithZip317StandardFee()
         testTransparentWithZip317NonStandardFee()
         testSaplingWithNonStandardFees()
+        testTransparentWithExternalSigner()
+        testOrchardWithExternalSigner()
     }
     
 }
 TransactionBuilderTest(supp).execute()
 
+class PartialTransactionTest(supp: TestSupport) {
+
+    val supp = supp
+
+    fun testTransparentOfflineSigning(){
+        val expectedTransactionBytes = supp.getAsU8Array(
+            "transaction_non_standard_fee")
+
+        val key = ZcashUnifiedSpendingKey.fromBytes(ZcashKeysEra.ORCHARD, supp.getAsU8Array("unified_spending_key"))
+        val address = key.transparent().toAccountPubkey().deriveExternalIvk().deriveAddress(0u)
+
+        val prevCoin = ZcashTxOut(ZcashAmount(200), address.script())
+
+        // Creator, without the spending key
+        val created = ZcashPartialTransaction(ZcashConsensusParameters.MAIN_NETWORK, ZcashBlockHeight(2030820u), ZcashFeeRules.FixedNonStandard(0u))
+        created.addTransparentInput(ZcashOutPoint(List(32) { 0u }, 1u), prevCoin, ZcashScope.EXTERNAL, 0u)
+        created.addTransparentOutput(address, ZcashAmount(200))
+
+        // Prover, with the full viewing key
+        val toProve = ZcashPartialTransaction.fromBytes(created.toBytes())
+        assert(!toProve.isProven())
+        toProve.prove(key.toUnifiedFullViewingKey(), ZcashLocalTxProver.withDefaultLocation())
+
+        // Signer, offline
+        val toSign = ZcashPartialTransaction.fromBytes(toProve.toBytes())
+        assert(toSign.isProven() && !toSign.isSigned())
+        toSign.sign(key)
+
+        // Extractor
+        val signed = ZcashPartialTransaction.fromBytes(toSign.toBytes())
+        assert(signed.isSigned())
+        assert(signed.extract().toBytes() == expectedTransactionBytes)
+    }
+
+    fun testOrchardAndInternalTransparentOfflineSigning(){
+        val key = ZcashUnifiedSpendingKey.fromBytes(ZcashKeysEra.ORCHARD, supp.getAsU8Array("unified_spending_key"))
+        val ufvk = key.toUnifiedFullViewingKey()
+        val fvk = ufvk.orchard()!!
+
+        // A note and a coin received by the internal (change) addresses of the account
+        val changeAddress = fvk.addressAt(ZcashOrchardDiversifierIndex.fromU32(0u), ZcashOrchardScope.INTERNAL)
+        val nullifier = ZcashOrchardNullifier.fromBytes(List(32) { 0u })
+        val rseed = ZcashOrchardRandomSeed.fromBytes(List(32) { 0u }, nullifier)
+        val note = ZcashOrchardNote.fromParts(changeAddress, ZcashOrchardNoteValue.fromRaw(15u), nullifier, rseed)
+        val merklePath = ZcashOrchardMerklePath.fromParts(0u, List(32) { ZcashOrchardMerkleHash.fromBytes(List(32) { 0u }) })
+
+        val changeSecretKey = key.transparent().deriveInternalSecretKey(3u)
+        val changeTransparentAddress = SecpPublicKey.fromSecretKey(changeSecretKey).toTransparentAddress()
+        val prevCoin = ZcashTxOut(ZcashAmount(200), changeTransparentAddress.script())
+
+        val created = ZcashPartialTransaction(ZcashConsensusParameters.MAIN_NETWORK, ZcashBlockHeight(2030820u), ZcashFeeRules.FixedNonStandard(0u))
+        created.addTransparentInput(ZcashOutPoint(List(32) { 0u }, 1u), prevCoin, ZcashScope.INTERNAL, 3u)
+        created.addOrchardSpend(note, merklePath)
+        created.addTransparentOutput(changeTransparentAddress, ZcashAmount(100))
+        created.addOrchardOutput(
+            fvk.toOvk(ZcashOrchardScope.EXTERNAL),
+            fvk.addressAt(ZcashOrchardDiversifierIndex.fromU32(0u), ZcashOrchardScope.EXTERNAL),
+            ZcashOrchardNoteValue.fromRaw(115u),
+            ZcashMemoBytes.empty())
+
+        val toSign = ZcashPartialTransaction.fromBytes(created.toBytes())
+        toSign.prove(ufvk, ZcashLocalTxProver.withDefaultLocation())
+
+        // Another account cannot sign the transaction
+        val otherKey = ZcashUnifiedSpendingKey.fromSeed(ZcashConsensusParameters.MAIN_NETWORK, List(32) { 2u }, ZcashAccountId(0u))
+        try {
+            ZcashPartialTransaction.fromBytes(toSign.toBytes()).sign(otherKey)
+            assert(false)
+        } catch (e: ZcashException.Message) {}
+
+        toSign.sign(key)
+        val transaction = ZcashPartialTransaction.fromBytes(toSign.toBytes()).extract()
+
+        val validator = ZcashOrchardBatchValidator()
+        validator.addBundle(transaction.orchardBundle()!!, transaction.shieldedSighash(listOf(prevCoin)))
+        assert(validator.validate(ZcashVerifyingKey()) == listOf(1u))
+    }
+
+    fun execute(){
+        testTransparentOfflineSigning()
+        testOrchardAndInternalTransparentOfflineSigning()
+    }
+}
+PartialTransactionTest(supp).execute()
+
+class MultisigTransactionTest(supp: TestSupport) {
+
+    val supp = supp
+
+    fun hexToBytes(hex: String): List<UByte> = hex.chunked(2).map { it.toInt(16).toUByte() }
+
+    fun testTwoOfThreeSpend(){
+        val secretKeys = listOf(11, 22, 33).map { k -> SecpSecretKey(List(31) { 0u.toUByte() } + k.toUByte()) }
+        val publicKeys = listOf(
+            "03774ae7f858a9411e5ef4246b70c65aac5649980be5c17891bbec17895da008cb",
+            "03421f5fc9a21065445c96fdb91c0c1e2f2431741c72713b4b99ddcb316f31e9fc",
+            "021697ffa6fd9de627c077e3d2fe541084ce13300b0bec1146f95ae57f0d0bd6a5",
+        ).map { hexToBytes(it) }
+
+        val redeemScript = ZcashScript.multisig(2u, publicKeys)
+        val address = ZcashTransparentAddress.fromRedeemScript(redeemScript)
+        assert(address.isScript())
+        assert(address.encode(ZcashConsensusParameters.MAIN_NETWORK) == "t3VzBYbUHd9soHKhdnakH6jLaH7koRmgfQN")
+
+        val builder = ZcashTransactionBuilder(ZcashConsensusParameters.MAIN_NETWORK, ZcashBlockHeight(2500000u))
+        builder.addTransparentMultisigInput(redeemScript, ZcashOutPoint(List(32) { 1u }, 0u), ZcashTxOut(ZcashAmount(100000), address.script()))
+        builder.addTransparentOutput(ZcashTransparentAddress.fromPublicKey(List(20) { 7u }), ZcashAmount(90000))
+        val unsigned = builder.buildMultisig(ZcashFeeRules.Zip317Standard())
+
+        // Each cosigner signs its own copy
+        val first = ZcashMultisigTransaction.fromBytes(unsigned.toBytes())
+        first.sign(0u, secretKeys[0])
+        val second = ZcashMultisigTransaction.fromBytes(unsigned.toBytes())
+        second.sign(0u, secretKeys[2])
+        assert(!first.isComplete())
+
+        first.combine(second)
+        assert(first.signatureCount(0u) == 2u)
+        assert(first.isComplete())
+
+        val transaction = first.finalize()
+        assert(transaction.txid().toHexString() == "557768c456baa6fdfce63ab3c4d1c0059c8a6fcf0ba03d01810ea032ef39d5ac")
+
+        // The finalized transaction commits to the same sighash the cosigners signed
+        val prevouts = listOf(ZcashTxOut(ZcashAmount(100000), address.script()))
+        assert(transaction.transparentSighash(0u, 1u, prevouts, redeemScript) == unsigned.sighash(0u))
+        assert(transaction.shieldedSighash(prevouts).size == 32)
+        assert(transaction.txidDigests().transparentPrevoutsDigest != null)
+        assert(transaction.txidDigests().saplingDigest == null)
+        assert(transaction.authCommitment().size == 32)
+
+        val lookup = object : ZcashPrevoutLookup {
+            override fun prevoutValue(outpoint: ZcashOutPoint): ULong? =
+                if (outpoint.hash() == List(32) { 1u.toUByte() } && outpoint.n() == 0u) 100000uL else null
+        }
+        val feeRule = ZcashZip317FeeRule.standard()
+        assert(transaction.feePaid(lookup).value() == 10000L)
+        assert(transaction.zip317LogicalActions(feeRule) == 2uL)
+        assert(transaction.zip317ConventionalFee(feeRule).value() == 10000L)
+    }
+
+    fun execute(){
+        testTwoOfThreeSpend()
+    }
+}
+MultisigTransactionTest(supp).execute()
+
 
 class OrchardTransactionBuilderTest(supp: TestSupport) {
 
     val supp = supp
 
+    // Minimal JSON parser, returning maps, lists, strings, numbers, booleans and nulls
+    class JsonParser(val text: String) {
+        var pos = 0
+
+        fun parse(): Any? {
+            val value = parseValue()
+            skipWhitespace()
+            assert(pos == text.length)
+            return value
+        }
+
+        fun skipWhitespace() {
+            while (pos < text.length && text[pos].isWhitespace()) pos++
+        }
+
+        fun parseValue(): Any? {
+            skipWhitespace()
+            return when (text[pos]) {
+                '{' -> {
+                    pos++
+                    val map = mutableMapOf<String, Any?>()
+                    skipWhitespace()
+                    while (text[pos] != '}') {
+                        val key = parseValue() as String
+                        skipWhitespace()
+                        assert(text[pos++] == ':')
+                        map[key] = parseValue()
+                        skipWhitespace()
+                        if (text[pos] == ',') pos++
+                        skipWhitespace()
+                    }
+                    pos++
+                    map
+                }
+                '[' -> {
+                    pos++
+                    val list = mutableListOf<Any?>()
+                    skipWhitespace()
+                    while (text[pos] != ']') {
+                        list.add(parseValue())
+                        skipWhitespace()
+                        if (text[pos] == ',') pos++
+                        skipWhitespace()
+                    }
+                    pos++
+                    list
+                }
+                '"' -> {
+                    val end = text.indexOf('"', pos + 1)
+                    val string = text.substring(pos + 1, end)
+                    pos = end + 1
+                    string
+                }
+                else -> {
+                    val start = pos
+                    while (pos < text.length && text[pos] !in ",}] \n") pos++
+                    when (val token = text.substring(start, pos)) {
+                        "true" -> true
+                        "false" -> false
+                        "null" -> null
+                        else -> token.toBigDecimal()
+                    }
+                }
+            }
+        }
+    }
+
     fun testTransactionGeneration(){
         val key = ZcashUnifiedSpendingKey.fromBytes(ZcashKeysEra.ORCHARD, supp.getAsU8Array("unified_spending_key"))
 
@@ -2025,18 +2689,156 @@ class OrchardTransactionBuilderTest(supp: TestSupport) {
         builder.addSpend(fvk!!, note, merklePath)
         builder.addRecipient(ovk, address, noteValue, null)
 
+        // A payment to another account, recoverable with the internal OVK
+        val otherKey = ZcashUnifiedSpendingKey.fromSeed(ZcashConsensusParameters.MAIN_NETWORK, List(32) { 2u }, ZcashAccountId(0u))
+        val otherAddress = otherKey.toUnifiedFullViewingKey().orchard()!!.addressAt(ZcashOrchardDiversifierIndex.fromU32(0u), ZcashOrchardScope.EXTERNAL)
+        builder.addRecipient(fvk.toOvk(ZcashOrchardScope.INTERNAL), otherAddress, ZcashOrchardNoteValue.fromRaw(0u), null)
+
         val transaction = builder.build(listOf(key.orchard()), List(32) { 0u })
 
         assert(transaction.toBytes().size == 9165)
 
+        // The bundle was signed over an all-zero sighash
+        val validator = ZcashOrchardBatchValidator()
+        validator.addBundle(transaction.orchardBundle()!!, List(32) { 0u })
+        validator.addBundle(transaction.orchardBundle()!!, List(32) { 1u })
+        assert(validator.validate(ZcashVerifyingKey()) == listOf(1u))
+
+        val json = JsonParser(transaction.toJson(ZcashConsensusParameters.MAIN_NETWORK, listOf(ufvk))).parse() as Map<*, *>
+        assert(json["txid"] == transaction.txid().toHexString())
+
+        val actions = (json["orchard"] as Map<*, *>)["actions"] as List<*>
+        val decrypted = actions.map { (it as Map<*, *>)["decrypted"] as Map<*, *> }
+        assert(decrypted.map { it["transferType"] }.toSet() == setOf("wallet_internal", "outgoing"))
+        for (note in decrypted) {
+            // Decrypted note values are in zatoshis, as told by their name
+            assert(note.containsKey("valueZat"))
+            assert(!note.containsKey("value"))
+        }
+        assert(decrypted.first { it["transferType"] == "wallet_internal" }["valueZat"] == 15.toBigDecimal())
+        assert(decrypted.first { it["transferType"] == "outgoing" }["valueZat"] == 0.toBigDecimal())
+    }
+
+    fun testPaymentDisclosure(){
+        val key = ZcashUnifiedSpendingKey.fromBytes(ZcashKeysEra.ORCHARD, supp.getAsU8Array("unified_spending_key"))
+
+        val ufvk = key.toUnifiedFullViewingKey()
+        val fvk = ufvk.orchard()
+        val ovk = fvk!!.toOvk(ZcashOrchardScope.EXTERNAL)
+        val address = fvk!!.toIvk(ZcashOrchardScope.INTERNAL).address(ZcashOrchardDiversifier.fromBytes(List(11) { 0u }))
+
+        val noteValue = ZcashOrchardNoteValue.fromRaw(15u)
+        val nullifier = ZcashOrchardNullifier.fromBytes(List(32) { 0u })
+        val rseed = ZcashOrchardRandomSeed.fromBytes(List(32) { 0u }, nullifier)
+        val note = ZcashOrchardNote.fromParts(address, noteValue, nullifier, rseed)
+
+        val merkleHash = ZcashOrchardMerkleHash.fromBytes(List(32) { 0u })
+        val merklePath = ZcashOrchardMerklePath.fromParts(0u, List(32) { merkleHash })
+        val anchor = merklePath.root(note.commitment().toExtractedNoteCommitment())
+        val flags = ZcashOrchardFlags.fromParts(true, true)
+
+        val params = ZcashConsensusParameters.MAIN_NETWORK
+        val height = ZcashBlockHeight(2030820u)
+        val builder = ZcashOrchardTransactionBuilder(params, height, ZcashBlockHeight(2030820u+100u), anchor, flags)
+        builder.addSpend(fvk!!, note, merklePath)
+        builder.addRecipient(ovk, address, noteValue, null)
+
+        // The randomizer of the spend is kept when signing, to sign the disclosure later
+        val alphas = mutableListOf<List<UByte>>()
+        val signer = object : ZcashExternalSigner {
+            override fun signTransparent(sighash: List<UByte>, inputIndex: UInt): List<UByte>? = null
+
+            override fun signSapling(sighash: List<UByte>, alpha: List<UByte>, spendIndex: UInt): List<UByte>? = null
+
+            override fun signOrchard(sighash: List<UByte>, alpha: List<UByte>, spendIndex: UInt): List<UByte>? {
+                alphas.add(alpha)
+                return key.orchard().signSpendAuth(sighash, alpha)
+            }
+        }
+        val transaction = builder.buildWithSigner(signer)
+        val alpha = alphas.single()
+
+        // Only the action paying the recipient can be disclosed, the other one is a dummy output
+        val disclosures = (0u until 2u).mapNotNull {
+            try {
+                ZcashPaymentDisclosure(params, height, transaction, ufvk, ZcashDisclosureProtocol.ORCHARD, it, "invoice 42")
+            } catch (e: ZcashException) {
+                null
+            }
+        }
+        assert(disclosures.size == 1)
+        val unsigned = disclosures[0]
+
+        try {
+            verifyPaymentDisclosure(params, height, transaction, unsigned)
+            assert(false)
+        } catch (e: ZcashException.Message) {
+            assert(e.message!!.contains("not signed"))
+        }
+
+        val signature = key.orchard().signSpendAuth(unsigned.signatureHash(), alpha)
+        val disclosure = ZcashPaymentDisclosure.decode(
+            unsigned.signed(ZcashDisclosureProtocol.ORCHARD, signature).encode())
+        assert(disclosure.isSigned())
+        assert(disclosure.message() == "invoice 42")
+        assert(disclosure.txid().toBytes() == transaction.txid().toBytes())
+        assert(disclosure.protocol() == ZcashDisclosureProtocol.ORCHARD)
+        assert(disclosure.outputIndex() == unsigned.outputIndex())
+
+        val payment = verifyPaymentDisclosure(params, height, transaction, disclosure)
+        assert(payment.value.value() == 15uL)
+        assert(payment.recipient.encode(params) == ZcashUnifiedAddress(address, null, null).encode(params))
+
+        // The signature must be made with the randomizer of a spend of the named protocol
+        for (forged in listOf(
+            unsigned.signed(ZcashDisclosureProtocol.SAPLING, signature),
+            unsigned.signed(ZcashDisclosureProtocol.ORCHARD,
+                key.orchard().signSpendAuth(unsigned.signatureHash(), List(32) { if (it == 0) 1u else 0u })),
+        )) {
+            try {
+                verifyPaymentDisclosure(params, height, transaction, forged)
+                assert(false)
+            } catch (e: ZcashException.Message) {
+                assert(e.message!!.contains("not signed by a spender"))
+            }
+        }
     }
 
     fun execute(){
        testTransactionGeneration()
+       testPaymentDisclosure()
     }
 }
 OrchardTransactionBuilderTest(supp).execute()
 
+fun testWalletPaymentDisclosure(){
+    val params = ZcashConsensusParameters.MAIN_NETWORK
+    val dir = kotlin.io.path.createTempDirectory().toFile()
+    val db = ZcashWalletDb.forPath(java.io.File(dir, "wallet.db").path, params)
+    val seed = supp.getAsU8Array("seed")
+    db.initialize(seed)
+    val treeState = ZcashTreeState("main", 2030819u, "02".repeat(32), 0u, "000000", "000000")
+    db.createAccount(seed, ZcashAccountBirthday.fromTreestate(treeState, null))
+
+    val transaction = ZcashTransaction.fromBytes(supp.getAsU8Array("transaction_sapling"), ZcashBranchId.NU5)
+    decryptAndStoreTransaction(params, db, transaction)
+
+    // The height of the output is needed to decrypt it, so the transaction must be mined
+    try {
+        db.createPaymentDisclosure(transaction.txid(), ZcashDisclosureProtocol.SAPLING, 0u, "")
+        assert(false)
+    } catch (e: ZcashException.Message) {
+        assert(e.message!!.contains("not mined yet"))
+    }
+    try {
+        db.createPaymentDisclosure(ZcashTxId.fromBytes(List(32) { 7u }), ZcashDisclosureProtocol.SAPLING, 0u, "")
+        assert(false)
+    } catch (e: ZcashException.Message) {
+        assert(e.message!!.contains("not in the wallet"))
+    }
+}
+testWalletPaymentDisclosure()
+
 class TransactionSerializationTest(supp: TestSupport) {
 
     val supp = supp
@@ -2104,6 +2906,40 @@ class TransactionExplorationTest(supp: TestSupport) {
         assert(1 == vin.size)
         val vin0_bytes = supp.getAsU8Array("transaction_standard_fee_vin_0")
         assert(vin0_bytes == vin[0].toBytes())
+        assert(vin[0].prevout().hash() == List(32) { 0u.toUByte() })
+        assert(vin[0].prevout().n() == 1u)
+        assert(vin[0].sequence() == 0xFFFFFFFFu)
+
+        // scripts
+        assert(vout[0].scriptPubkey().kind() == ZcashScriptKind.PUBKEY_HASH)
+        assert(vout[0].scriptPubkey().address()!!.toBytes() == vout_0_address)
+        assert(vin[0].scriptSig().instructions().size == 2)
+        assert(vin[0].scriptSig().kind() == ZcashScriptKind.UNKNOWN)
+        val signature = vin[0].scriptSig().instructions()[0].data!!
+        val signatureHex = signature.dropLast(1).joinToString("") { "%02x".format(it.toInt()) }
+        assert(vin[0].scriptSig().disassembleScriptSig().startsWith(signatureHex + "[ALL] "))
+        assert(vin[0].scriptSig().disassemble().startsWith(signatureHex + "01 "))
+    }
+
+    fun testScriptTemplates(){
+        val script = ZcashScript.p2sh(List(20) { 9u })
+        assert(script.kind() == ZcashScriptKind.SCRIPT_HASH)
+        assert(script.disassemble() == "OP_HASH160 0909090909090909090909090909090909090909 OP_EQUAL")
+
+        val nullData = ZcashScript.opReturn("hello".toByteArray().map { it.toUByte() })
+        assert(nullData.kind() == ZcashScriptKind.NULL_DATA)
+        assert(nullData.disassemble() == "OP_RETURN 68656c6c6f")
+        assert(nullData.address() == null)
+
+        val truncated = ZcashScript.fromRawBytes(listOf(0x76u, 0x05u, 0x01u))
+        assert(truncated.kind() == ZcashScriptKind.UNKNOWN)
+        assert(truncated.disassemble() == "OP_DUP [error]")
+
+        // Pushes of up to 4 bytes are script numbers
+        assert(ZcashScript.fromRawBytes(listOf(0x02u, 0xffu, 0x00u)).disassemble() == "255")
+        assert(ZcashScript.fromRawBytes(listOf(0x01u, 0x81u)).disassemble() == "-1")
+        assert(ZcashScript.fromRawBytes(listOf(0x00u, 0xbcu)).disassemble() == "0 OP_UNKNOWN")
+        assert(ZcashScript.fromRawBytes(listOf(0x05u, 1u, 2u, 3u, 4u, 5u)).disassemble() == "0102030405")
     }
 
     fun testSaplingBundle(){       
@@ -2132,7 +2968,114 @@ class TransactionExplorationTest(supp: TestSupport) {
         assert(0.toLong() == bundle.valueBalance().value())
     }
 
-    fun testOrchardBundle(){       
+    fun testSaplingBundleCrypto(){
+        val key = ZcashUnifiedSpendingKey.fromBytes(ZcashKeysEra.ORCHARD, supp.getAsU8Array("unified_spending_key"))
+        val dfvk = key.toUnifiedFullViewingKey().sapling()!!
+        val params = ZcashConsensusParameters.MAIN_NETWORK
+        val height = ZcashBlockHeight(2030820u)
+        val address = key.sapling().defaultAddress().address
+
+        val transactionBytes = supp.getAsU8Array("transaction_sapling")
+        val tx = ZcashTransaction.fromBytes(transactionBytes, ZcashBranchId.NU5)
+
+        val bundle = tx.saplingBundle()!!
+
+        // Decrypt output with IVK
+        val ivk = dfvk.toIvk(ZcashScope.EXTERNAL)
+        val output_ivk = bundle.decryptOutputWithKey(params, height, 0u, ivk)
+        assert(200.toULong() == output_ivk.note.value().inner())
+        assert(address.toBytes() == output_ivk.address.toBytes())
+        assert(ZcashMemoBytes.empty().data() == output_ivk.memo.data())
+
+        // Decrypt outputs with IVKs, the padding output is skipped
+        val outputs_ivk = bundle.decryptOutputsWithKeys(params, height, listOf(ivk))
+        assert(1 == outputs_ivk.size)
+        assert(0.toULong() == outputs_ivk[0].idx)
+        assert(ivk.toRepr() == outputs_ivk[0].key.toRepr())
+        assert(200.toULong() == outputs_ivk[0].note.value().inner())
+
+        // Recover output with OVK, the change was sent with the internal one
+        val ovk = dfvk.toOvk(ZcashScope.INTERNAL)
+        val output_ovk = bundle.recoverOutputWithOvk(params, height, 0u, ovk)
+        assert(200.toULong() == output_ovk.note.value().inner())
+        assert(address.toBytes() == output_ovk.address.toBytes())
+
+        try {
+            bundle.recoverOutputWithOvk(params, height, 0u, dfvk.toOvk(ZcashScope.EXTERNAL))
+            assert(false)
+        } catch (e: ZcashException.Message) {}
+
+        try {
+            bundle.decryptOutputWithKey(params, height, 2u, ivk)
+            assert(false)
+        } catch (e: ZcashException.Message) {}
+
+        // Recover outputs with OVKs
+        val outputs_ovk = bundle.recoverOutputsWithOvks(params, height, listOf(dfvk.toOvk(ZcashScope.EXTERNAL), ovk))
+        assert(1 == outputs_ovk.size)
+        assert(0.toULong() == outputs_ovk[0].idx)
+        assert(ovk.toBytes() == outputs_ovk[0].key.toBytes())
+
+        // Compact outputs
+        val theOutput = bundle.shieldedOutputs()[0]
+        assert(580 == theOutput.encCiphertext().size)
+        assert(80 == theOutput.outCiphertext().size)
+        val compact = theOutput.toCompact()
+        assert(theOutput.cmu().toBytes() == compact.cmu().toBytes())
+        assert(theOutput.ephemeralKey() == compact.ephemeralKey())
+        assert(theOutput.encCiphertext().take(52) == compact.encCiphertext())
+        assert(200.toULong() == compact.decryptWithKey(params, height, ivk).note.value().inner())
+
+        val fromParts = ZcashSaplingCompactOutputDescription.fromParts(
+            theOutput.ephemeralKey(), theOutput.cmu(), theOutput.encCiphertext().take(52))
+        assert(address.toBytes() == fromParts.decryptWithKey(params, height, ivk).address.toBytes())
+    }
+
+    fun testSaplingSpendMatching(){
+        val key = ZcashUnifiedSpendingKey.fromBytes(ZcashKeysEra.ORCHARD, supp.getAsU8Array("unified_spending_key"))
+        val nk = key.toUnifiedFullViewingKey().sapling()!!.toNk(ZcashScope.EXTERNAL)
+
+        val tx = ZcashTransaction.fromBytes(supp.getAsU8Array("transaction_sapling"), ZcashBranchId.NU5)
+        val bundle = tx.saplingBundle()!!
+
+        // The spent note is the first leaf of the tree
+        val note = key.sapling().defaultAddress().address.createNote(200u, ZcashRseed.AfterZip212(List(32) { 0u }))
+        val nf = note.nf(nk, 0u)
+        assert(supp.getAsU8Array("transaction_sapling_spend_0_nullifier") == nf.toBytes())
+        assert(note.nf(nk, 1u).toBytes() != nf.toBytes())
+
+        val unknown = ZcashSaplingNullifier.fromBytes(List(32) { 0u })
+        val matches = bundle.matchSpends(listOf(unknown, nf))
+        assert(1 == matches.size)
+        assert(0.toULong() == matches[0].spendIdx)
+        assert(1.toULong() == matches[0].noteIdx)
+
+        assert(bundle.matchSpends(listOf(unknown)).isEmpty())
+    }
+
+    fun testOrchardSpendMatching(){
+        val key = ZcashUnifiedSpendingKey.fromBytes(ZcashKeysEra.ORCHARD, supp.getAsU8Array("unified_spending_key"))
+        val fvk = key.toUnifiedFullViewingKey().orchard()!!
+
+        val tx = ZcashTransaction.fromBytes(supp.getAsU8Array("transaction_orchard"), ZcashBranchId.NU5)
+        val bundle = tx.orchardBundle()!!
+
+        val address = fvk.toIvk(ZcashOrchardScope.INTERNAL).address(ZcashOrchardDiversifier.fromBytes(List(11) { 0u }))
+        assert(supp.getAsU8Array("transaction_orchard_address") == address.toRawAddressBytes())
+
+        val rho = ZcashOrchardNullifier.fromBytes(List(32) { 0u })
+        val note = ZcashOrchardNote.fromParts(address, ZcashOrchardNoteValue.fromRaw(15u), rho, ZcashOrchardRandomSeed.fromBytes(List(32) { 0u }, rho))
+        val nf = note.nullifier(fvk)
+
+        val matches = bundle.matchSpends(listOf(nf))
+        assert(1 == matches.size)
+        assert(0.toULong() == matches[0].noteIdx)
+        assert(bundle.actions()[matches[0].actionIdx.toInt()].nullifier().toBytes() == nf.toBytes())
+
+        assert(bundle.matchSpends(listOf(rho)).isEmpty())
+    }
+
+    fun testOrchardBundle(){
         val transactionBytes = supp.getAsU8Array("transaction_orchard")
         val tx = ZcashTransaction.fromBytes(transactionBytes, ZcashBranchId.NU5)
 
@@ -2213,8 +3156,446 @@ class TransactionExplorationTest(supp: TestSupport) {
         testFirstLevelFields()
         testTransparentBundle()
         testSaplingBundle()
+        testSaplingBundleCrypto()
+        testSaplingSpendMatching()
         testOrchardBundle()
+        testOrchardSpendMatching()
         testOrchardBundleCrypto()
+        testScriptTemplates()
+    }
+}
+TransactionExplorationTest(supp).execute()
+class WalletDbTest(supp: TestSupport) {
+
+    val supp = supp
+    val params = ZcashConsensusParameters.MAIN_NETWORK
+    val seed = List(32) { 7.toUByte() }
+
+    fun newWallet(dir: java.io.File): ZcashWalletDb {
+        val db = ZcashWalletDb.forPath(java.io.File(dir, "wallet.db").path, params)
+        db.initialize(seed)
+        return db
+    }
+
+    fun birthday(): ZcashAccountBirthday {
+        val treeState = ZcashTreeState("main", 2000000u, "00".repeat(32), 0u, "000000", "")
+        return ZcashAccountBirthday.fromTreestate(treeState, null)
+    }
+
+    fun transparentOutput(account: UInt, index: UInt): ZcashWalletTransparentOutput {
+        val usk = ZcashUnifiedSpendingKey.fromSeed(params, seed, ZcashAccountId(account))
+        val address = usk.toUnifiedFullViewingKey().transparent()!!.deriveExternalIvk().deriveAddress(index)
+        val txOut = ZcashTxOut(ZcashAmount(100000), address.script())
+        return ZcashWalletTransparentOutput.fromParts(ZcashOutPoint(List(32) { 1u }, 0u), txOut, ZcashBlockHeight(2000001u))
+    }
+
+    fun testDiscoverAccounts() {
+        val dir = kotlin.io.path.createTempDirectory().toFile()
+        val cache = ZcashFsBlockDb.forPath(dir.path)
+        cache.initialize(dir.path)
+        val db = newWallet(dir)
+        val fromHeight = ZcashBlockHeight(2000000u)
+
+        assert(db.discoverAccounts(seed, birthday(), dir.path, fromHeight, listOf(), 3u).isEmpty())
+
+        // Account 4 is only found because account 2 extends the search past the first batch
+        val outputs = listOf(transparentOutput(2u, 5u), transparentOutput(4u, 0u))
+        val accounts = db.discoverAccounts(seed, birthday(), dir.path, fromHeight, outputs, 3u)
+        assert(accounts.map { it.accountId.id } == listOf(0u, 1u, 2u, 3u, 4u))
+        assert(db.getUnifiedFullViewingKeys().size == 5)
+
+        try {
+            db.discoverAccounts(seed, birthday(), dir.path, fromHeight, listOf(), 3u)
+            assert(false)
+        } catch (e: ZcashException.Message) {}
+
+        // Account 4 is out of reach of a gap limit of 3 on its own
+        val other = ZcashWalletDb.forPath(java.io.File(dir, "other.db").path, params)
+        other.initialize(seed)
+        assert(other.discoverAccounts(seed, birthday(), dir.path, fromHeight, listOf(transparentOutput(4u, 0u)), 3u).isEmpty())
+
+        try {
+            other.discoverAccounts(seed, birthday(), dir.path, fromHeight, listOf(), 0u)
+            assert(false)
+        } catch (e: ZcashException.Message) {}
+    }
+
+    fun execute(){
+        testDiscoverAccounts()
+    }
+}
+WalletDbTest(supp).execute()
+class CompactBlockScanningTest(supp: TestSupport) {
+
+    val supp = supp
+    val params = ZcashConsensusParameters.MAIN_NETWORK
+
+    fun varint(value: Long): List<UByte> {
+        val out = mutableListOf<UByte>()
+        var n = value
+        while (n >= 0x80) {
+            out.add(((n and 0x7f) or 0x80).toUByte())
+            n = n shr 7
+        }
+        out.add(n.toUByte())
+        return out
+    }
+
+    fun bytesField(tag: Int, data: List<UByte>) = listOf((tag shl 3 or 2).toUByte()) + varint(data.size.toLong()) + data
+
+    fun varintField(tag: Int, value: Long) = listOf((tag shl 3).toUByte()) + varint(value)
+
+    // Encodes the Sapling part of a transaction as a height 2030820 compact block
+    fun compactBlock(tx: ZcashTransaction, saplingTreeSize: Long): ZcashCompactBlock {
+        val bundle = tx.saplingBundle()!!
+        var compactTx = varintField(1, 0) + bytesField(2, tx.txid().toBytes())
+        for (spend in bundle.shieldedSpends()) {
+            compactTx += bytesField(4, bytesField(1, spend.nullifier().toBytes()))
+        }
+        for (output in bundle.shieldedOutputs()) {
+            val compactOutput = bytesField(1, output.cmu().toBytes()) +
+                bytesField(2, output.ephemeralKey()) +
+                bytesField(3, output.encCiphertext().take(52))
+            compactTx += bytesField(5, compactOutput)
+        }
+        val block = varintField(2, 2030820) +
+            bytesField(3, List(32) { 1u }) +
+            bytesField(4, List(32) { 2u }) +
+            varintField(5, 1700000000) +
+            bytesField(7, compactTx) +
+            bytesField(8, varintField(1, saplingTreeSize))
+        return ZcashCompactBlock.fromBytes(block)
+    }
+
+    fun testScanBlock() {
+        val key = ZcashUnifiedSpendingKey.fromBytes(ZcashKeysEra.ORCHARD, supp.getAsU8Array("unified_spending_key"))
+        val ufvk = key.toUnifiedFullViewingKey()
+        val tx = ZcashTransaction.fromBytes(supp.getAsU8Array("transaction_sapling"), ZcashBranchId.NU5)
+        val block = compactBlock(tx, 12)
+        assert(block.height().value() == 2030820u)
+        assert(block.prevHash().toBytes() == List(32) { 2.toUByte() })
+        assert(ZcashCompactBlock.fromBytes(block.toBytes()).toBytes() == block.toBytes())
+
+        val account = ZcashAccountId(0u)
+        val note = key.sapling().defaultAddress().address.createNote(200u, ZcashRseed.AfterZip212(List(32) { 0u }))
+        val nf = note.nf(ufvk.sapling()!!.toNk(ZcashScope.EXTERNAL), 0u)
+        val prior = ZcashBlockMetadata.fromParts(ZcashBlockHeight(2030819u), ZcashBlockHash.fromSlice(List(32) { 2u }), 10u)
+
+        val scanned = scanBlock(params, block, mapOf(account to ufvk), listOf(TupleAccountIdAndSaplingNullifier(account, nf)), prior)
+        assert(scanned.height().value() == 2030820u)
+        assert(scanned.metadata().saplingTreeSize() == 12u)
+        assert(scanned.transactions().size == 1)
+
+        val walletTx = scanned.transactions()[0]
+        assert(walletTx.txid().toBytes() == tx.txid().toBytes())
+        assert(walletTx.index() == 0u)
+
+        val spends = walletTx.saplingSpends()
+        assert(spends.size == 1)
+        assert(spends[0].index() == 0u)
+        assert(spends[0].account() == account)
+        assert(spends[0].nf().toBytes() == nf.toBytes())
+
+        val outputs = walletTx.saplingOutputs()
+        assert(outputs.size == 1)
+        assert(outputs[0].index() == 0u)
+        assert(outputs[0].account() == account)
+        assert(outputs[0].note().value().inner() == 200.toULong())
+        assert(outputs[0].noteCommitmentTreePosition().value() == 10.toULong())
+        assert(outputs[0].cmu().toBytes() == tx.saplingBundle()!!.shieldedOutputs()[0].cmu().toBytes())
+
+        // Without prior metadata, positions come from the block's chain metadata
+        val unchained = scanBlock(params, block, mapOf(account to ufvk), listOf(), null)
+        assert(unchained.transactions()[0].saplingSpends().isEmpty())
+        assert(unchained.transactions()[0].saplingOutputs()[0].noteCommitmentTreePosition().value() == 10.toULong())
+
+        val otherUfvk = ZcashUnifiedSpendingKey.fromSeed(params, List(32) { 2u }, account).toUnifiedFullViewingKey()
+        assert(scanBlock(params, block, mapOf(account to otherUfvk), listOf(), prior).transactions().isEmpty())
+
+        try {
+            val wrongParent = ZcashBlockMetadata.fromParts(ZcashBlockHeight(2030819u), ZcashBlockHash.fromSlice(List(32) { 3u }), 10u)
+            scanBlock(params, block, mapOf(account to ufvk), listOf(), wrongParent)
+            assert(false)
+        } catch (e: ZcashException.Message) {}
+    }
+
+    fun testScanBlockWithIvks() {
+        val key = ZcashUnifiedSpendingKey.fromBytes(ZcashKeysEra.ORCHARD, supp.getAsU8Array("unified_spending_key"))
+        val dfvk = key.toUnifiedFullViewingKey().sapling()!!
+        val tx = ZcashTransaction.fromBytes(supp.getAsU8Array("transaction_sapling"), ZcashBranchId.NU5)
+        val block = compactBlock(tx, 12)
+        val account = ZcashAccountId(0u)
+        val prior = ZcashBlockMetadata.fromParts(ZcashBlockHeight(2030819u), ZcashBlockHash.fromSlice(List(32) { 2u }), 10u)
+
+        val ivk = ZcashSaplingIvk.fromRepr(dfvk.toIvk(ZcashScope.EXTERNAL).toRepr())
+        val external = ZcashSaplingScanningKey(account, ZcashScope.EXTERNAL, ivk, dfvk.toNk(ZcashScope.EXTERNAL))
+        val scanned = scanBlockWithIvks(params, block, listOf(external), listOf(), prior)
+        val outputs = scanned.transactions()[0].saplingOutputs()
+        assert(outputs.size == 1)
+        assert(outputs[0].account() == account)
+        assert(outputs[0].note().value().inner() == 200.toULong())
+
+        // The nullifiers match the ones computed from the full viewing key
+        val fromUfvk = scanBlock(params, block, mapOf(account to key.toUnifiedFullViewingKey()), listOf(), prior)
+        assert(outputs[0].nf().toBytes() == fromUfvk.transactions()[0].saplingOutputs()[0].nf().toBytes())
+
+        // The output was sent to an external address, so the internal key does not see it
+        val internal = ZcashSaplingScanningKey(account, ZcashScope.INTERNAL, dfvk.toIvk(ZcashScope.INTERNAL), dfvk.toNk(ZcashScope.INTERNAL))
+        assert(scanBlockWithIvks(params, block, listOf(internal), listOf(), prior).transactions().isEmpty())
+    }
+
+    fun testScannedNoteWitness() {
+        val dir = kotlin.io.path.createTempDirectory().toFile()
+        val db = ZcashWalletDb.forPath(java.io.File(dir, "wallet.db").path, params)
+        val seed = supp.getAsU8Array("seed")
+        db.initialize(seed)
+
+        val treeState = ZcashTreeState("main", 2030819u, "02".repeat(32), 0u, "000000", "000000")
+        val account = db.createAccount(seed, ZcashAccountBirthday.fromTreestate(treeState, null))
+        val ufvk = account.unifiedSpendingKey.toUnifiedFullViewingKey()
+
+        val tx = ZcashTransaction.fromBytes(supp.getAsU8Array("transaction_sapling"), ZcashBranchId.NU5)
+        val prior = ZcashBlockMetadata.fromParts(ZcashBlockHeight(2030819u), ZcashBlockHash.fromSlice(List(32) { 2u }), 0u)
+        val scanned = scanBlock(params, compactBlock(tx, 2), mapOf(account.accountId to ufvk), listOf(), prior)
+        db.updateChainTip(2030820u)
+        db.putBlocks(listOf(scanned))
+
+        val heights = db.getTargetAndAnchorHeights(1u)!!
+        assert(heights.anchorHeight.value() == 2030820u)
+        val notes = db.getSpendableSaplingNotes(account.accountId, heights.anchorHeight, listOf())
+        assert(notes.size == 1)
+        assert(notes[0].noteCommitmentTreePosition().value() == 0.toULong())
+
+        // The wallet path matches the one of a witness of the same tree
+        val outputs = tx.saplingBundle()!!.shieldedOutputs()
+        val tree = ZcashCommitmentTree.empty()
+        tree.append(ZcashSaplingNode.fromCmu(outputs[0].cmu()))
+        val witness = ZcashIncrementalWitness.fromTree(tree)
+        witness.append(ZcashSaplingNode.fromCmu(outputs[1].cmu()))
+
+        val path = db.getSaplingWitness(notes[0], 1u)
+        assert(path.position() == 0.toULong())
+        assert(path.authPath().map { it.node.toBytes() } == witness.path()!!.authPath().map { it.node.toBytes() })
+
+        try {
+            db.getSaplingWitness(notes[0], 0u)
+            assert(false)
+        } catch (e: ZcashException.Message) {}
+
+        // There is no checkpoint that deep yet
+        try {
+            db.getSaplingWitness(notes[0], 5u)
+            assert(false)
+        } catch (e: ZcashException.Message) {}
+
+        val pathAtHeight = db.getSaplingWitnessAtHeight(notes[0], ZcashBlockHeight(2030820u))
+        assert(pathAtHeight.authPath().map { it.node.toBytes() } == path.authPath().map { it.node.toBytes() })
+
+        // The wallet has no tree state for blocks it has not scanned
+        try {
+            db.getSaplingWitnessAtHeight(notes[0], ZcashBlockHeight(2030821u))
+            assert(false)
+        } catch (e: ZcashException.Message) {}
+    }
+
+    fun invoiceWallet(): Pair<ZcashWalletDb, TupleAccountIdAndUnifiedSpendingKey> {
+        val dir = kotlin.io.path.createTempDirectory().toFile()
+        val db = ZcashWalletDb.forPath(java.io.File(dir, "wallet.db").path, params)
+        val seed = supp.getAsU8Array("seed")
+        db.initialize(seed)
+        val treeState = ZcashTreeState("main", 2030819u, "02".repeat(32), 0u, "000000", "000000")
+        return Pair(db, db.createAccount(seed, ZcashAccountBirthday.fromTreestate(treeState, null)))
+    }
+
+    // Stores the block paying 200 zatoshis to the default address of the account
+    fun receiveInvoicePayment(db: ZcashWalletDb, account: TupleAccountIdAndUnifiedSpendingKey) {
+        val ufvk = account.unifiedSpendingKey.toUnifiedFullViewingKey()
+        val tx = ZcashTransaction.fromBytes(supp.getAsU8Array("transaction_sapling"), ZcashBranchId.NU5)
+        val prior = ZcashBlockMetadata.fromParts(ZcashBlockHeight(2030819u), ZcashBlockHash.fromSlice(List(32) { 2u }), 0u)
+        db.updateChainTip(2030820u)
+        db.putBlocks(listOf(scanBlock(params, compactBlock(tx, 2), mapOf(account.accountId to ufvk), listOf(), prior)))
+    }
+
+    fun testInvoiceCreation() {
+        val (db, account) = invoiceWallet()
+        val id = account.accountId
+        val expiresAt = System.currentTimeMillis().toULong() / 1000u + 3600u
+        val default = account.unifiedSpendingKey.toUnifiedFullViewingKey().defaultAddress()
+
+        val invoice = db.createInvoice(id, ZcashNonNegativeAmount.fromU64(300u), expiresAt, "coffee")
+        assert(invoice.account == id)
+        assert(invoice.amount.value() == 300.toULong())
+        assert(invoice.label == "coffee")
+        assert(invoice.expiresAt == expiresAt)
+        assert(invoice.createdAt < expiresAt)
+        assert(invoice.address.encode(params) != default.address.encode(params))
+        assert(invoice.address.encode(params) == db.getCurrentAddress(id)!!.encode(params))
+        assert(db.getTransparentReceivers(id).containsKey(invoice.address.transparent()!!.encode(params)))
+
+        assert(db.getInvoice(invoice.id)!!.address.encode(params) == invoice.address.encode(params))
+        assert(db.getInvoice(invoice.id + 1) == null)
+        try {
+            db.getInvoiceStatus(invoice.id + 1, expiresAt)
+            assert(false)
+        } catch (e: ZcashException.Message) {}
+        assert(db.getInvoices(id).map { it.id } == listOf(invoice.id))
+
+        val invoiceAt = db.createInvoiceAt(id, default.diversifierIndex, ZcashNonNegativeAmount.fromU64(100u), expiresAt, null)
+        assert(invoiceAt.address.encode(params) == default.address.encode(params))
+        assert(invoiceAt.label == null)
+        assert(db.getInvoices(id).map { it.id } == listOf(invoice.id, invoiceAt.id))
+
+        // Index 5 is valid, but past the current address of the wallet until it is issued
+        try {
+            db.createInvoiceAt(id, ZcashDiversifierIndex.fromU32(5u), ZcashNonNegativeAmount.fromU64(1u), expiresAt, null)
+            assert(false)
+        } catch (e: ZcashException.Message) {}
+        db.getNextAvailableAddress(id)
+        db.createInvoiceAt(id, ZcashDiversifierIndex.fromU32(5u), ZcashNonNegativeAmount.fromU64(1u), expiresAt, null)
+
+        // Index 0 has no valid Sapling diversifier
+        try {
+            db.createInvoiceAt(id, ZcashDiversifierIndex.fromU32(0u), ZcashNonNegativeAmount.fromU64(1u), expiresAt, null)
+            assert(false)
+        } catch (e: ZcashException.Message) {}
+        try {
+            db.createInvoiceAt(id, invoice.diversifierIndex, ZcashNonNegativeAmount.fromU64(1u), expiresAt, null)
+            assert(false)
+        } catch (e: ZcashException.Message) {}
+        try {
+            db.createInvoice(id, ZcashNonNegativeAmount.fromU64(1u), expiresAt - 7200u, null)
+            assert(false)
+        } catch (e: ZcashException.Message) {}
+
+        val uri = ZcashTransactionRequest.fromInvoice(invoice).toUri(params)!!
+        assert(uri.startsWith("zcash:" + invoice.address.encode(params)))
+        assert(uri.contains("amount=0.000003"))
+    }
+
+    fun testInvoiceStates() {
+        val now = System.currentTimeMillis().toULong() / 1000u
+        val expiresAt = now + 3600u
+        val expected = mapOf(
+            300uL to ZcashInvoiceState.PARTIALLY_PAID,
+            200uL to ZcashInvoiceState.PAID,
+            100uL to ZcashInvoiceState.OVERPAID,
+        )
+        for ((amount, paidState) in expected) {
+            val (db, account) = invoiceWallet()
+            val default = account.unifiedSpendingKey.toUnifiedFullViewingKey().defaultAddress()
+            val invoice = db.createInvoiceAt(account.accountId, default.diversifierIndex, ZcashNonNegativeAmount.fromU64(amount), expiresAt, null)
+
+            val unpaid = db.getInvoiceStatus(invoice.id, now)
+            assert(unpaid.state == ZcashInvoiceState.UNPAID)
+            assert(unpaid.received.value() == 0.toULong())
+            assert(db.getInvoiceStatus(invoice.id, expiresAt + 1u).state == ZcashInvoiceState.EXPIRED)
+
+            receiveInvoicePayment(db, account)
+            val paid = db.getInvoiceStatus(invoice.id, now)
+            assert(paid.state == paidState)
+            assert(paid.received.value() == 200.toULong())
+
+            // Only partially paid invoices expire
+            val afterExpiry = if (paidState == ZcashInvoiceState.PARTIALLY_PAID) ZcashInvoiceState.EXPIRED else paidState
+            assert(db.getInvoiceStatus(invoice.id, expiresAt + 1u).state == afterExpiry)
+        }
+
+    }
+
+    // Encodes the Orchard actions of a transaction as a compact block at `height`
+    fun orchardCompactBlock(tx: ZcashTransaction, height: Long): ZcashCompactBlock {
+        var compactTx = varintField(1, 0) + bytesField(2, tx.txid().toBytes())
+        for (action in tx.orchardBundle()!!.actions()) {
+            val note = action.encryptedNote()
+            val compactAction = bytesField(1, action.nullifier().toBytes()) +
+                bytesField(2, action.cmx().toBytes()) +
+                bytesField(3, note.epkBytes) +
+                bytesField(4, note.encCiphertext.take(52))
+            compactTx += bytesField(6, compactAction)
+        }
+        val block = varintField(2, height) +
+            bytesField(3, List(32) { 1u }) +
+            bytesField(4, List(32) { 2u }) +
+            varintField(5, 1700000000) +
+            bytesField(7, compactTx)
+        return ZcashCompactBlock.fromBytes(block)
+    }
+
+    fun testDiscoverOrchardAccount() {
+        val params = ZcashConsensusParameters.TEST_NETWORK
+        val seed = supp.getAsU8Array("testnet_wallet_seed")
+        val tx = ZcashTransaction.fromBytes(supp.getAsU8Array("testnet_transaction_orchard"), ZcashBranchId.NU5)
+        val block = orchardCompactBlock(tx, 2000010)
+
+        val dir = kotlin.io.path.createTempDirectory().toFile()
+        val cache = ZcashFsBlockDb.forPath(dir.path)
+        cache.initialize(dir.path)
+        val meta = ZcashBlockMeta(block.height(), block.hash(), block.time(), 0u, tx.orchardBundle()!!.actions().size.toUInt())
+        val blockFile = java.io.File(meta.blockFilePath(java.io.File(dir, "blocks").path))
+        blockFile.parentFile.mkdirs()
+        blockFile.writeBytes(block.toBytes().map { it.toByte() }.toByteArray())
+        cache.writeBlockMetadata(listOf(meta))
+
+        val db = ZcashWalletDb.forPath(java.io.File(dir, "wallet.db").path, params)
+        db.initialize(seed)
+        val treeState = ZcashTreeState("test", 2000000u, "00".repeat(32), 0u, "000000", "")
+        val birthday = ZcashAccountBirthday.fromTreestate(treeState, null)
+
+        // The only output of the block is an Orchard action to the change address of account 0
+        val accounts = db.discoverAccounts(seed, birthday, dir.path, block.height(), listOf(), 2u)
+        assert(accounts.map { it.accountId.id } == listOf(0u))
+        assert(db.getUnifiedFullViewingKeys().size == 1)
+    }
+
+    fun testOrchardWitness() {
+        val tx = ZcashTransaction.fromBytes(supp.getAsU8Array("testnet_transaction_orchard"), ZcashBranchId.NU5)
+        val actions = tx.orchardBundle()!!.actions()
+
+        val dir = kotlin.io.path.createTempDirectory().toFile()
+        val cache = ZcashFsBlockDb.forPath(dir.path)
+        cache.initialize(dir.path)
+        val metas = listOf(2000001L, 2000002L).map { height ->
+            val block = orchardCompactBlock(tx, height)
+            val meta = ZcashBlockMeta(block.height(), block.hash(), block.time(), 0u, actions.size.toUInt())
+            val blockFile = java.io.File(meta.blockFilePath(java.io.File(dir, "blocks").path))
+            blockFile.parentFile.mkdirs()
+            blockFile.writeBytes(block.toBytes().map { it.toByte() }.toByteArray())
+            meta
+        }
+        cache.writeBlockMetadata(metas)
+
+        // The note is in the first block, the anchor is at the end of the second one
+        val treeState = ZcashTreeState("test", 2000000u, "00".repeat(32), 0u, "000000", "")
+        val cmx = actions[0].cmx()
+        val path = cache.getOrchardWitness(treeState, cmx, ZcashBlockHeight(2000002u))
+
+        val tree = ZcashOrchardCommitmentTree.empty()
+        for (action in actions + actions) {
+            tree.append(ZcashOrchardMerkleHash.fromCmx(action.cmx()))
+        }
+        assert(path.root(cmx).toBytes() == tree.root().toBytes())
+
+        // The blocks after the anchor must be cached
+        try {
+            cache.getOrchardWitness(treeState, cmx, ZcashBlockHeight(2000003u))
+            assert(false)
+        } catch (e: ZcashException.Message) {}
+
+        // The anchor must be after the tree state
+        try {
+            cache.getOrchardWitness(treeState, cmx, ZcashBlockHeight(2000000u))
+            assert(false)
+        } catch (e: ZcashException.Message) {}
+    }
+
+    fun execute(){
+        testScanBlock()
+        testScanBlockWithIvks()
+        testOrchardWitness()
+        testScannedNoteWitness()
+        testInvoiceCreation()
+        testInvoiceStates()
+        testDiscoverOrchardAccount()
     }
 }
-TransactionExplorationTest(supp).execute()
\ No newline at end of file
+CompactBlockScanningTest(supp).execute()